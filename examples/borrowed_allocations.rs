@@ -0,0 +1,81 @@
+//! Counts the allocations of an owned and a borrowed decode of the same map
+//!
+//! Usage: cargo run --release --example borrowed_allocations
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use cairn::{decode_map_borrowed, encode_map_to_vec, DecodedElement, Decoder};
+
+const ROOMS: usize = 200;
+const ENTITIES: usize = 50;
+
+/// Forwards to the system allocator, counting calls and bytes
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Runs `f` once, printing its allocation count, allocated bytes and time
+fn measure<T>(label: &str, f: impl FnOnce() -> T) -> T {
+    let (allocations, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), BYTES.load(Ordering::Relaxed));
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<9} {:>8} allocations, {:>9} bytes, {:?}",
+        label,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        BYTES.load(Ordering::Relaxed) - bytes,
+        elapsed,
+    );
+    result
+}
+
+fn main() -> std::io::Result<()> {
+    let mut levels = DecodedElement::new("levels");
+    for room in 0..ROOMS {
+        let mut entities = DecodedElement::new("entities");
+        for i in 0..ENTITIES {
+            let mut spinner = DecodedElement::new("spinner");
+            spinner.set_attribute("id", (room * ENTITIES + i) as i64);
+            spinner.set_attribute("x", (i * 8) as i64);
+            spinner.set_attribute("y", 64);
+            spinner.set_attribute("color", "Blue");
+            entities.add_child(spinner);
+        }
+
+        let mut level = DecodedElement::new("level");
+        level.set_attribute("name", format!("room-{:03}", room));
+        level.add_child(entities);
+        levels.add_child(level);
+    }
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "borrowed_allocations");
+    map.add_child(levels);
+
+    let bytes = encode_map_to_vec(&map)?;
+    println!("{} bytes, {} elements", bytes.len(), map.element_count());
+
+    measure("owned", || Decoder::new().decode_from_reader(&bytes[..]))?;
+    measure("borrowed", || decode_map_borrowed(&bytes))?;
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,53 @@
+//! Times decoding a synthetic map file with different read buffer sizes
+//!
+//! Usage: cargo run --release --example buffer_size
+
+use std::time::Instant;
+
+use cairn::{encode_map, DecodedElement, Decoder, DEFAULT_BUFFER_SIZE};
+
+const ROOMS: usize = 400;
+const ENTITIES: usize = 100;
+const RUNS: u32 = 10;
+const SIZES: [usize; 5] = [512, DEFAULT_BUFFER_SIZE, 64 * 1024, 256 * 1024, 1024 * 1024];
+
+fn main() -> std::io::Result<()> {
+    let mut levels = DecodedElement::new("levels");
+    for room in 0..ROOMS {
+        let mut entities = DecodedElement::new("entities");
+        for i in 0..ENTITIES {
+            let mut spinner = DecodedElement::new("spinner");
+            spinner.set_attribute("id", (room * ENTITIES + i) as i64);
+            spinner.set_attribute("x", (i * 8) as i64);
+            spinner.set_attribute("y", 64);
+            spinner.set_attribute("color", "Blue");
+            entities.add_child(spinner);
+        }
+        let mut level = DecodedElement::new("level");
+        level.set_attribute("name", format!("room-{:03}", room));
+        level.add_child(entities);
+        levels.add_child(level);
+    }
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "buffer_size");
+    map.add_child(levels);
+
+    let bin = std::env::temp_dir().join(format!("cairn-buffer-size-{}.bin", std::process::id()));
+    encode_map(&map, &bin)?;
+    println!("{} bytes, {} elements", std::fs::metadata(&bin)?.len(), map.element_count());
+
+    for size in SIZES {
+        let decoder = Decoder::new().buffer_size(size);
+        decoder.decode(&bin)?;
+
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            decoder.decode(&bin)?;
+        }
+        println!("{:>8} byte buffer: {:?} per run", size, start.elapsed() / RUNS);
+    }
+
+    std::fs::remove_file(&bin)?;
+
+    Ok(())
+}
\ No newline at end of file
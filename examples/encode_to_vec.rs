@@ -0,0 +1,91 @@
+//! Compares `Encoder::encode_to_vec` with encoding into a growing `Vec`
+//!
+//! The pre-sized vector is never reallocated, at the cost of a counting
+//! pass over the map; the growing one is reallocated as it doubles.
+//!
+//! Usage: cargo run --release --example encode_to_vec
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use cairn::{DecodedElement, Encoder};
+
+const ROOMS: usize = 400;
+const ENTITIES: usize = 100;
+const RUNS: u32 = 10;
+
+/// Forwards to the system allocator, counting reallocations and the bytes
+/// they copy
+struct Counting;
+
+static REALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static COPIED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        REALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        COPIED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Runs `f` once for its reallocations, then `RUNS` times for its average time
+fn measure<T>(label: &str, f: impl Fn() -> T) -> T {
+    let (reallocations, copied) = (REALLOCATIONS.load(Ordering::Relaxed), COPIED.load(Ordering::Relaxed));
+    let result = f();
+    let reallocations = REALLOCATIONS.load(Ordering::Relaxed) - reallocations;
+    let copied = COPIED.load(Ordering::Relaxed) - copied;
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        f();
+    }
+
+    println!("{:<6} {:>3} reallocations, {:>8} bytes copied, {:?} per run", label, reallocations, copied, start.elapsed() / RUNS);
+    result
+}
+
+fn main() -> std::io::Result<()> {
+    let mut levels = DecodedElement::new("levels");
+    for room in 0..ROOMS {
+        let mut entities = DecodedElement::new("entities");
+        for i in 0..ENTITIES {
+            let mut spinner = DecodedElement::new("spinner");
+            spinner.set_attribute("id", (room * ENTITIES + i) as i64);
+            spinner.set_attribute("x", (i * 8) as i64);
+            spinner.set_attribute("y", 64);
+            spinner.set_attribute("color", "Blue");
+            entities.add_child(spinner);
+        }
+
+        let mut level = DecodedElement::new("level");
+        level.set_attribute("name", format!("room-{:03}", room));
+        level.add_child(entities);
+        levels.add_child(level);
+    }
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "encode_to_vec");
+    map.add_child(levels);
+
+    let encoder = Encoder::new();
+    let sized = measure("sized", || encoder.encode_to_vec(&map))?;
+    let grown = measure("grown", || {
+        let mut bytes = Vec::new();
+        encoder.encode_to_writer(&map, &mut bytes).map(|_| bytes)
+    })?;
+    println!("{} bytes; capacity {} sized, {} grown", sized.len(), sized.capacity(), grown.capacity());
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,52 @@
+//! Decodes a map and prints its rooms with their bounds and most common entities
+//!
+//! Usage: cargo run --example inspect -- path/to/map.bin
+
+use std::collections::HashMap;
+
+use cairn::{decode_map, DecodedElement};
+
+fn number(element: &DecodedElement, key: &str) -> f64 {
+    element.attributes.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0)
+}
+
+fn main() -> std::io::Result<()> {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: inspect <map.bin>");
+        std::process::exit(2);
+    };
+
+    let map = decode_map(&path)?;
+    let package = map.attributes.get("package").and_then(|v| v.as_str()).unwrap_or("");
+    println!("{} ({})", path, package);
+
+    let rooms = map.find_child("levels").and_then(|levels| levels.children.as_deref()).unwrap_or(&[]);
+
+    for room in rooms {
+        let name = room.attributes.get("name").and_then(|v| v.as_str()).unwrap_or("<unnamed>");
+        println!(
+            "{}  x={} y={} {}x{}",
+            name,
+            number(room, "x"),
+            number(room, "y"),
+            number(room, "width"),
+            number(room, "height"),
+        );
+
+        let entities = room.find_child("entities").and_then(|e| e.children.as_deref()).unwrap_or(&[]);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for entity in entities {
+            *counts.entry(entity.name.as_str()).or_default() += 1;
+        }
+
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        for (entity, count) in counts.into_iter().take(5) {
+            println!("    {:>4}  {}", count, entity);
+        }
+    }
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,40 @@
+//! Times decoding a synthetic map whose lookup table has 50,000 entries
+//!
+//! Usage: cargo run --release --example large_lookup
+
+use std::time::Instant;
+
+use cairn::{count_elements, encode_map_to_vec, DecodedElement, Decoder};
+
+const STRINGS: usize = 50_000;
+const RUNS: u32 = 20;
+
+fn main() -> std::io::Result<()> {
+    // One decal per string, so every lookup entry is referenced once
+    let mut decals = DecodedElement::new("fgdecals");
+    for i in 0..STRINGS {
+        let mut decal = DecodedElement::new("decal");
+        decal.set_attribute("texture", format!("mods/generated/decal_{:05}", i));
+        decals.add_child(decal);
+    }
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "large_lookup");
+    map.add_child(decals);
+
+    let bytes = encode_map_to_vec(&map)?;
+    println!("{} bytes, {} lookup strings", bytes.len(), STRINGS);
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        Decoder::new().decode_from_reader(&bytes[..])?;
+    }
+    println!("decode:         {:?} per run", start.elapsed() / RUNS);
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        count_elements(&bytes[..])?;
+    }
+    println!("count_elements: {:?} per run", start.elapsed() / RUNS);
+
+    Ok(())
+}
\ No newline at end of file
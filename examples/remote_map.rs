@@ -0,0 +1,58 @@
+//! Decodes a map straight from an HTTP URL, without saving it to disk
+//!
+//! Usage: cargo run --example remote_map -- http://host[:port]/path/map.bin
+//!
+//! Only plain HTTP is spoken here, to stay dependency-free. For HTTPS, pass
+//! the body reader of any HTTP client (for example `ureq`'s
+//! `response.into_reader()`) to `decode_map_from_reader` the same way.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+use cairn::decode_map_from_reader;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Sends a GET request and returns the stream positioned at the body
+fn get(url: &str) -> io::Result<BufReader<TcpStream>> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| invalid(format!("Not an http:// URL: {}", url)))?;
+    let (host, path) = rest.split_once('/').map_or((rest, "/".to_string()), |(host, path)| (host, format!("/{}", path)));
+    let address = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+
+    let mut stream = TcpStream::connect(address)?;
+    // HTTP/1.0 keeps the body free of chunked encoding and ends it by closing
+    write!(stream, "GET {} HTTP/1.0\r\nHost: {}\r\nAccept-Encoding: identity\r\n\r\n", path, host)?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(io::Error::new(io::ErrorKind::Other, format!("Request failed: {}", status.trim_end())));
+    }
+
+    // Skip the headers, up to the empty line
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    Ok(reader)
+}
+
+fn main() -> io::Result<()> {
+    let Some(url) = std::env::args().nth(1) else {
+        eprintln!("Usage: remote_map <http://host/path/map.bin>");
+        std::process::exit(2);
+    };
+
+    // Gzip-compressed maps (.bin.gz) are unpacked transparently
+    let map = decode_map_from_reader(get(&url)?)?;
+
+    let package = map.get_str("package").unwrap_or_default();
+    let rooms = map.find_child("levels").and_then(|levels| levels.children.as_ref()).map_or(0, Vec::len);
+    println!("{}: package {:?}, {} rooms, {} elements", url, package, rooms, map.element_count());
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,87 @@
+//! Compares the peak memory of the tree and streaming conversions, both ways
+//!
+//! Usage: cargo run --release --example streaming_memory
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use cairn::{bin_to_json, bin_to_json_streaming, encode_map, json_to_bin, json_to_bin_streaming, DecodedElement};
+
+const ROOMS: usize = 400;
+const ENTITIES: usize = 100;
+
+/// Forwards to the system allocator, tracking live and peak bytes
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(live, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Runs `f` once, printing the peak bytes it held above the starting point and its time
+fn measure<T>(label: &str, f: impl FnOnce() -> T) -> T {
+    let base = LIVE.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    println!("{:<19} {:>10} bytes peak, {:?}", label, PEAK.load(Ordering::Relaxed) - base, elapsed);
+    result
+}
+
+fn main() -> std::io::Result<()> {
+    let mut levels = DecodedElement::new("levels");
+    for room in 0..ROOMS {
+        let mut entities = DecodedElement::new("entities");
+        for i in 0..ENTITIES {
+            let mut spinner = DecodedElement::new("spinner");
+            spinner.set_attribute("id", (room * ENTITIES + i) as i64);
+            spinner.set_attribute("x", (i * 8) as i64);
+            spinner.set_attribute("y", 64);
+            spinner.set_attribute("color", "Blue");
+            entities.add_child(spinner);
+        }
+
+        let mut level = DecodedElement::new("level");
+        level.set_attribute("name", format!("room-{:03}", room));
+        level.add_child(entities);
+        levels.add_child(level);
+    }
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "streaming_memory");
+    map.add_child(levels);
+
+    let dir = std::env::temp_dir();
+    let bin = dir.join(format!("cairn-streaming-memory-{}.bin", std::process::id()));
+    let json = dir.join(format!("cairn-streaming-memory-{}.json", std::process::id()));
+
+    encode_map(&map, &bin)?;
+    println!("{} bytes, {} elements", std::fs::metadata(&bin)?.len(), map.element_count());
+    drop(map);
+
+    measure("bin2json tree", || bin_to_json(&bin, &json))?;
+    measure("bin2json streaming", || bin_to_json_streaming(&bin, &json))?;
+    measure("json2bin tree", || json_to_bin(&json, &bin))?;
+    measure("json2bin streaming", || json_to_bin_streaming(&json, &bin))?;
+
+    std::fs::remove_file(&bin)?;
+    std::fs::remove_file(&json)?;
+
+    Ok(())
+}
\ No newline at end of file
@@ -1,27 +1,71 @@
-use serde_json::{json, Value};
+use serde_json::{json, Number, Value};
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::io::{self, Read, Write};
 
+use crate::decoder::DEFAULT_MAGIC;
 use crate::element::DecodedElement;
+use crate::fields::{FieldType, FieldTypes};
+use crate::index::ElementOffsets;
+use crate::order::{canonical_children, entity_order, SORTABLE_CONTAINERS};
 
-/// Read variable-length integer from byte stream
-pub fn read_var_length<R: Read>(reader: &mut R) -> io::Result<u32> {
-    let mut result = 0;
-    let mut count = 0;
+/// Reader adapter that counts the bytes consumed so far
+pub struct PositionReader<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: Read> PositionReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, position: 0 }
+    }
     
-    loop {
-        let mut byte = [0u8; 1];
-        reader.read_exact(&mut byte)?;
-        
-        result += ((byte[0] & 0x7F) as u32) << (count * 7);
-        count += 1;
-        
-        if (byte[0] >> 7) == 0 {
-            break;
-        }
+    /// Wraps a reader that is already `position` bytes into the stream
+    pub fn starting_at(inner: R, position: u64) -> Self {
+        Self { inner, position }
     }
     
-    Ok(result)
+    /// Number of bytes read from the underlying reader
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+impl<R: Read> Read for PositionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+/// Writer that discards its input and only counts the bytes
+#[derive(Debug, Default)]
+pub struct CountingWriter {
+    count: u64,
+}
+
+impl CountingWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Number of bytes written so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len() as u64;
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 /// Write variable-length integer to byte stream
@@ -38,51 +82,403 @@ pub fn write_var_length<W: Write>(writer: &mut W, mut n: u32) -> io::Result<()>
     writer.write_all(&bytes)
 }
 
-/// Read string from byte stream
-pub fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
-    let length = read_var_length(reader)? as usize;
-    let mut bytes = vec![0u8; length];
-    reader.read_exact(&mut bytes)?;
-    
-    String::from_utf8(bytes)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
-}
-
 /// Write string to byte stream
 pub fn write_string<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
     write_var_length(writer, s.len() as u32)?;
     writer.write_all(s.as_bytes())
 }
 
-/// Read run-length encoded string from byte stream
-pub fn read_run_length_encoded<R: Read>(reader: &mut R) -> io::Result<String> {
-    let mut byte_count = [0u8; 2];
-    reader.read_exact(&mut byte_count)?;
-    let byte_count = u16::from_le_bytes(byte_count) as usize;
+/// Error for a field that needs more bytes than the input has left
+fn truncated(needed: u64, remaining: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("File truncated: expected at least {} more bytes, found {}", needed, remaining),
+    )
+}
+
+/// Checks that string bytes read from the input are UTF-8
+fn utf8(bytes: &[u8]) -> io::Result<&str> {
+    std::str::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Input the decoders read the binary format from
+///
+/// Every field layout is parsed once, against this trait: [`SliceSource`]
+/// lends strings straight out of a map held in memory, [`StreamSource`]
+/// copies them out of a reader into a reused buffer.
+pub trait Source {
+    type Bytes<'s>: AsRef<[u8]>
+    where
+        Self: 's;
+    type Str<'s>: AsRef<str>
+    where
+        Self: 's;
+    
+    /// Number of bytes left in the input, when known
+    fn remaining(&self) -> Option<u64>;
+    
+    /// Reads the next `n` bytes
+    fn bytes(&mut self, n: usize) -> io::Result<Self::Bytes<'_>>;
+    
+    /// Reads the next `n` bytes, which must be UTF-8
+    fn str(&mut self, n: usize) -> io::Result<Self::Str<'_>>;
+    
+    /// Reads the next `N` bytes into an array
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]>;
+    
+    /// Errors early when fewer than `needed` bytes are known to be left
+    fn ensure_remaining(&self, needed: u64) -> io::Result<()> {
+        match self.remaining() {
+            Some(remaining) if remaining < needed => Err(truncated(needed, remaining)),
+            _ => Ok(()),
+        }
+    }
+    
+    fn u8(&mut self) -> io::Result<u8> {
+        self.array().map(|[byte]| byte)
+    }
+    
+    fn u16(&mut self) -> io::Result<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+    
+    /// Reads a variable-length integer
+    fn var_length(&mut self) -> io::Result<u32> {
+        let first = self.u8()?;
+        var_length_from(self, first)
+    }
+    
+    /// Reads a string with a variable-length prefix
+    fn string(&mut self) -> io::Result<Self::Str<'_>> {
+        let length = self.var_length()?;
+        self.str(length as usize)
+    }
+}
+
+/// Finishes a variable-length integer whose first byte was already read
+fn var_length_from<S: Source + ?Sized>(source: &mut S, first: u8) -> io::Result<u32> {
+    let mut byte = first;
+    let mut result = 0;
     
-    let mut data = vec![0u8; byte_count];
-    reader.read_exact(&mut data)?;
+    // A u32 takes at most five bytes of seven bits
+    for shift in (0..32).step_by(7) {
+        result |= ((byte & 0x7F) as u32) << shift;
+        
+        if (byte >> 7) == 0 {
+            return Ok(result);
+        }
+        byte = source.u8()?;
+    }
     
-    let mut result = String::new();
+    Err(io::Error::new(io::ErrorKind::InvalidData, "Variable-length integer longer than five bytes"))
+}
+
+/// [`Source`] over a map held in memory
+pub struct SliceSource<'a> {
+    input: &'a [u8],
+    rest: &'a [u8],
+}
+
+impl<'a> SliceSource<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self { input, rest: input }
+    }
     
-    for i in (0..byte_count).step_by(2) {
-        let times = data[i] as usize;
-        let character = data[i + 1] as char;
-        result.push_str(&character.to_string().repeat(times));
+    /// Offset of the next byte from the start of the input
+    pub fn position(&self) -> u64 {
+        (self.input.len() - self.rest.len()) as u64
     }
     
-    Ok(result)
+    /// The bytes not read yet
+    pub fn rest(&self) -> &'a [u8] {
+        self.rest
+    }
+}
+
+impl<'a> Source for SliceSource<'a> {
+    type Bytes<'s> = &'a [u8] where Self: 's;
+    type Str<'s> = &'a str where Self: 's;
+    
+    fn remaining(&self) -> Option<u64> {
+        Some(self.rest.len() as u64)
+    }
+    
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        self.ensure_remaining(n as u64)?;
+        
+        let (bytes, rest) = self.rest.split_at(n);
+        self.rest = rest;
+        Ok(bytes)
+    }
+    
+    fn str(&mut self, n: usize) -> io::Result<&'a str> {
+        utf8(self.bytes(n)?)
+    }
+    
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+}
+
+/// [`Source`] over a reader, copying each string into a reused buffer
+pub struct StreamSource<'r, R> {
+    reader: &'r mut R,
+    buffer: &'r mut Vec<u8>,
+    remaining: Option<u64>,
+}
+
+impl<'r, R: Read> StreamSource<'r, R> {
+    pub fn new(reader: &'r mut R, buffer: &'r mut Vec<u8>) -> Self {
+        Self { reader, buffer, remaining: None }
+    }
+    
+    /// Fails reads early that need more than `remaining` bytes, when known
+    pub fn limit(mut self, remaining: Option<u64>) -> Self {
+        self.remaining = remaining;
+        self
+    }
+    
+    pub fn reader(&self) -> &R {
+        self.reader
+    }
+    
+    fn consume(&mut self, n: usize) {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(n as u64);
+        }
+    }
+}
+
+impl<R: Read> Source for StreamSource<'_, R> {
+    type Bytes<'s> = &'s [u8] where Self: 's;
+    type Str<'s> = &'s str where Self: 's;
+    
+    fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+    
+    fn bytes(&mut self, n: usize) -> io::Result<&[u8]> {
+        self.ensure_remaining(n as u64)?;
+        
+        // The buffer grows with the bytes actually read, so a corrupt length
+        // cannot allocate more than the input holds
+        self.buffer.clear();
+        let read = (&mut *self.reader).take(n as u64).read_to_end(self.buffer)?;
+        if read < n {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        
+        self.consume(n);
+        Ok(self.buffer)
+    }
+    
+    fn str(&mut self, n: usize) -> io::Result<&str> {
+        utf8(self.bytes(n)?)
+    }
+    
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        self.ensure_remaining(N as u64)?;
+        
+        let mut array = [0u8; N];
+        self.reader.read_exact(&mut array)?;
+        
+        self.consume(N);
+        Ok(array)
+    }
+}
+
+/// Format variations a decode accepts
+#[derive(Debug, Clone, Copy)]
+pub struct Format<'a> {
+    /// Header string the file must start with
+    pub magic: &'a str,
+    /// Skip a byte order mark or whitespace in front of the header
+    pub skip_leading_bytes: bool,
+    /// Read [`DOUBLE_TYPE_CODE`] values instead of failing on them
+    pub double_precision: bool,
+}
+
+impl Default for Format<'_> {
+    fn default() -> Self {
+        Self { magic: DEFAULT_MAGIC, skip_leading_bytes: false, double_precision: false }
+    }
+}
+
+/// Bytes that stray exports leave before the header: a UTF-8 byte order
+/// mark and ASCII whitespace
+const LEADING_JUNK: &[u8] = &[0xEF, 0xBB, 0xBF, b' ', b'\t', b'\r', b'\n'];
+
+/// Error for a file whose header is not `magic`
+fn header_mismatch(found: Option<&str>, magic: &str) -> io::Error {
+    let message = match found {
+        _ if magic == DEFAULT_MAGIC => "Invalid Celeste map file".to_string(),
+        Some(found) => format!("Invalid header {:?}, expected {:?}", found, magic),
+        None => format!("Invalid header, expected {:?}", magic),
+    };
+    
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read the header string and check it against `format`
+///
+/// A BOM or whitespace in front of the header would otherwise be taken for
+/// the length prefix. When the header follows such bytes, they are skipped
+/// if `format.skip_leading_bytes` is set and reported as an error otherwise.
+pub fn read_magic<S: Source>(source: &mut S, format: &Format) -> io::Result<()> {
+    let mut prefix = Vec::new();
+    write_var_length(&mut prefix, format.magic.len() as u32)?;
+    
+    let mut first = source.u8()?;
+    
+    let mut skipped = 0;
+    while first != prefix[0] && LEADING_JUNK.contains(&first) {
+        first = source.u8()?;
+        skipped += 1;
+    }
+    
+    // Only junk followed by the real header counts as leading bytes
+    if skipped > 0 && first != prefix[0] {
+        return Err(header_mismatch(None, format.magic));
+    }
+    if skipped > 0 && !format.skip_leading_bytes {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unexpected {} leading bytes before the header", skipped),
+        ));
+    }
+    
+    let length = var_length_from(source, first)?;
+    let header = source.bytes(length as usize)?;
+    if header.as_ref() != format.magic.as_bytes() {
+        return Err(header_mismatch(std::str::from_utf8(header.as_ref()).ok(), format.magic));
+    }
+    
+    Ok(())
+}
+
+/// Read the size of the lookup table that follows the package
+///
+/// Every lookup string takes at least one byte and `root_size` more bytes
+/// must follow the table, so a table too large for the input fails here
+/// rather than after reading it.
+pub fn read_lookup_len<S: Source>(source: &mut S, root_size: u64) -> io::Result<usize> {
+    let lookup_len = source.u16()?;
+    source.ensure_remaining(lookup_len as u64 + root_size)?;
+    Ok(lookup_len as usize)
+}
+
+/// One attribute value as stored, before any lookup is resolved
+///
+/// Returned by [`read_value`]; each decoder turns it into its own value type.
+pub enum RawValue<S, B> {
+    Bool(bool),
+    /// Any of the u8, i16 and i32 encodings
+    Int(i32),
+    Float(f32),
+    Double(f64),
+    /// Lookup table index, not checked against the table yet
+    Lookup(usize),
+    Str(S),
+    /// Run-length encoded string as its raw (count, byte) pairs
+    RunLength(B),
+}
+
+impl<S: AsRef<str>, B: AsRef<[u8]>> RawValue<S, B> {
+    /// The JSON value `decode_map` gives, resolving lookup indices with `resolve`
+    pub fn into_json(self, resolve: impl FnOnce(usize) -> io::Result<String>) -> io::Result<Value> {
+        let value = match self {
+            RawValue::Bool(b) => Value::Bool(b),
+            RawValue::Int(i) => Value::from(i),
+            // Handle JSON serialization of floating point values
+            RawValue::Float(f) if f.is_finite() => json!(f),
+            RawValue::Double(f) if f.is_finite() => json!(f),
+            RawValue::Float(_) | RawValue::Double(_) => Value::Null,
+            RawValue::Lookup(index) => Value::String(resolve(index)?),
+            RawValue::Str(s) => Value::String(s.as_ref().to_string()),
+            RawValue::RunLength(runs) => {
+                let mut s = String::new();
+                expand_run_length(runs.as_ref(), &mut s);
+                Value::String(s)
+            }
+        };
+        
+        Ok(value)
+    }
+}
+
+/// Read the value that follows a type byte
+///
+/// Inline strings must be UTF-8 and run-length data whole (count, byte)
+/// pairs; lookup indices are left for the caller to check. The non-vanilla
+/// [`DOUBLE_TYPE_CODE`] is an error unless `double_precision` is set.
+pub fn read_value<S: Source>(
+    source: &mut S,
+    type_byte: u8,
+    double_precision: bool,
+) -> io::Result<RawValue<S::Str<'_>, S::Bytes<'_>>> {
+    let value = match type_byte {
+        0 => RawValue::Bool(source.u8()? != 0),
+        1 => RawValue::Int(source.u8()? as i32),
+        2 => RawValue::Int(i16::from_le_bytes(source.array()?) as i32),
+        3 => RawValue::Int(i32::from_le_bytes(source.array()?)),
+        4 => RawValue::Float(f32::from_le_bytes(source.array()?)),
+        5 => RawValue::Lookup(source.u16()? as usize),
+        6 => RawValue::Str(source.string()?),
+        7 => {
+            let byte_count = source.u16()? as usize;
+            let runs = source.bytes(byte_count)?;
+            
+            // Runs are (count, byte) pairs, so an odd length means corrupt data
+            if byte_count % 2 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Run-length encoded string has odd byte count {}", byte_count),
+                ));
+            }
+            RawValue::RunLength(runs)
+        }
+        DOUBLE_TYPE_CODE if double_precision => RawValue::Double(f64::from_le_bytes(source.array()?)),
+        DOUBLE_TYPE_CODE => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Non-vanilla value type 8 (f64), which Celeste cannot load; enable Decoder::double_precision to read it",
+            ));
+        }
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid value type")),
+    };
+    
+    Ok(value)
+}
+
+/// Append the characters of run-length data to `out`
+///
+/// Each run is a count and a byte, which stands for the character with that
+/// code point, so 0x80-0xFF give U+0080-U+00FF as Celeste reads them. Runs
+/// with a count of zero add nothing.
+pub fn expand_run_length(runs: &[u8], out: &mut String) {
+    for pair in runs.chunks_exact(2) {
+        out.extend(std::iter::repeat(pair[1] as char).take(pair[0] as usize));
+    }
+}
+
+/// Number of runs with a count of zero in run-length data
+fn zero_runs(runs: &[u8]) -> usize {
+    runs.chunks_exact(2).filter(|pair| pair[0] == 0).count()
 }
 
 /// Encode string using run-length encoding
+///
+/// Characters are written as their code point, the inverse of
+/// `read_run_length_encoded`, so only strings within U+0000-U+00FF can be
+/// encoded. Runs longer than 255 are split, and zero-count runs are never
+/// written.
 pub fn encode_run_length(s: &str) -> Option<Vec<u8>> {
-    // Only allow run length encoding if the string contains only single-byte characters
-    if s.chars().any(|c| c as u32 > 0xFF) {
-        return None;
-    }
+    // Only allow run length encoding if every character fits in one byte
+    let bytes: Vec<u8> = s.chars().map(|c| u8::try_from(c).ok()).collect::<Option<_>>()?;
     
     let mut result = Vec::new();
-    let bytes = s.as_bytes();
     
     if bytes.is_empty() {
         return Some(result);
@@ -110,108 +506,178 @@ pub fn encode_run_length(s: &str) -> Option<Vec<u8>> {
 }
 
 /// Decode value from byte stream based on type code
+///
+/// Reads exactly the bytes of one attribute value, the part that follows
+/// the type byte in the file, and leaves the reader right after it. Type 5
+/// resolves its index in `lookup`; the other types ignore it. Non-finite
+/// floats decode as `null`. Besides the vanilla types 0 to 7, the
+/// non-vanilla [`DOUBLE_TYPE_CODE`] is read as an f64, unlike in a map
+/// decode where it takes `Decoder::double_precision`. Useful on its own to
+/// probe a few bytes:
+/// `decode_value(2, &[], &mut &[0xff, 0xff][..])` gives `-1`.
 pub fn decode_value<R: Read>(type_byte: u8, lookup: &[String], reader: &mut R) -> io::Result<Value> {
-    match type_byte {
-        0 => {
-            let mut value = [0u8; 1];
-            reader.read_exact(&mut value)?;
-            Ok(Value::Bool(value[0] != 0))
-        }
-        1 => {
-            let mut value = [0u8; 1];
-            reader.read_exact(&mut value)?;
-            Ok(Value::Number(value[0].into()))
-        }
-        2 => {
-            let mut value = [0u8; 2];
-            reader.read_exact(&mut value)?;
-            Ok(Value::Number(i16::from_le_bytes(value).into()))
-        }
-        3 => {
-            let mut value = [0u8; 4];
-            reader.read_exact(&mut value)?;
-            Ok(Value::Number(i32::from_le_bytes(value).into()))
-        }
-        4 => {
-            let mut value = [0u8; 4];
-            reader.read_exact(&mut value)?;
-            let float = f32::from_le_bytes(value);
-            
-            // Handle JSON serialization of floating point values
-            if float.is_finite() {
-                Ok(json!(float))
-            } else {
-                Ok(Value::Null)
-            }
+    let mut buffer = Vec::new();
+    let mut source = StreamSource::new(reader, &mut buffer);
+    let value = read_value(&mut source, type_byte, true)?;
+    
+    value.into_json(|index| {
+        check_lookup_index(lookup.len(), index, "lookup")?;
+        Ok(lookup[index].clone())
+    })
+}
+
+/// Pick the binary type code for a JSON number
+///
+/// The smallest type that holds the value exactly wins, tried in this order:
+///
+/// 1. `1` (u8) for non-negative integers up to 255
+/// 2. `2` (i16) for integers in -32768..=32767, which covers all small negatives
+/// 3. `3` (i32) for the remaining integers that fit in 32 bits
+/// 4. `4` (f32) for everything else within f32 range
+///
+/// Integer-ness is taken from the JSON representation, not the value: `3.0`
+/// is a float in JSON and is stored as an f32, while `3` is stored as a u8.
+/// Integers outside the i32 range fall through to f32 and lose precision.
+/// Returns `None` when the value is outside f32 range.
+pub fn number_type_code(n: &Number) -> Option<u8> {
+    if n.as_u64().is_some_and(|n| u8::try_from(n).is_ok()) {
+        Some(1)
+    } else if n.as_i64().is_some_and(|n| i16::try_from(n).is_ok()) {
+        Some(2)
+    } else if n.as_i64().is_some_and(|n| i32::try_from(n).is_ok()) {
+        Some(3)
+    } else if n.as_f64().is_some_and(|n| n >= f32::MIN as f64 && n <= f32::MAX as f64) {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// Type code of an 8-byte f64, written only with `Encoder::double_precision`
+///
+/// Not part of the vanilla format: Celeste fails to load maps containing
+/// it. Cairn's decoders reject it too unless `Decoder::double_precision` is
+/// set, and then read it back as a float.
+pub const DOUBLE_TYPE_CODE: u8 = 8;
+
+/// Run-length encoding is used when it is at least this many bytes shorter
+pub const DEFAULT_RLE_MIN_SAVINGS: usize = 1;
+
+/// Settings shared by every element written during one encode
+pub struct EncodeContext<'a> {
+    pub lookup: &'a HashMap<String, usize>,
+    pub field_types: Option<&'a FieldTypes>,
+    /// Reorder known children into the sequence Celeste writes them in
+    pub canonical_child_order: bool,
+    /// Sort the children of entity and trigger containers
+    pub sort_entities: bool,
+    /// Fail on `null` attributes instead of silently dropping them
+    pub strict_nulls: bool,
+    /// Bytes run-length encoding must save over a raw string to be used;
+    /// `None` disables it
+    pub rle_min_savings: Option<usize>,
+    /// Write `__`-prefixed attributes instead of dropping them
+    pub preserve_underscore_attrs: bool,
+    /// Children to leave out of the output, together with their subtrees
+    pub skip_elements: Option<&'a dyn Fn(&DecodedElement) -> bool>,
+    /// Write floats that lose precision as f32 with [`DOUBLE_TYPE_CODE`]
+    pub double_precision: bool,
+}
+
+impl<'a> EncodeContext<'a> {
+    pub fn new(lookup: &'a HashMap<String, usize>) -> Self {
+        Self {
+            lookup,
+            field_types: None,
+            canonical_child_order: false,
+            sort_entities: false,
+            strict_nulls: false,
+            rle_min_savings: Some(DEFAULT_RLE_MIN_SAVINGS),
+            preserve_underscore_attrs: false,
+            skip_elements: None,
+            double_precision: false,
         }
-        5 => {
-            let mut index = [0u8; 2];
-            reader.read_exact(&mut index)?;
-            let index = u16::from_le_bytes(index) as usize;
+    }
+}
+
+/// Pick the type code for a number, honoring a forced field type
+fn field_number_type_code(key: &str, n: &Number, field_type: Option<FieldType>) -> io::Result<u8> {
+    let out_of_range = |range: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Attribute {} value {} is outside the {} range", key, n, range),
+        )
+    };
+    
+    match field_type {
+        None => number_type_code(n).ok_or_else(|| out_of_range("32-bit float")),
+        Some(FieldType::Float) => match number_type_code(n) {
+            Some(_) => Ok(4),
+            None => Err(out_of_range("32-bit float")),
+        },
+        Some(FieldType::Integer) => {
+            let value = n.as_f64().filter(|v| v.fract() == 0.0).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Attribute {} must be an integer, got {}", key, n))
+            })?;
             
-            if index < lookup.len() {
-                Ok(Value::String(lookup[index].clone()))
-            } else {
-                Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid lookup index"))
+            match number_type_code(&Number::from(value as i64)) {
+                Some(code) if code != 4 => Ok(code),
+                _ => Err(out_of_range("32-bit integer")),
             }
         }
-        6 => {
-            let s = read_string(reader)?;
-            Ok(Value::String(s))
-        }
-        7 => {
-            let s = read_run_length_encoded(reader)?;
-            Ok(Value::String(s))
-        }
-        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid value type"))
     }
 }
 
 /// Encode value to byte stream with appropriate type code
-pub fn encode_value<W: Write>(writer: &mut W, _key: &str, value: &Value, lookup: &HashMap<String, usize>) -> io::Result<()> {
+///
+/// Numbers use the type chosen by [`number_type_code`], unless the key has a
+/// forced type in the context's field table.
+pub fn encode_value<W: Write>(writer: &mut W, key: &str, value: &Value, ctx: &EncodeContext) -> io::Result<()> {
+    let lookup = ctx.lookup;
+    
     match value {
         Value::Bool(b) => {
             writer.write_all(&[0])?;
             writer.write_all(&[*b as u8])?;
         }
         Value::Number(n) => {
-            if let Some(n_u8) = n.as_u64().and_then(|n| u8::try_from(n).ok()) {
-                writer.write_all(&[1])?;
-                writer.write_all(&[n_u8])?;
-            } else if let Some(n_i16) = n.as_i64().and_then(|n| i16::try_from(n).ok()) {
-                writer.write_all(&[2])?;
-                writer.write_all(&n_i16.to_le_bytes())?;
-            } else if let Some(n_i32) = n.as_i64().and_then(|n| i32::try_from(n).ok()) {
-                writer.write_all(&[3])?;
-                writer.write_all(&n_i32.to_le_bytes())?;
-            } else if let Some(n_f32) = n.as_f64().and_then(|n| {
-                if n >= f32::MIN as f64 && n <= f32::MAX as f64 {
-                    Some(n as f32)
-                } else {
-                    None
+            let field_type = ctx.field_types.and_then(|types| types.get(key));
+            let float = n.as_f64().unwrap_or_default();
+            let type_code = match field_number_type_code(key, n, field_type) {
+                // Values an f32 cannot hold exactly, or at all, keep full precision
+                Ok(4) | Err(_)
+                    if ctx.double_precision
+                        && field_type != Some(FieldType::Integer)
+                        && float.is_finite()
+                        && float as f32 as f64 != float =>
+                {
+                    DOUBLE_TYPE_CODE
                 }
-            }) {
-                writer.write_all(&[4])?;
-                writer.write_all(&n_f32.to_le_bytes())?;
-            } else {
-                return Err(io::Error::new(io::ErrorKind::InvalidData, "Number out of range"));
+                result => result?,
+            };
+            
+            writer.write_all(&[type_code])?;
+            
+            // Integer codes are only chosen for whole values within range
+            match type_code {
+                1 => writer.write_all(&[float as u8])?,
+                2 => writer.write_all(&(float as i16).to_le_bytes())?,
+                3 => writer.write_all(&(float as i32).to_le_bytes())?,
+                DOUBLE_TYPE_CODE => writer.write_all(&float.to_le_bytes())?,
+                _ => writer.write_all(&(float as f32).to_le_bytes())?,
             }
         }
         Value::String(s) => {
             if let Some(&index) = lookup.get(s) {
                 writer.write_all(&[5])?;
                 writer.write_all(&(index as u16).to_le_bytes())?;
-            } else if let Some(encoded) = encode_run_length(s) {
-                let encoded_len = encoded.len();
-                
-                if encoded_len < s.len() && encoded_len <= u16::MAX as usize {
-                    writer.write_all(&[7])?;
-                    writer.write_all(&(encoded_len as u16).to_le_bytes())?;
-                    writer.write_all(&encoded)?;
-                } else {
-                    writer.write_all(&[6])?;
-                    write_string(writer, s)?;
-                }
+            } else if let Some(encoded) = ctx.rle_min_savings.and_then(|min_savings| {
+                encode_run_length(s)
+                    .filter(|encoded| encoded.len() + min_savings <= s.len() && encoded.len() <= u16::MAX as usize)
+            }) {
+                writer.write_all(&[7])?;
+                writer.write_all(&(encoded.len() as u16).to_le_bytes())?;
+                writer.write_all(&encoded)?;
             } else {
                 writer.write_all(&[6])?;
                 write_string(writer, s)?;
@@ -223,81 +689,424 @@ pub fn encode_value<W: Write>(writer: &mut W, _key: &str, value: &Value, lookup:
     Ok(())
 }
 
-/// Decode element from byte stream
-pub fn decode_element<R: Read>(reader: &mut R, lookup: &[String]) -> io::Result<DecodedElement> {
-    let mut index = [0u8; 2];
-    reader.read_exact(&mut index)?;
-    let name_index = u16::from_le_bytes(index) as usize;
+/// Lookup table of a decoded map, with every string in one shared buffer
+///
+/// Large modded maps have tens of thousands of lookup strings; storing
+/// them back to back with their end offsets costs one allocation for the
+/// whole table instead of one per string.
+#[derive(Debug, Clone, Default)]
+pub struct LookupTable {
+    text: String,
+    ends: Vec<usize>,
+}
+
+impl LookupTable {
+    pub fn with_capacity(strings: usize) -> Self {
+        Self { text: String::new(), ends: Vec::with_capacity(strings) }
+    }
     
-    if name_index >= lookup.len() {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid element name index"));
+    pub fn push(&mut self, s: &str) {
+        self.text.push_str(s);
+        self.ends.push(self.text.len());
     }
     
-    let name = lookup[name_index].clone();
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
     
-    let mut attribute_count = [0u8; 1];
-    reader.read_exact(&mut attribute_count)?;
-    let attribute_count = attribute_count[0] as usize;
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let end = *self.ends.get(index)?;
+        let start = index.checked_sub(1).map_or(0, |previous| self.ends[previous]);
+        Some(&self.text[start..end])
+    }
     
-    let mut attributes = HashMap::new();
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+}
+
+/// Settings and diagnostics shared by every element read during one decode
+pub struct DecodeContext<'a> {
+    pub lookup: &'a LookupTable,
+    /// Keep partially read elements instead of failing on the first error
+    pub recover: bool,
+    /// Problems worked around in recovery mode
+    pub warnings: Vec<String>,
+    /// Set once recovery mode kept a partial element
+    pub cut_short: bool,
+    /// References per lookup index, counted only when set
+    pub usage: Option<Vec<usize>>,
+    /// Called with every element once it is fully decoded
+    pub on_element: Option<&'a dyn Fn(&mut DecodedElement)>,
+    /// Warn about run-length encoded values with zero-count runs
+    pub strict_run_length: bool,
+    /// Blame child decode failures on a wrong child count
+    pub strict_child_counts: bool,
+    /// Accept the non-vanilla [`DOUBLE_TYPE_CODE`]
+    pub double_precision: bool,
+    /// Reused for the inline strings of every attribute
+    buffer: Vec<u8>,
+}
+
+impl<'a> DecodeContext<'a> {
+    pub fn new(lookup: &'a LookupTable) -> Self {
+        Self {
+            lookup,
+            recover: false,
+            warnings: Vec::new(),
+            cut_short: false,
+            usage: None,
+            on_element: None,
+            strict_run_length: false,
+            strict_child_counts: false,
+            double_precision: false,
+            buffer: Vec::new(),
+        }
+    }
     
-    for _ in 0..attribute_count {
-        let mut key_index = [0u8; 2];
-        reader.read_exact(&mut key_index)?;
-        let key_index = u16::from_le_bytes(key_index) as usize;
-        
-        if key_index >= lookup.len() {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid attribute key index"));
+    /// Passes a fully decoded element to the `on_element` hook, if any
+    pub(crate) fn observe(&self, element: &mut DecodedElement) {
+        if let Some(on_element) = self.on_element {
+            on_element(element);
         }
-        
-        let key = lookup[key_index].clone();
-        
-        let mut type_byte = [0u8; 1];
-        reader.read_exact(&mut type_byte)?;
-        
-        let value = decode_value(type_byte[0], lookup, reader)?;
-        attributes.insert(key, value);
     }
     
-    let mut child_count = [0u8; 2];
-    reader.read_exact(&mut child_count)?;
-    let child_count = u16::from_le_bytes(child_count) as usize;
+    /// Resolves a lookup index read from the stream, counting the reference
+    fn resolve(&mut self, index: usize, what: &str) -> io::Result<String> {
+        let s = lookup_string(self.lookup, index, what)?;
+        if let Some(usage) = &mut self.usage {
+            usage[index] += 1;
+        }
+        Ok(s)
+    }
+}
+
+/// Checks a lookup index read from the stream
+pub fn check_lookup_index(lookup_len: usize, index: usize, what: &str) -> io::Result<()> {
+    if index < lookup_len {
+        return Ok(());
+    }
+    
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid {} index {} >= lookup table size {}", what, index, lookup_len),
+    ))
+}
+
+/// Resolve a lookup index read from the stream
+fn lookup_string(lookup: &LookupTable, index: usize, what: &str) -> io::Result<String> {
+    check_lookup_index(lookup.len(), index, what)?;
+    Ok(lookup.get(index).unwrap_or_default().to_string())
+}
+
+/// Decode one key/value pair
+fn decode_attribute<R: Read>(reader: &mut R, ctx: &mut DecodeContext) -> io::Result<(String, Value)> {
+    // The source borrows the context's buffer while the context resolves keys
+    let mut buffer = std::mem::take(&mut ctx.buffer);
+    let attribute = read_attribute(&mut StreamSource::new(reader, &mut buffer), ctx);
+    ctx.buffer = buffer;
+    
+    attribute
+}
+
+fn read_attribute<S: Source>(source: &mut S, ctx: &mut DecodeContext) -> io::Result<(String, Value)> {
+    let key = ctx.resolve(source.u16()? as usize, "attribute key")?;
+    
+    let type_byte = source.u8()?;
+    let value = read_value(source, type_byte, ctx.double_precision)?;
+    
+    if let RawValue::RunLength(runs) = &value {
+        let zero_runs = zero_runs(runs.as_ref());
+        if ctx.strict_run_length && zero_runs > 0 {
+            ctx.warnings.push(format!(
+                "Attribute {} has {} zero-count runs in its run-length encoded value, possible corruption",
+                key, zero_runs,
+            ));
+        }
+    }
+    
+    // Resolved through the context so the reference is counted
+    let value = value.into_json(|index| ctx.resolve(index, "lookup"))?;
+    Ok((key, value))
+}
+
+/// Decode element from byte stream
+///
+/// In recovery mode, an error while reading an attribute or child ends the
+/// element early: everything read so far is kept, a warning such as
+/// "expected 5 attributes, got 3" is recorded, and the error is swallowed.
+/// The bytes after the error no longer line up with elements, so nothing
+/// more is read: every enclosing element ends right there too, adding a
+/// warning of its own when it had children left. Errors in the element's
+/// own name index still fail.
+pub fn decode_element<R: Read>(reader: &mut R, ctx: &mut DecodeContext) -> io::Result<DecodedElement> {
+    let mut element = decode_element_tree(reader, ctx)?;
+    ctx.observe(&mut element);
+    Ok(element)
+}
+
+fn decode_element_tree<R: Read>(reader: &mut R, ctx: &mut DecodeContext) -> io::Result<DecodedElement> {
+    let (mut element, child_count) = decode_element_head(reader, ctx)?;
+    let Some(child_count) = child_count else {
+        return Ok(element);
+    };
     
-    let children = if child_count > 0 {
+    if child_count > 0 {
         let mut children = Vec::with_capacity(child_count);
         
-        for _ in 0..child_count {
-            let child = decode_element(reader, lookup)?;
-            children.push(child);
+        for read in 0..child_count {
+            match decode_element(reader, ctx) {
+                Ok(child) => children.push(child),
+                Err(e) if ctx.strict_child_counts && ChildCountMismatch::suspects(&e) => {
+                    let mismatch = ChildCountMismatch { element: element.name, declared: child_count, read, source: e };
+                    return Err(io::Error::new(mismatch.source.kind(), mismatch));
+                }
+                Err(e) => {
+                    element.children = (!children.is_empty()).then_some(children);
+                    let detail = format!("expected {} children, got {}", child_count, read);
+                    return recover(ctx, element, e, detail);
+                }
+            }
+            
+            // The child was cut short, so the stream is misaligned from here on
+            if ctx.cut_short {
+                let kept = read + 1;
+                if kept < child_count {
+                    ctx.warnings.push(format!(
+                        "Element {}: expected {} children, got {} (stopped after a damaged child)",
+                        element.name, child_count, kept,
+                    ));
+                }
+                element.children = Some(children);
+                return Ok(element);
+            }
         }
         
-        Some(children)
-    } else {
-        None
-    };
+        element.children = Some(children);
+    }
     
-    Ok(DecodedElement {
-        name,
-        attributes,
-        children,
-    })
+    Ok(element)
 }
 
-/// Encode element to byte stream
-pub fn encode_element<W: Write>(writer: &mut W, element: &DecodedElement, lookup: &HashMap<String, usize>) -> io::Result<()> {
-    let name_index = lookup.get(&element.name).ok_or_else(|| {
+/// A child that failed to decode, blamed on its parent's child count
+///
+/// When an element declares more children than it has, the bytes after its
+/// real children are read as one more child, which then fails far from the
+/// actual mistake. That child fails right at its start, its name index not
+/// being in the lookup table, or runs into the end of the file; other
+/// failures keep their own error. Only the innermost parent is blamed.
+#[derive(Debug)]
+struct ChildCountMismatch {
+    element: String,
+    declared: usize,
+    read: usize,
+    source: io::Error,
+}
+
+impl ChildCountMismatch {
+    fn is(error: &io::Error) -> bool {
+        error.get_ref().is_some_and(|e| e.is::<ChildCountMismatch>())
+    }
+    
+    /// Whether a child's failure points at its parent's child count
+    fn suspects(error: &io::Error) -> bool {
+        if Self::is(error) {
+            return false;
+        }
+        
+        error.kind() == io::ErrorKind::UnexpectedEof || error.get_ref().is_some_and(|e| e.is::<BadElementName>())
+    }
+}
+
+/// An element name index outside the lookup table
+///
+/// The first field of an element, so what fails when reading starts at a
+/// byte that is no element boundary.
+#[derive(Debug)]
+struct BadElementName(io::Error);
+
+impl fmt::Display for BadElementName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Error for BadElementName {}
+
+impl fmt::Display for ChildCountMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Child count mismatch suspected: {} declares {} children, but child {} does not decode ({})",
+            self.element,
+            self.declared,
+            self.read + 1,
+            self.source,
+        )
+    }
+}
+
+impl Error for ChildCountMismatch {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Decode an element's name and attributes, returning its child count
+///
+/// The children follow in the stream and are left for the caller to read,
+/// one `decode_element` call each. The count is `None` when recovery mode
+/// cut the element short, in which case no children follow.
+pub fn decode_element_head<R: Read>(
+    reader: &mut R,
+    ctx: &mut DecodeContext,
+) -> io::Result<(DecodedElement, Option<usize>)> {
+    let mut index = [0u8; 2];
+    reader.read_exact(&mut index)?;
+    let name = ctx
+        .resolve(u16::from_le_bytes(index) as usize, "element name")
+        .map_err(|e| io::Error::new(e.kind(), BadElementName(e)))?;
+    
+    let mut element = DecodedElement::new(name);
+    
+    let mut attribute_count = [0u8; 1];
+    if let Err(e) = reader.read_exact(&mut attribute_count) {
+        return recover(ctx, element, e, "missing attribute count".to_string()).map(|e| (e, None));
+    }
+    let attribute_count = attribute_count[0] as usize;
+    
+    for read in 0..attribute_count {
+        match decode_attribute(reader, ctx) {
+            Ok((key, value)) => {
+                element.attributes.insert(key, value);
+            }
+            Err(e) => {
+                let detail = format!("expected {} attributes, got {}", attribute_count, read);
+                return recover(ctx, element, e, detail).map(|e| (e, None));
+            }
+        }
+    }
+    
+    let mut child_count = [0u8; 2];
+    if let Err(e) = reader.read_exact(&mut child_count) {
+        return recover(ctx, element, e, "missing child count".to_string()).map(|e| (e, None));
+    }
+    
+    Ok((element, Some(u16::from_le_bytes(child_count) as usize)))
+}
+
+/// Count an element and its descendants, discarding their data
+///
+/// Parses the same layout as [`decode_element`], checking every value, but
+/// only allocates the source's string buffer, if any.
+pub fn count_element<S: Source>(source: &mut S, lookup_len: usize, double_precision: bool) -> io::Result<usize> {
+    check_lookup_index(lookup_len, source.u16()? as usize, "element name")?;
+    
+    let attribute_count = source.u8()?;
+    for _ in 0..attribute_count {
+        skip_attribute(source, lookup_len, double_precision)?;
+    }
+    
+    let mut count = 1;
+    for _ in 0..source.u16()? {
+        count += count_element(source, lookup_len, double_precision)?;
+    }
+    
+    Ok(count)
+}
+
+/// Walks one element like [`count_element`], recording the offset of every
+/// element in its subtree
+pub fn index_element<R: Read>(
+    source: &mut StreamSource<PositionReader<R>>,
+    lookup: &LookupTable,
+    double_precision: bool,
+) -> io::Result<ElementOffsets> {
+    let offset = source.reader().position();
+    let name = lookup_string(lookup, source.u16()? as usize, "element name")?;
+    
+    let attribute_count = source.u8()?;
+    for _ in 0..attribute_count {
+        skip_attribute(source, lookup.len(), double_precision)?;
+    }
+    
+    let child_count = source.u16()? as usize;
+    let mut children = Vec::with_capacity(child_count);
+    for _ in 0..child_count {
+        children.push(index_element(source, lookup, double_precision)?);
+    }
+    
+    Ok(ElementOffsets { name, offset, children })
+}
+
+/// Read past one key/value pair, checking its lookup references
+fn skip_attribute<S: Source>(source: &mut S, lookup_len: usize, double_precision: bool) -> io::Result<()> {
+    check_lookup_index(lookup_len, source.u16()? as usize, "attribute key")?;
+    
+    let type_byte = source.u8()?;
+    if let RawValue::Lookup(index) = read_value(source, type_byte, double_precision)? {
+        check_lookup_index(lookup_len, index, "lookup")?;
+    }
+    
+    Ok(())
+}
+
+/// Returns the partial element in recovery mode, or the error otherwise
+fn recover(
+    ctx: &mut DecodeContext,
+    element: DecodedElement,
+    error: io::Error,
+    detail: String,
+) -> io::Result<DecodedElement> {
+    if !ctx.recover {
+        return Err(error);
+    }
+    
+    ctx.warnings.push(format!("Element {}: {} ({})", element.name, detail, error));
+    ctx.cut_short = true;
+    
+    Ok(element)
+}
+
+/// Encode element name and attributes, everything that precedes the child count
+pub fn encode_element_header<'a, W: Write>(
+    writer: &mut W,
+    name: &str,
+    attributes: impl Iterator<Item = (&'a String, &'a Value)>,
+    ctx: &EncodeContext,
+) -> io::Result<()> {
+    let lookup = ctx.lookup;
+    let name_index = lookup.get(name).ok_or_else(|| {
         io::Error::new(io::ErrorKind::InvalidData, "Element name not in lookup table")
     })?;
     
     writer.write_all(&(*name_index as u16).to_le_bytes())?;
     
     // Filter out special attributes
-    let attributes: HashMap<_, _> = element.attributes.iter()
-        .filter(|(k, _)| !k.starts_with("__"))
+    let attributes: HashMap<_, _> = attributes
+        .filter(|(k, _)| ctx.preserve_underscore_attrs || !k.starts_with("__"))
+        .collect();
+    
+    if ctx.strict_nulls {
+        if let Some((key, _)) = attributes.iter().find(|(_, v)| v.is_null()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Attribute {} of element {} is null, which the binary format cannot store", key, name),
+            ));
+        }
+    }
+    
+    let attributes: HashMap<_, _> = attributes.into_iter()
         .filter(|(_, v)| !v.is_null())
         .collect();
     
-    writer.write_all(&[attributes.len() as u8])?;
+    let attribute_count = u8::try_from(attributes.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Element {} has {} attributes, more than the 255 the format allows", name, attributes.len()),
+        )
+    })?;
+    writer.write_all(&[attribute_count])?;
     
     for (attr, value) in &attributes {
         let attr_index = lookup.get(attr.as_str()).ok_or_else(|| {
@@ -305,14 +1114,57 @@ pub fn encode_element<W: Write>(writer: &mut W, element: &DecodedElement, lookup
         })?;
         
         writer.write_all(&(*attr_index as u16).to_le_bytes())?;
-        encode_value(writer, attr, value, lookup)?;
+        encode_value(writer, attr, value, ctx)
+            .map_err(|e| io::Error::new(e.kind(), format!("Element {}: {}", name, e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Encode element to byte stream
+pub fn encode_element<W: Write>(writer: &mut W, element: &DecodedElement, ctx: &EncodeContext) -> io::Result<()> {
+    encode_element_header(writer, &element.name, element.attributes.iter(), ctx)?;
+    
+    let children = element.children.as_deref().unwrap_or(&[]);
+    let sort = ctx.sort_entities && SORTABLE_CONTAINERS.contains(&element.name.as_str());
+    
+    // Children are only collected when they need reordering or filtering
+    if !ctx.canonical_child_order && ctx.skip_elements.is_none() && !sort {
+        return encode_children(writer, &element.name, children.iter(), ctx);
+    }
+    
+    let mut children = if ctx.canonical_child_order {
+        canonical_children(&element.name, children)
+    } else {
+        children.iter().collect()
+    };
+    if let Some(skip) = ctx.skip_elements {
+        children.retain(|child| !skip(child));
+    }
+    if sort {
+        children.sort_by(|a, b| entity_order(a, b));
     }
     
-    let children = element.children.as_ref().map(|c| c.as_slice()).unwrap_or(&[]);
-    writer.write_all(&(children.len() as u16).to_le_bytes())?;
+    encode_children(writer, &element.name, children.into_iter(), ctx)
+}
+
+/// Writes the child count and the children of the element named `name`
+fn encode_children<'a, W: Write>(
+    writer: &mut W,
+    name: &str,
+    children: impl ExactSizeIterator<Item = &'a DecodedElement>,
+    ctx: &EncodeContext,
+) -> io::Result<()> {
+    let child_count = u16::try_from(children.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Element {} has {} children, more than the 65535 the format allows", name, children.len()),
+        )
+    })?;
+    writer.write_all(&child_count.to_le_bytes())?;
     
     for child in children {
-        encode_element(writer, child, lookup)?;
+        encode_element(writer, child, ctx)?;
     }
     
     Ok(())
@@ -0,0 +1,263 @@
+use serde_json::{json, Value};
+use std::io;
+
+use crate::binary::{
+    check_lookup_index, expand_run_length, read_lookup_len, read_magic, read_value, Format, RawValue, SliceSource,
+    Source,
+};
+use crate::decoder::Decoder;
+use crate::element::DecodedElement;
+use crate::error::DecodeError;
+
+/// Element borrowing its strings from the buffer it was decoded from
+///
+/// Returned by [`decode_map_borrowed`] for read-only analysis: names, keys
+/// and string values point into the input bytes instead of being copied,
+/// so a decode only allocates the attribute and child lists. The root also
+/// holds the package as its `package` attribute, as with `decode_map`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementRef<'a> {
+    pub name: &'a str,
+    /// Attributes in file order
+    pub attributes: Vec<(&'a str, ValueRef<'a>)>,
+    pub children: Vec<ElementRef<'a>>,
+}
+
+/// Attribute value of an [`ElementRef`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueRef<'a> {
+    Bool(bool),
+    /// Any of the u8, i16 and i32 encodings
+    Int(i32),
+    Float(f32),
+    /// The non-vanilla [`DOUBLE_TYPE_CODE`](crate::DOUBLE_TYPE_CODE)
+    Double(f64),
+    /// A lookup table or inline string
+    Str(&'a str),
+    /// Run-length encoded string, kept as its raw (count, byte) pairs
+    RunLength(&'a [u8]),
+}
+
+impl<'a> ValueRef<'a> {
+    /// The string, if the value is stored as one; run-length encoded
+    /// strings have no borrowed form, see [`ValueRef::to_value`]
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            ValueRef::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The owned JSON value `decode_map` gives for this attribute
+    pub fn to_value(&self) -> Value {
+        match *self {
+            ValueRef::Bool(b) => Value::Bool(b),
+            ValueRef::Int(i) => Value::from(i),
+            ValueRef::Float(f) if f.is_finite() => json!(f),
+            ValueRef::Float(_) => Value::Null,
+            ValueRef::Double(f) if f.is_finite() => json!(f),
+            ValueRef::Double(_) => Value::Null,
+            ValueRef::Str(s) => Value::String(s.to_string()),
+            ValueRef::RunLength(runs) => {
+                let mut s = String::new();
+                expand_run_length(runs, &mut s);
+                Value::String(s)
+            }
+        }
+    }
+}
+
+impl<'a> ElementRef<'a> {
+    /// Looks up one attribute by key
+    pub fn attribute(&self, key: &str) -> Option<ValueRef<'a>> {
+        self.attributes.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+    }
+
+    /// First direct child with the given name
+    pub fn find_child(&self, name: &str) -> Option<&ElementRef<'a>> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Copies this element and its subtree into an owned [`DecodedElement`]
+    pub fn to_element(&self) -> DecodedElement {
+        let mut element = DecodedElement::new(self.name);
+
+        for (key, value) in &self.attributes {
+            element.attributes.insert(key.to_string(), value.to_value());
+        }
+
+        if !self.children.is_empty() {
+            element.children = Some(self.children.iter().map(ElementRef::to_element).collect());
+        }
+
+        element
+    }
+}
+
+/// Decodes a binary map without copying its strings
+///
+/// The returned tree borrows from `bytes`; use [`ElementRef::to_element`]
+/// for an owned copy. Errors carry a [`DecodeError`] with the byte offset,
+/// as with `decode_map`. Bytes after the root element are ignored. Expects
+/// the standard header; see [`Decoder::decode_borrowed`] for others.
+pub fn decode_map_borrowed(bytes: &[u8]) -> io::Result<ElementRef<'_>> {
+    Decoder::new().decode_borrowed(bytes)
+}
+
+/// Decodes a map in the given `format` without copying its strings
+pub(crate) fn decode_borrowed<'a>(bytes: &'a [u8], format: &Format) -> io::Result<ElementRef<'a>> {
+    SliceReader::new(bytes, None).decode(format)
+}
+
+/// Map decoded by [`decode_map_sections`], with the raw bytes of each room
+pub struct MapSections<'a> {
+    root: ElementRef<'a>,
+    raw_levels: Vec<&'a [u8]>,
+}
+
+impl<'a> MapSections<'a> {
+    /// The whole map, as [`decode_map_borrowed`] returns it
+    pub fn root(&self) -> &ElementRef<'a> {
+        &self.root
+    }
+
+    pub fn into_root(self) -> ElementRef<'a> {
+        self.root
+    }
+
+    /// Every `level` under `levels`, in file order, with the bytes it was
+    /// decoded from
+    pub fn levels(&self) -> impl Iterator<Item = (&ElementRef<'a>, &'a [u8])> + '_ {
+        let levels = self.root.find_child("levels").map(|levels| levels.children.as_slice()).unwrap_or_default();
+        levels.iter().filter(|level| level.name == "level").zip(self.raw_levels.iter().copied())
+    }
+
+    /// Raw bytes of the room with the given `name` attribute
+    pub fn raw_level(&self, name: &str) -> Option<&'a [u8]> {
+        self.levels()
+            .find(|(level, _)| level.attribute("name").and_then(|value| value.as_str()) == Some(name))
+            .map(|(_, raw)| raw)
+    }
+}
+
+/// Decodes a binary map as [`decode_map_borrowed`] does, keeping the exact
+/// bytes of every room
+///
+/// Each `level` is paired with the slice of `bytes` it was decoded from, so
+/// an editor can revert a room by copying that slice back instead of
+/// encoding it again, and the result is byte-identical to the source. Both
+/// the elements and the slices borrow from `bytes`, which must outlive
+/// them. The slices hold lookup table indices, not strings, so they only
+/// decode correctly next to the header of the file they came from. Expects
+/// the standard header; see [`Decoder::decode_borrowed_sections`] for
+/// others.
+pub fn decode_map_sections(bytes: &[u8]) -> io::Result<MapSections<'_>> {
+    Decoder::new().decode_borrowed_sections(bytes)
+}
+
+/// Decodes a map in the given `format`, keeping the bytes of every room
+pub(crate) fn decode_borrowed_sections<'a>(bytes: &'a [u8], format: &Format) -> io::Result<MapSections<'a>> {
+    let mut reader = SliceReader::new(bytes, Some(Vec::new()));
+    let root = reader.decode(format)?;
+
+    Ok(MapSections { root, raw_levels: reader.sections.unwrap_or_default() })
+}
+
+/// Element walker over the input, with the lookup table read from its header
+struct SliceReader<'a> {
+    source: SliceSource<'a>,
+    lookup: Vec<&'a str>,
+    /// Nesting depth of the element being decoded, the root being 0
+    depth: usize,
+    /// Raw bytes of each room, when asked for by `decode_map_sections`
+    sections: Option<Vec<&'a [u8]>>,
+    /// Accept the non-vanilla [`DOUBLE_TYPE_CODE`](crate::DOUBLE_TYPE_CODE)
+    double_precision: bool,
+}
+
+impl<'a> SliceReader<'a> {
+    fn new(bytes: &'a [u8], sections: Option<Vec<&'a [u8]>>) -> Self {
+        Self { source: SliceSource::new(bytes), lookup: Vec::new(), depth: 0, sections, double_precision: false }
+    }
+
+    /// Decodes the whole map, errors carrying the offset where decoding stopped
+    fn decode(&mut self, format: &Format) -> io::Result<ElementRef<'a>> {
+        self.double_precision = format.double_precision;
+        self.decode_body(format).map_err(|e| DecodeError::wrap(e, self.source.position()))
+    }
+
+    fn decode_body(&mut self, format: &Format) -> io::Result<ElementRef<'a>> {
+        read_magic(&mut self.source, format)?;
+        let package = self.source.string()?;
+
+        // The smallest root element takes five bytes
+        let lookup_len = read_lookup_len(&mut self.source, 5)?;
+        self.lookup.reserve(lookup_len);
+        for _ in 0..lookup_len {
+            let s = self.source.string()?;
+            self.lookup.push(s);
+        }
+
+        let mut root = self.decode_element()?;
+        root.attributes.push(("package", ValueRef::Str(package)));
+        Ok(root)
+    }
+
+    fn resolve(&self, index: usize, what: &str) -> io::Result<&'a str> {
+        check_lookup_index(self.lookup.len(), index, what)?;
+        Ok(self.lookup[index])
+    }
+
+    /// Reads a lookup index and resolves it
+    fn read_lookup(&mut self, what: &str) -> io::Result<&'a str> {
+        let index = self.source.u16()? as usize;
+        self.resolve(index, what)
+    }
+
+    fn decode_element(&mut self) -> io::Result<ElementRef<'a>> {
+        let name = self.read_lookup("element name")?;
+
+        let attribute_count = self.source.u8()? as usize;
+        let mut attributes = Vec::with_capacity(attribute_count);
+        for _ in 0..attribute_count {
+            let key = self.read_lookup("attribute key")?;
+            let value = self.read_value()?;
+            attributes.push((key, value));
+        }
+
+        let child_count = self.source.u16()? as usize;
+        // Every child takes at least five bytes
+        let mut children = Vec::with_capacity(child_count.min(self.source.rest().len() / 5));
+        let capture = self.sections.is_some() && self.depth == 1 && name == "levels";
+
+        self.depth += 1;
+        for _ in 0..child_count {
+            let start = self.source.rest();
+            let child = self.decode_element()?;
+
+            if let Some(sections) = self.sections.as_mut().filter(|_| capture && child.name == "level") {
+                sections.push(&start[..start.len() - self.source.rest().len()]);
+            }
+            children.push(child);
+        }
+        self.depth -= 1;
+
+        Ok(ElementRef { name, attributes, children })
+    }
+
+    fn read_value(&mut self) -> io::Result<ValueRef<'a>> {
+        let type_byte = self.source.u8()?;
+
+        let value = match read_value(&mut self.source, type_byte, self.double_precision)? {
+            RawValue::Bool(b) => ValueRef::Bool(b),
+            RawValue::Int(i) => ValueRef::Int(i),
+            RawValue::Float(f) => ValueRef::Float(f),
+            RawValue::Double(f) => ValueRef::Double(f),
+            RawValue::Lookup(index) => ValueRef::Str(self.resolve(index, "lookup")?),
+            RawValue::Str(s) => ValueRef::Str(s),
+            RawValue::RunLength(runs) => ValueRef::RunLength(runs),
+        };
+
+        Ok(value)
+    }
+}
\ No newline at end of file
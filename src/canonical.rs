@@ -0,0 +1,141 @@
+use serde_json::{Number, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::element::DecodedElement;
+use crate::json::JsonLoader;
+
+const INDENT: &str = "  ";
+
+/// Serializes a map as pretty-printed JSON in canonical form
+///
+/// `__name` comes first, then the attributes sorted by key, then
+/// `__children`. Floats are written with the shortest digits that give the
+/// same 32-bit float the binary format stores, so `0.10000000149011612`
+/// from a decoded map becomes `0.1`. Integers are left as they are, and
+/// integral floats keep their `.0` so they still encode as floats.
+pub fn to_canonical_json(map: &DecodedElement) -> String {
+    let mut out = String::new();
+    write_element(&mut out, map, 0);
+    out
+}
+
+/// Rewrites a JSON map file in canonical form, see [`to_canonical_json`]
+///
+/// The file is only written when its contents change. Returns whether it
+/// did. Duplicate keys are an error, so no value is dropped silently.
+pub fn canonicalize_json<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let path = path.as_ref();
+    let original = fs::read(path)?;
+    let map = JsonLoader::new().load_from_reader(&original[..])?.map;
+
+    let canonical = to_canonical_json(&map);
+    if canonical.as_bytes() == original {
+        return Ok(false);
+    }
+
+    fs::write(path, canonical)?;
+    Ok(true)
+}
+
+fn write_element(out: &mut String, element: &DecodedElement, depth: usize) {
+    let mut keys: Vec<_> = element.attributes.keys().collect();
+    keys.sort();
+
+    out.push('{');
+    write_key(out, "__name", depth + 1);
+    write_string(out, &element.name);
+
+    for key in keys {
+        out.push(',');
+        write_key(out, key, depth + 1);
+        write_value(out, &element.attributes[key], depth + 1);
+    }
+
+    if let Some(children) = &element.children {
+        out.push(',');
+        write_key(out, "__children", depth + 1);
+        write_list(out, children, depth + 1, write_element);
+    }
+
+    newline(out, depth);
+    out.push('}');
+}
+
+fn write_value(out: &mut String, value: &Value, depth: usize) {
+    match value {
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => write_list(out, items, depth, write_value),
+        Value::Object(fields) if !fields.is_empty() => {
+            let mut keys: Vec<_> = fields.keys().collect();
+            keys.sort();
+
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_key(out, key, depth + 1);
+                write_value(out, &fields[key], depth + 1);
+            }
+            newline(out, depth);
+            out.push('}');
+        }
+        // null, booleans and {}
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+fn write_number(out: &mut String, n: &Number) {
+    let single = n.as_f64().filter(|_| n.is_f64()).map(|f| f as f32);
+
+    match single {
+        Some(f) if f.is_finite() => {
+            // f32's Display is the shortest round-trip form and never uses exponents
+            let text = f.to_string();
+            out.push_str(&text);
+            if !text.contains('.') {
+                out.push_str(".0");
+            }
+        }
+        // Integers, and floats the binary format cannot hold anyway
+        _ => out.push_str(&n.to_string()),
+    }
+}
+
+fn write_list<T>(out: &mut String, items: &[T], depth: usize, write_item: fn(&mut String, &T, usize)) {
+    if items.is_empty() {
+        out.push_str("[]");
+        return;
+    }
+
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        newline(out, depth + 1);
+        write_item(out, item, depth + 1);
+    }
+    newline(out, depth);
+    out.push(']');
+}
+
+fn write_key(out: &mut String, key: &str, depth: usize) {
+    newline(out, depth);
+    write_string(out, key);
+    out.push_str(": ");
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push_str(&Value::from(s).to_string());
+}
+
+fn newline(out: &mut String, depth: usize) {
+    out.push('\n');
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// RGBA color as stored in hex attributes such as `color="ff0000"`
+///
+/// Celeste reads colors as `RRGGBB` or, where alpha matters, `RRGGBBAA`
+/// hex strings. Six-digit colors are opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    /// An opaque color
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: u8::MAX }
+    }
+
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Parses a 6-digit `RRGGBB` or 8-digit `RRGGBBAA` hex string
+    ///
+    /// Digits may be in either case and a leading `#` is skipped, as
+    /// Celeste does. Returns `None` for any other length or a non-hex digit.
+    pub fn from_attr(s: &str) -> Option<Color> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        match hex.len() {
+            6 => Some(Color::rgb(channel(0)?, channel(2)?, channel(4)?)),
+            8 => Some(Color::rgba(channel(0)?, channel(2)?, channel(4)?, channel(6)?)),
+            _ => None,
+        }
+    }
+
+    /// Formats the color as lowercase hex, the way Celeste's editors write it
+    ///
+    /// Opaque colors use the 6-digit form, others the 8-digit one, so a
+    /// value read with [`Color::from_attr`] is written back unchanged apart
+    /// from case, unless it spelled out an opaque alpha of `ff`.
+    pub fn to_attr(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != u8::MAX {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,229 @@
+use std::fs;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use crate::decoder::{Decoder, DEFAULT_MAGIC};
+use crate::element::DecodedElement;
+use crate::inflate::{crc32, inflate};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_DIRECTORY: u32 = 0x0605_4b50;
+
+/// Gzip layers peeled before giving up, so a corrupt file cannot loop forever
+const MAX_LAYERS: usize = 8;
+
+/// Largest decompressed size of a gzip layer or zip entry, 256 MiB
+///
+/// Far above any real map, while a few kilobytes of crafted data could
+/// otherwise expand to fill all memory. Larger output is an error.
+pub const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Removes gzip layers from `bytes` until something else is left
+///
+/// Layers are recognized by their magic bytes, never by file name, so a
+/// `.bin` that is really gzip-compressed is unpacked too. Bytes that are
+/// not gzip are returned unchanged. Each layer may decompress to at most
+/// [`MAX_DECOMPRESSED_SIZE`] bytes.
+pub fn unwrap_gzip(mut bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    for _ in 0..MAX_LAYERS {
+        if !bytes.starts_with(GZIP_MAGIC) {
+            return Ok(bytes);
+        }
+        bytes = gunzip(&bytes)?;
+    }
+
+    Err(invalid(format!("More than {} nested gzip layers", MAX_LAYERS)))
+}
+
+/// Decodes a map from any reader, such as a network stream
+///
+/// A plain map is decoded while it arrives, without being buffered whole.
+/// Gzip-compressed data, recognized by its magic bytes as in
+/// [`unwrap_gzip`], is read to the end and unpacked first; more than
+/// [`MAX_DECOMPRESSED_SIZE`] compressed bytes is an error, like larger
+/// output. The reader is buffered internally.
+pub fn decode_map_from_reader<R: Read>(mut reader: R) -> io::Result<DecodedElement> {
+    // Network streams may return fewer bytes than asked for
+    let mut magic = Vec::with_capacity(GZIP_MAGIC.len());
+    reader.by_ref().take(GZIP_MAGIC.len() as u64).read_to_end(&mut magic)?;
+
+    if magic != GZIP_MAGIC {
+        return Decoder::new().decode_from_reader(BufReader::new(magic.chain(reader)));
+    }
+
+    // Compressed data never needs to be much larger than what it expands to
+    let mut bytes = magic;
+    reader.take(MAX_DECOMPRESSED_SIZE as u64).read_to_end(&mut bytes)?;
+    if bytes.len() > MAX_DECOMPRESSED_SIZE {
+        return Err(invalid(format!("Compressed data exceeds the limit of {} bytes", MAX_DECOMPRESSED_SIZE)));
+    }
+    let bytes = unwrap_gzip(bytes)?;
+
+    Decoder::new().decode_from_reader(&bytes[..])
+}
+
+/// Decodes a map stored in a zip archive, whatever wraps it inside
+///
+/// With an `entry` name, that entry is decoded. Without one, the first
+/// entry whose contents turn out to be a map is used. Entries may be
+/// stored or deflated, and gzip layers inside them are removed as by
+/// [`unwrap_gzip`], so a `.bin.gz` inside a `.zip` decodes directly.
+/// The zip itself may also be gzip-compressed.
+pub fn decode_map_from_zip<P: AsRef<Path>>(path: P, entry: Option<&str>) -> io::Result<DecodedElement> {
+    let archive = unwrap_gzip(fs::read(path)?)?;
+    if !archive.starts_with(ZIP_LOCAL_HEADER) {
+        return Err(invalid("Not a zip archive"));
+    }
+
+    let entries = zip_entries(&archive)?;
+    let selected: Vec<_> = match entry {
+        Some(name) => entries.iter().filter(|e| e.name == name).collect(),
+        None => entries.iter().collect(),
+    };
+
+    for candidate in selected {
+        let bytes = unwrap_gzip(extract(&archive, candidate)?)?;
+
+        if entry.is_some() || is_map(&bytes) {
+            return Decoder::new().decode_from_reader(&bytes[..]);
+        }
+    }
+
+    match entry {
+        Some(name) => Err(io::Error::new(io::ErrorKind::NotFound, format!("No entry {:?} in zip archive", name))),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, "No map found in zip archive")),
+    }
+}
+
+/// True when `bytes` start with the length-prefixed map header
+fn is_map(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&(DEFAULT_MAGIC.len() as u8)) && bytes[1..].starts_with(DEFAULT_MAGIC.as_bytes())
+}
+
+fn gunzip(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let truncated = || invalid("Gzip data truncated");
+
+    if bytes.len() < 10 || bytes[2] != 8 {
+        return Err(invalid("Unsupported gzip compression method"));
+    }
+    let flags = bytes[3];
+    let mut position = 10;
+
+    if flags & FEXTRA != 0 {
+        let length = bytes.get(position..position + 2).ok_or_else(truncated)?;
+        position += 2 + u16::from_le_bytes([length[0], length[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = bytes[position.min(bytes.len())..].iter().position(|&b| b == 0).ok_or_else(truncated)?;
+            position += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        position += 2;
+    }
+
+    let (data, consumed) = inflate(bytes.get(position..).ok_or_else(truncated)?, MAX_DECOMPRESSED_SIZE)?;
+
+    let trailer = bytes.get(position + consumed..position + consumed + 8).ok_or_else(truncated)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+
+    if crc != crc32(&data) || size != data.len() as u32 {
+        return Err(invalid("Gzip checksum mismatch"));
+    }
+
+    Ok(data)
+}
+
+/// One file in a zip archive's central directory
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    header_offset: usize,
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> io::Result<u16> {
+    let field = bytes.get(at..at + 2).ok_or_else(|| invalid("Zip archive truncated"))?;
+    Ok(u16::from_le_bytes([field[0], field[1]]))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> io::Result<u32> {
+    let field = bytes.get(at..at + 4).ok_or_else(|| invalid("Zip archive truncated"))?;
+    Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]]))
+}
+
+/// Lists the entries from the central directory at the end of the archive
+fn zip_entries(archive: &[u8]) -> io::Result<Vec<ZipEntry>> {
+    // The end record is at least 22 bytes and may be followed by a comment
+    let end = (0..archive.len().saturating_sub(21))
+        .rev()
+        .find(|&at| read_u32(archive, at).ok() == Some(ZIP_END_OF_DIRECTORY))
+        .ok_or_else(|| invalid("Zip archive has no central directory"))?;
+
+    let count = read_u16(archive, end + 10)? as usize;
+    let mut position = read_u32(archive, end + 16)? as usize;
+    let mut entries = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        if read_u32(archive, position)? != ZIP_CENTRAL_HEADER {
+            return Err(invalid("Corrupt zip central directory"));
+        }
+
+        let name_len = read_u16(archive, position + 28)? as usize;
+        let extra_len = read_u16(archive, position + 30)? as usize;
+        let comment_len = read_u16(archive, position + 32)? as usize;
+        let name = archive.get(position + 46..position + 46 + name_len).ok_or_else(|| invalid("Zip archive truncated"))?;
+
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: read_u16(archive, position + 10)?,
+            crc: read_u32(archive, position + 16)?,
+            compressed_size: read_u32(archive, position + 20)? as usize,
+            header_offset: read_u32(archive, position + 42)? as usize,
+        });
+
+        position += 46 + name_len + extra_len + comment_len;
+    }
+
+    Ok(entries)
+}
+
+/// Reads and decompresses one entry, checking its CRC
+fn extract(archive: &[u8], entry: &ZipEntry) -> io::Result<Vec<u8>> {
+    let header = entry.header_offset;
+    let name_len = read_u16(archive, header + 26)? as usize;
+    let extra_len = read_u16(archive, header + 28)? as usize;
+
+    let start = header + 30 + name_len + extra_len;
+    let data = archive
+        .get(start..start + entry.compressed_size)
+        .ok_or_else(|| invalid(format!("Zip entry {} truncated", entry.name)))?;
+
+    let bytes = match entry.method {
+        0 => data.to_vec(),
+        8 => inflate(data, MAX_DECOMPRESSED_SIZE)?.0,
+        method => {
+            return Err(invalid(format!("Zip entry {} uses unsupported compression method {}", entry.name, method)));
+        }
+    };
+
+    if crc32(&bytes) != entry.crc {
+        return Err(invalid(format!("Zip entry {} fails its checksum", entry.name)));
+    }
+
+    Ok(bytes)
+}
\ No newline at end of file
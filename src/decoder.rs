@@ -0,0 +1,667 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::binary::{
+    count_element, decode_element, decode_element_head, read_lookup_len, read_magic, write_string, DecodeContext, Format,
+    LookupTable, PositionReader, SliceSource, Source, StreamSource,
+};
+use crate::borrowed::{decode_borrowed, decode_borrowed_sections, ElementRef, MapSections};
+use crate::element::DecodedElement;
+use crate::error::DecodeError;
+use crate::index::{index_map_with, MapIndex};
+
+/// Buffer size used for file I/O unless configured otherwise
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Header string every Celeste map starts with
+pub const DEFAULT_MAGIC: &str = "CELESTE MAP";
+
+/// Root attribute set on maps cut short by [`Decoder::max_rooms`]
+pub const TRUNCATED_ATTRIBUTE: &str = "__truncated";
+
+/// Callback registered with [`Decoder::on_element`]
+#[derive(Clone)]
+struct ElementHook {
+    pattern: String,
+    callback: Arc<dyn Fn(&mut DecodedElement) + Send + Sync>,
+}
+
+impl fmt::Debug for ElementHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ElementHook({:?})", self.pattern)
+    }
+}
+
+/// Configurable binary map decoder
+///
+/// `decode_map` uses the default settings; build a `Decoder` to change them.
+/// Besides full decodes, the decoder also counts, indexes and partially
+/// decodes maps, see [`Decoder::count_elements`] and the methods after it;
+/// those only use its format settings, [`Decoder::magic`],
+/// [`Decoder::skip_leading_bytes`] and [`Decoder::double_precision`]. The
+/// free functions of the same names expect a vanilla map.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    buffer_size: usize,
+    recover: bool,
+    lookup_usage: bool,
+    unused_lookup: bool,
+    keep_lookup: bool,
+    magic: String,
+    max_rooms: Option<usize>,
+    hooks: Vec<ElementHook>,
+    skip_leading_bytes: bool,
+    strict_run_length: bool,
+    strict_child_counts: bool,
+    strict_trailing: bool,
+    double_precision: bool,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            recover: false,
+            lookup_usage: false,
+            unused_lookup: false,
+            keep_lookup: false,
+            magic: DEFAULT_MAGIC.to_string(),
+            max_rooms: None,
+            hooks: Vec::new(),
+            skip_leading_bytes: false,
+            strict_run_length: false,
+            strict_child_counts: false,
+            strict_trailing: false,
+            double_precision: false,
+        }
+    }
+}
+
+/// Decoded map together with the problems recovery mode worked around
+#[derive(Debug, Clone)]
+pub struct DecodeReport {
+    pub map: DecodedElement,
+    /// One message per element that was cut short, plus one for bytes left
+    /// after the root element; empty for intact files. Also holds the
+    /// findings of [`Decoder::strict_run_length`]
+    pub warnings: Vec<String>,
+    /// References to each lookup string, only filled with
+    /// [`Decoder::lookup_usage`]
+    pub lookup_usage: HashMap<String, usize>,
+    /// Lookup strings no element referenced, in table order, only filled
+    /// with [`Decoder::unused_lookup`]
+    pub unused_lookup: Vec<String>,
+    /// The file's lookup table in its original order, only filled with
+    /// [`Decoder::keep_lookup`]
+    pub lookup: Vec<String>,
+}
+
+impl Decoder {
+    /// Creates a decoder with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Sets the read buffer size used by [`Decoder::decode`]
+    ///
+    /// Larger buffers mean fewer read syscalls on big maps, which matters
+    /// most on slow storage; for a file already in the page cache the
+    /// difference is a few percent (`examples/buffer_size.rs` measures it).
+    /// Defaults to [`DEFAULT_BUFFER_SIZE`].
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+    
+    /// Keeps partially read elements instead of failing on truncated data
+    ///
+    /// When an element cannot be read in full, because the file is cut
+    /// short or a byte in it is corrupt, what was read of it is kept and
+    /// decoding stops there: the bytes after the first error no longer line
+    /// up with elements, so the rest of the file is ignored rather than read
+    /// as garbage. Use [`Decoder::decode_with_report`] to see what was
+    /// skipped. The file header and lookup table must still be intact.
+    pub fn recover(mut self, enabled: bool) -> Self {
+        self.recover = enabled;
+        self
+    }
+    
+    /// Counts how often each lookup string is referenced
+    ///
+    /// Every element name, attribute key and lookup-table value read during
+    /// the decode counts once. The counts end up in
+    /// [`DecodeReport::lookup_usage`], keyed by string; strings that are in
+    /// the table but never referenced are listed with a count of zero.
+    pub fn lookup_usage(mut self, enabled: bool) -> Self {
+        self.lookup_usage = enabled;
+        self
+    }
+    
+    /// Reports lookup strings that no element references
+    ///
+    /// Cairn's encoder only stores strings the map uses, unless seeded with
+    /// `Encoder::with_seed_lookup`, so unreferenced entries point to another
+    /// encoder or to corruption. Each one is listed in
+    /// [`DecodeReport::unused_lookup`] and adds a warning. Repeated entries
+    /// count separately: an unused copy of a used string is reported. The
+    /// check is skipped when decoding stops early because of
+    /// [`Decoder::max_rooms`] or recovery.
+    pub fn unused_lookup(mut self, enabled: bool) -> Self {
+        self.unused_lookup = enabled;
+        self
+    }
+    
+    /// Returns the file's lookup table as it was stored
+    ///
+    /// The table ends up in [`DecodeReport::lookup`], with its original
+    /// order, repeated entries and unreferenced strings. Passing it to
+    /// `Encoder::with_seed_lookup` re-encodes the map with the exact same
+    /// lookup section, to compare cairn's output with a reference file.
+    pub fn keep_lookup(mut self, enabled: bool) -> Self {
+        self.keep_lookup = enabled;
+        self
+    }
+    
+    /// Sets the header string the file must start with
+    ///
+    /// Defaults to [`DEFAULT_MAGIC`]; change it to read files written by
+    /// `Encoder::magic` for a private format variant.
+    pub fn magic(mut self, magic: &str) -> Self {
+        self.magic = magic.to_string();
+        self
+    }
+    
+    /// Skips a byte order mark or whitespace in front of the header
+    ///
+    /// Some bad exports put a UTF-8 BOM or stray whitespace before the map.
+    /// Without this option such files fail with an "unexpected leading
+    /// bytes" error; with it, those bytes are ignored and the rest of the
+    /// file decodes normally. Byte offsets in errors still count from
+    /// the start of the file.
+    pub fn skip_leading_bytes(mut self, enabled: bool) -> Self {
+        self.skip_leading_bytes = enabled;
+        self
+    }
+    
+    /// Warns about degenerate run-length encoded values
+    ///
+    /// A run with a count of zero adds nothing, and no encoder writes one,
+    /// so such runs hint at corrupt data. They decode the same either way;
+    /// with this option each affected attribute also adds a warning to
+    /// [`DecodeReport::warnings`].
+    pub fn strict_run_length(mut self, enabled: bool) -> Self {
+        self.strict_run_length = enabled;
+        self
+    }
+    
+    /// Reports suspected wrong child counts instead of generic errors
+    ///
+    /// A buggy or hand-edited encoder that writes the wrong child count
+    /// shifts everything after it: with too high a count, the bytes that
+    /// follow are read as one more child and fail somewhere inside it; with
+    /// too low a count, the remaining children are left after the root. With
+    /// this option, a child that fails the way such a shift makes it fail,
+    /// running into the end of the file or starting with a name index
+    /// outside the lookup table, is reported as a suspected child count
+    /// mismatch, naming the parent where the shift surfaced and its declared
+    /// count; the wrong count may be on an element before it. Other
+    /// failures, such as an invalid value type, keep their own error.
+    /// Trailing bytes that decode as whole elements become an error rather
+    /// than a warning. Errors still carry the byte offset where decoding
+    /// stopped. Has no effect in recovery mode, which keeps what it can.
+    pub fn strict_child_counts(mut self, enabled: bool) -> Self {
+        self.strict_child_counts = enabled;
+        self
+    }
+    
+    /// Fails on a second map header after the root element
+    ///
+    /// Two files joined together decode as the first one, with a warning in
+    /// [`DecodeReport::warnings`] naming the offset of the second header.
+    /// With this option that is an error instead. Has no effect in recovery
+    /// mode.
+    pub fn strict_trailing(mut self, enabled: bool) -> Self {
+        self.strict_trailing = enabled;
+        self
+    }
+    
+    /// Reads the non-vanilla f64 values written by `Encoder::double_precision`
+    ///
+    /// They use the extension type code
+    /// [`DOUBLE_TYPE_CODE`](crate::DOUBLE_TYPE_CODE), which Celeste cannot
+    /// load, so by default they fail the decode with a "non-vanilla value
+    /// type" error: a map only cairn can read is never taken for a playable
+    /// one. With this option they decode as floats. Off by default.
+    pub fn double_precision(mut self, enabled: bool) -> Self {
+        self.double_precision = enabled;
+        self
+    }
+    
+    /// Stops decoding once `max_rooms` rooms have been read
+    ///
+    /// Meant for previews of large maps: the `level` children of `levels`
+    /// are read up to the limit, and everything from the next room on is
+    /// skipped, including later sections such as `Style`. The returned map
+    /// is then intentionally incomplete: its root gets a `true`
+    /// [`TRUNCATED_ATTRIBUTE`] and [`DecodeReport::warnings`] says where
+    /// decoding stopped. Never encode such a map over the original file.
+    /// Maps with no more rooms than the limit decode in full.
+    pub fn max_rooms(mut self, max_rooms: usize) -> Self {
+        self.max_rooms = Some(max_rooms);
+        self
+    }
+    
+    /// Calls `callback` with every decoded element whose name matches `pattern`
+    ///
+    /// `pattern` is an element name in which `*` matches any run of
+    /// characters, so `"*"` matches every element and `"FrostHelper/*"` every
+    /// entity of that mod. Each element is passed once it is complete,
+    /// children included, so children are seen before their parent. The
+    /// callback may modify the element, and the full tree is still built.
+    /// Hooks run in the order they were added. Decoders without hooks pay
+    /// nothing for the feature.
+    pub fn on_element(mut self, pattern: &str, callback: impl Fn(&mut DecodedElement) + Send + Sync + 'static) -> Self {
+        self.hooks.push(ElementHook { pattern: pattern.to_string(), callback: Arc::new(callback) });
+        self
+    }
+    
+    /// Decode a binary Celeste map file
+    ///
+    /// The file size is known here, so truncation is detected as early as
+    /// the declared lengths allow, and data after the root element is
+    /// noticed as a warning in [`DecodeReport::warnings`], one that names a
+    /// second map header when two files were joined together (an error with
+    /// [`Decoder::strict_trailing`]).
+    pub fn decode<P: AsRef<Path>>(&self, path: P) -> io::Result<DecodedElement> {
+        self.decode_with_report(path).map(|report| report.map)
+    }
+    
+    /// Like [`Decoder::decode`], also returning the recovery warnings
+    pub fn decode_with_report<P: AsRef<Path>>(&self, path: P) -> io::Result<DecodeReport> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        
+        self.decode_inner(BufReader::with_capacity(self.buffer_size, file), Some(file_len))
+    }
+    
+    /// Decode a binary Celeste map from any reader
+    ///
+    /// The reader is used as is; wrap it in a `BufReader` if it is unbuffered.
+    pub fn decode_from_reader<R: Read>(&self, reader: R) -> io::Result<DecodedElement> {
+        self.decode_inner(reader, None).map(|report| report.map)
+    }
+    
+    /// Like [`count_elements`], with this decoder's format settings
+    pub fn count_elements<R: Read>(&self, reader: R) -> io::Result<usize> {
+        let mut reader = PositionReader::new(reader);
+        
+        let count = read_header(&mut reader, &self.format(), None, 5).and_then(|(_, lookup)| {
+            let mut buffer = Vec::new();
+            count_element(&mut StreamSource::new(&mut reader, &mut buffer), lookup.len(), self.double_precision)
+        });
+        
+        count.map_err(|e| wrap_error(e, &reader))
+    }
+    
+    /// Like [`decode_sections`], with this decoder's format settings
+    pub fn decode_sections<R: Read>(&self, reader: R) -> io::Result<Sections<R>> {
+        let mut reader = PositionReader::new(reader);
+        
+        let head = read_header(&mut reader, &self.format(), None, 5).and_then(|(package, lookup)| {
+            let mut ctx = DecodeContext::new(&lookup);
+            ctx.double_precision = self.double_precision;
+            let (mut root, child_count) = decode_element_head(&mut reader, &mut ctx)?;
+            root.attributes.insert("package".to_string(), Value::String(package));
+            Ok((root, lookup, child_count.unwrap_or(0)))
+        });
+        
+        let double_precision = self.double_precision;
+        match head {
+            Ok((root, lookup, remaining)) => Ok(Sections { reader, lookup, root, remaining, double_precision }),
+            Err(e) => Err(wrap_error(e, &reader)),
+        }
+    }
+    
+    /// Like [`index_map`](crate::index_map), with this decoder's format settings
+    pub fn index_map<R: Read + Seek>(&self, reader: R) -> io::Result<MapIndex<R>> {
+        index_map_with(reader, &self.format())
+    }
+    
+    /// Like [`decode_map_borrowed`](crate::decode_map_borrowed), with this
+    /// decoder's format settings
+    pub fn decode_borrowed<'a>(&self, bytes: &'a [u8]) -> io::Result<ElementRef<'a>> {
+        decode_borrowed(bytes, &self.format())
+    }
+    
+    /// Like [`decode_map_sections`](crate::decode_map_sections), with this
+    /// decoder's format settings
+    pub fn decode_borrowed_sections<'a>(&self, bytes: &'a [u8]) -> io::Result<MapSections<'a>> {
+        decode_borrowed_sections(bytes, &self.format())
+    }
+    
+    /// Whether [`Decoder::recover`] is set
+    pub(crate) fn recovers(&self) -> bool {
+        self.recover
+    }
+    
+    /// Header settings, for the decoders built on the shared primitives
+    pub(crate) fn format(&self) -> Format<'_> {
+        Format { magic: &self.magic, skip_leading_bytes: self.skip_leading_bytes, double_precision: self.double_precision }
+    }
+    
+    fn decode_inner<R: Read>(&self, reader: R, total_len: Option<u64>) -> io::Result<DecodeReport> {
+        let mut reader = PositionReader::new(reader);
+        
+        decode_body(&mut reader, total_len, self).map_err(|e| wrap_error(e, &reader))
+    }
+}
+
+/// Count the elements of a binary map without building the tree
+///
+/// Walks the stream exactly like decoding, validating lookup indices and
+/// value types along the way, but discards attribute values as it reads
+/// them. Much cheaper than `decode_map(..)?.element_count()` when only the
+/// size of the map is needed. Errors carry a [`DecodeError`] like decoding.
+/// Expects the standard header; see [`Decoder::count_elements`] for others.
+pub fn count_elements<R: Read>(reader: R) -> io::Result<usize> {
+    Decoder::new().count_elements(reader)
+}
+
+/// Decode the root's direct children one at a time
+///
+/// Reads the header, lookup table and root element up front, then yields
+/// each top-level section (`levels`, `Style`, `Filler`, ...) as it is
+/// decoded, so only one section is held in memory and the caller can stop
+/// early. Iteration ends after the first error. Expects the standard
+/// header; see [`Decoder::decode_sections`] for others.
+pub fn decode_sections<R: Read>(reader: R) -> io::Result<Sections<R>> {
+    Decoder::new().decode_sections(reader)
+}
+
+/// Iterator over the top-level sections of a map, see [`decode_sections`]
+pub struct Sections<R> {
+    reader: PositionReader<R>,
+    lookup: LookupTable,
+    root: DecodedElement,
+    remaining: usize,
+    double_precision: bool,
+}
+
+impl<R: Read> Sections<R> {
+    /// The root element with its attributes and `package`, but no children
+    pub fn root(&self) -> &DecodedElement {
+        &self.root
+    }
+}
+
+impl<R: Read> Iterator for Sections<R> {
+    type Item = io::Result<DecodedElement>;
+    
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        
+        let mut ctx = DecodeContext::new(&self.lookup);
+        ctx.double_precision = self.double_precision;
+        
+        let result = decode_element(&mut self.reader, &mut ctx);
+        self.remaining = if result.is_ok() { self.remaining - 1 } else { 0 };
+        
+        Some(result.map_err(|e| wrap_error(e, &self.reader)))
+    }
+    
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// Attach the offset to a decode error
+pub(crate) fn wrap_error<R: Read>(e: io::Error, reader: &PositionReader<R>) -> io::Error {
+    // Replace the bare EOF from read_exact, keeping our own truncation messages
+    let e = if e.kind() == io::ErrorKind::UnexpectedEof && e.get_ref().is_none() {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "File truncated: unexpected end of file")
+    } else {
+        e
+    };
+    
+    DecodeError::wrap(e, reader.position())
+}
+
+/// Read the magic string, package and lookup table
+///
+/// `root_size` is the minimum number of bytes expected after the lookup
+/// table, for the early truncation check.
+pub(crate) fn read_header<R: Read>(
+    reader: &mut PositionReader<R>,
+    format: &Format,
+    total_len: Option<u64>,
+    root_size: u64,
+) -> io::Result<(String, LookupTable)> {
+    let remaining = total_len.map(|total_len| total_len.saturating_sub(reader.position()));
+    let mut buffer = Vec::new();
+    let mut source = StreamSource::new(reader, &mut buffer).limit(remaining);
+    
+    read_magic(&mut source, format)?;
+    let package = source.string()?.to_string();
+    let lookup_len = read_lookup_len(&mut source, root_size)?;
+    
+    // One buffer for reading and one for storing, whatever the table size
+    let mut lookup = LookupTable::with_capacity(lookup_len);
+    for _ in 0..lookup_len {
+        lookup.push(source.string()?);
+    }
+    
+    Ok((package, lookup))
+}
+
+/// Reports data left in the file after the root element
+///
+/// A second map header there means two files were joined together, which
+/// fails the decode with [`Decoder::strict_trailing`] outside recovery
+/// mode. Otherwise leftover bytes are only a warning. Without a known file
+/// size nothing is checked, so readers stay positioned right after the
+/// root.
+fn check_trailing<R: Read>(
+    reader: &mut PositionReader<R>,
+    total_len: Option<u64>,
+    settings: &Decoder,
+    ctx: &mut DecodeContext,
+) -> io::Result<()> {
+    let offset = reader.position();
+    let trailing = total_len.map_or(0, |total_len| total_len.saturating_sub(offset));
+    if trailing == 0 {
+        return Ok(());
+    }
+    
+    let mut header = Vec::new();
+    write_string(&mut header, &settings.magic)?;
+    
+    let mut start = vec![0u8; header.len().min(trailing as usize)];
+    reader.read_exact(&mut start)?;
+    
+    if start != header {
+        if settings.strict_child_counts && !settings.recover {
+            let mut rest = start;
+            reader.read_to_end(&mut rest)?;
+            
+            if let Some(elements) = count_whole_elements(&rest, ctx) {
+                let message = format!(
+                    "Child count mismatch suspected: {} trailing bytes after the root element decode as {} element(s), \
+                     so an element declares fewer children than it has",
+                    trailing, elements,
+                );
+                return Err(DecodeError::wrap(io::Error::new(io::ErrorKind::InvalidData, message), offset));
+            }
+        }
+        
+        ctx.warnings.push(format!("{} trailing bytes after the root element at byte offset {}", trailing, offset));
+        return Ok(());
+    }
+    
+    let message = format!("Trailing data looks like a second {} header", settings.magic);
+    if settings.strict_trailing && !settings.recover {
+        return Err(DecodeError::wrap(io::Error::new(io::ErrorKind::InvalidData, message), offset));
+    }
+    
+    ctx.warnings.push(format!("{} at byte offset {}", message, offset));
+    Ok(())
+}
+
+/// Number of elements `bytes` decodes to, if it holds nothing else
+fn count_whole_elements(bytes: &[u8], ctx: &DecodeContext) -> Option<usize> {
+    let mut source = SliceSource::new(bytes);
+    let mut elements = 0;
+    while !source.rest().is_empty() {
+        elements += count_element(&mut source, ctx.lookup.len(), ctx.double_precision).ok()?;
+    }
+    Some(elements)
+}
+
+fn decode_body<R: Read>(
+    reader: &mut PositionReader<R>,
+    total_len: Option<u64>,
+    settings: &Decoder,
+) -> io::Result<DecodeReport> {
+    // The smallest root element (name index, attribute count, child count)
+    // takes five bytes, unless recovery may cut it short
+    let root_size = if settings.recover { 0 } else { 5 };
+    let (package, lookup) = read_header(reader, &settings.format(), total_len, root_size)?;
+    
+    let run_hooks = |element: &mut DecodedElement| {
+        for hook in &settings.hooks {
+            if name_matches(&hook.pattern, &element.name) {
+                (hook.callback)(element);
+            }
+        }
+    };
+    
+    let mut ctx = DecodeContext::new(&lookup);
+    ctx.recover = settings.recover;
+    ctx.strict_run_length = settings.strict_run_length;
+    ctx.strict_child_counts = settings.strict_child_counts && !settings.recover;
+    ctx.double_precision = settings.double_precision;
+    if !settings.hooks.is_empty() {
+        ctx.on_element = Some(&run_hooks);
+    }
+    if settings.lookup_usage || settings.unused_lookup {
+        ctx.usage = Some(vec![0; lookup.len()]);
+    }
+    
+    let (mut map, truncated) = match settings.max_rooms {
+        Some(max_rooms) => decode_rooms_up_to(reader, &mut ctx, max_rooms)?,
+        None => (decode_element(reader, &mut ctx)?, false),
+    };
+    map.attributes.insert("package".to_string(), Value::String(package));
+    
+    // Unread elements would make their strings look unused
+    let complete = !truncated && !ctx.cut_short;
+    let usage = ctx.usage.take().unwrap_or_default();
+    
+    let mut unused_lookup = Vec::new();
+    if settings.unused_lookup && complete {
+        for (index, (s, count)) in lookup.iter().zip(&usage).enumerate() {
+            if *count == 0 {
+                ctx.warnings.push(format!("Lookup string {:?} at index {} is never referenced", s, index));
+                unused_lookup.push(s.to_string());
+            }
+        }
+    }
+    
+    if truncated {
+        map.attributes.insert(TRUNCATED_ATTRIBUTE.to_string(), Value::Bool(true));
+        let max_rooms = settings.max_rooms.unwrap_or_default();
+        ctx.warnings.push(format!("Room limit {} reached, stopped at byte offset {}", max_rooms, reader.position()));
+    } else if !ctx.cut_short {
+        // After a recovered error the rest of the file was skipped, not left over
+        check_trailing(reader, total_len, settings, &mut ctx)?;
+    }
+    
+    // Repeated table entries add up under the same string
+    let mut lookup_usage = HashMap::new();
+    if settings.lookup_usage {
+        for (s, count) in lookup.iter().zip(usage) {
+            *lookup_usage.entry(s.to_string()).or_insert(0) += count;
+        }
+    }
+    
+    let lookup = if settings.keep_lookup { lookup.iter().map(str::to_string).collect() } else { Vec::new() };
+    
+    Ok(DecodeReport { map, warnings: ctx.warnings, lookup_usage, unused_lookup, lookup })
+}
+
+/// Decode the root element, stopping once `max_rooms` rooms have been read
+///
+/// Reads element heads and children one at a time so it can stop right
+/// before the first room over the limit. Returns whether it did.
+fn decode_rooms_up_to<R: Read>(
+    reader: &mut R,
+    ctx: &mut DecodeContext,
+    max_rooms: usize,
+) -> io::Result<(DecodedElement, bool)> {
+    let (mut root, section_count) = decode_element_head(reader, ctx)?;
+    let mut rooms = 0;
+    
+    for _ in 0..section_count.unwrap_or(0) {
+        let (mut section, child_count) = decode_element_head(reader, ctx)?;
+        let is_levels = section.name == "levels";
+        
+        for _ in 0..child_count.unwrap_or(0) {
+            if is_levels && rooms == max_rooms {
+                ctx.observe(&mut section);
+                root.children.get_or_insert_with(Vec::new).push(section);
+                ctx.observe(&mut root);
+                return Ok((root, true));
+            }
+            
+            let child = decode_element(reader, ctx)?;
+            if is_levels && child.name == "level" {
+                rooms += 1;
+            }
+            section.children.get_or_insert_with(Vec::new).push(child);
+            
+            if ctx.cut_short {
+                break;
+            }
+        }
+        
+        ctx.observe(&mut section);
+        root.children.get_or_insert_with(Vec::new).push(section);
+        
+        // Recovery stopped inside this section, see `decode_element`
+        if ctx.cut_short {
+            break;
+        }
+    }
+    
+    ctx.observe(&mut root);
+    Ok((root, false))
+}
+
+/// Matches an element name against a pattern where `*` stands for any run
+/// of characters
+fn name_matches(pattern: &str, name: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == name;
+    };
+    let Some(mut remaining) = name.strip_prefix(prefix) else {
+        return false;
+    };
+    
+    let mut parts: Vec<_> = rest.split('*').collect();
+    let suffix = parts.pop().unwrap_or_default();
+    
+    for part in parts {
+        match remaining.find(part) {
+            Some(at) => remaining = &remaining[at + part.len()..],
+            None => return false,
+        }
+    }
+    
+    remaining.len() >= suffix.len() && remaining.ends_with(suffix)
+}
\ No newline at end of file
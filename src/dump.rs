@@ -0,0 +1,147 @@
+use std::io::{self, Read, Write};
+
+use crate::binary::{decode_value, PositionReader, SliceSource, Source, DOUBLE_TYPE_CODE};
+
+/// Raw bytes shown per line before the rest is elided
+const HEX_BYTES: usize = 12;
+
+/// Writes an annotated byte-level breakdown of a binary map to `out`
+///
+/// Every field gets one line with its offset, its raw bytes and what they
+/// decode to: the header, package, each lookup entry with its index, and
+/// each element with its attributes (key, type code, value) and child
+/// count, indented by depth. Meant for comparing files from different
+/// sources, so decoding stops at the first error after printing what was
+/// read so far.
+pub fn dump_structure<W: Write>(bytes: &[u8], out: &mut W) -> io::Result<()> {
+    let mut dump = Dump { bytes, reader: PositionReader::new(bytes), out };
+    
+    let header = dump.string()?;
+    dump.line(header.0, &format!("header {:?}", header.1))?;
+    
+    let package = dump.string()?;
+    dump.line(package.0, &format!("package {:?}", package.1))?;
+    
+    let (start, count) = dump.field(|r| read_u16(r))?;
+    dump.line(start, &format!("lookup table, {} strings", count))?;
+    
+    let mut lookup = Vec::with_capacity(count as usize);
+    for index in 0..count {
+        let (start, s) = dump.string()?;
+        dump.line(start, &format!("  [{}] {:?}", index, s))?;
+        lookup.push(s);
+    }
+    
+    dump.element(&lookup, 0)?;
+    
+    let trailing = bytes.len() as u64 - dump.reader.position();
+    if trailing > 0 {
+        writeln!(dump.out, "{} trailing bytes after the root element", trailing)?;
+    }
+    
+    Ok(())
+}
+
+struct Dump<'a, W> {
+    bytes: &'a [u8],
+    reader: PositionReader<&'a [u8]>,
+    out: &'a mut W,
+}
+
+impl<W: Write> Dump<'_, W> {
+    /// Runs `read` and returns the offset it started at with its result
+    fn field<T>(&mut self, read: impl FnOnce(&mut PositionReader<&[u8]>) -> io::Result<T>) -> io::Result<(u64, T)> {
+        let start = self.reader.position();
+        let value = read(&mut self.reader)?;
+        Ok((start, value))
+    }
+    
+    /// Reads a length-prefixed string and returns the offset it started at
+    ///
+    /// The length is checked against the bytes left before anything is
+    /// allocated, so a corrupt prefix is an error rather than a huge buffer.
+    fn string(&mut self) -> io::Result<(u64, String)> {
+        let start = self.reader.position();
+        let mut source = SliceSource::new(&self.bytes[start as usize..]);
+        let s = source.string()?.to_string();
+        
+        io::copy(&mut (&mut self.reader).take(source.position()), &mut io::sink())?;
+        Ok((start, s))
+    }
+    
+    /// Prints one annotated line for the bytes from `start` to the current position
+    fn line(&mut self, start: u64, description: &str) -> io::Result<()> {
+        let end = self.reader.position() as usize;
+        let field = &self.bytes[start as usize..end];
+        
+        let mut hex: Vec<_> = field.iter().take(HEX_BYTES).map(|b| format!("{:02x}", b)).collect();
+        if field.len() > HEX_BYTES {
+            hex.push(format!("+{}", field.len() - HEX_BYTES));
+        }
+        
+        writeln!(self.out, "{:08x}  {:<40}  {}", start, hex.join(" "), description)
+    }
+    
+    fn element(&mut self, lookup: &[String], depth: usize) -> io::Result<()> {
+        let indent = "  ".repeat(depth);
+        
+        let (start, name) = self.field(|r| read_u16(r).map(|i| lookup_name(lookup, i)))?;
+        self.line(start, &format!("{}element {}", indent, name))?;
+        
+        let (start, attributes) = self.field(|r| {
+            let mut count = [0u8; 1];
+            r.read_exact(&mut count)?;
+            Ok(count[0])
+        })?;
+        self.line(start, &format!("{}  {} attributes", indent, attributes))?;
+        
+        for _ in 0..attributes {
+            let (start, (key, type_code, value)) = self.field(|r| {
+                let key = read_u16(r)?;
+                let mut type_code = [0u8; 1];
+                r.read_exact(&mut type_code)?;
+                let value = decode_value(type_code[0], lookup, r)?;
+                Ok((lookup_name(lookup, key), type_code[0], value))
+            })?;
+            self.line(start, &format!("{}  {} = {} ({})", indent, key, value, type_name(type_code)))?;
+        }
+        
+        let (start, children) = self.field(|r| read_u16(r))?;
+        self.line(start, &format!("{}  {} children", indent, children))?;
+        
+        for _ in 0..children {
+            self.element(lookup, depth + 1)?;
+        }
+        
+        Ok(())
+    }
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+/// Lookup string for an index, or a marker when it is out of range
+fn lookup_name(lookup: &[String], index: u16) -> String {
+    match lookup.get(index as usize) {
+        Some(s) => format!("{} (#{})", s, index),
+        None => format!("<invalid #{}>", index),
+    }
+}
+
+fn type_name(type_code: u8) -> &'static str {
+    match type_code {
+        0 => "bool",
+        1 => "u8",
+        2 => "i16",
+        3 => "i32",
+        4 => "f32",
+        5 => "lookup",
+        6 => "string",
+        7 => "rle string",
+        DOUBLE_TYPE_CODE => "f64, non-vanilla",
+        _ => "unknown",
+    }
+}
\ No newline at end of file
@@ -1,9 +1,47 @@
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::color::Color;
+use crate::kind::ElementKind;
+use crate::validate::normalize_package;
+
+/// Attribute holding an editor comment, see [`DecodedElement::set_comment`]
+pub const COMMENT_ATTRIBUTE: &str = "__comment";
+
+/// Attribute whose string value is stored inline, run-length encoded when
+/// that is smaller, rather than in the lookup table
+pub(crate) const RUN_LENGTH_ATTRIBUTE: &str = "innerText";
+
+/// Attribute value by the type Celeste reads it as, see
+/// [`DecodedElement::typed_attributes`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypedValue<'a> {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(&'a str),
+}
+
+impl<'a> TypedValue<'a> {
+    /// Types a JSON value; integers that fit an i64 are `Int`, other numbers
+    /// `Float`. Null, arrays and objects have no typed form.
+    pub fn from_value(value: &'a Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(TypedValue::Bool(*b)),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => Some(TypedValue::Int(i)),
+                None => n.as_f64().map(TypedValue::Float),
+            },
+            Value::String(s) => Some(TypedValue::Str(s)),
+            _ => None,
+        }
+    }
+}
 
 /// Represents a decoded element from a Celeste map file
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DecodedElement {
     #[serde(rename = "__name")]
     pub name: String,
@@ -11,6 +49,62 @@ pub struct DecodedElement {
     pub attributes: HashMap<String, Value>,
     #[serde(rename = "__children", skip_serializing_if = "Option::is_none")]
     pub children: Option<Vec<DecodedElement>>,
+    /// Set by the mutators below; never serialized
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Clone for DecodedElement {
+    /// Clones the element tree. The copy starts out clean, since it has not
+    /// been modified since it was created.
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            attributes: self.attributes.clone(),
+            children: self.children.clone(),
+            dirty: false,
+        }
+    }
+}
+
+impl PartialEq for DecodedElement {
+    /// Structural equality over name, attributes and children. Attribute
+    /// order never matters; the dirty flag is ignored.
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.attributes == other.attributes
+            && self.children == other.children
+    }
+}
+
+impl Eq for DecodedElement {}
+
+impl Hash for DecodedElement {
+    /// Hashes what `semantically_eq` compares, so elements can go in a
+    /// `HashSet` or be `HashMap` keys.
+    ///
+    /// The name, the attributes sorted by key, and the children in order are
+    /// hashed. Like `semantically_eq`, the hash skips `__` attributes and
+    /// `null` values and treats no `__children` like an empty list; `1` and
+    /// `1.0` hash differently. `==` is stricter: it also compares those
+    /// skipped attributes, so two elements differing only in a `__comment`
+    /// hash the same but are distinct set entries; wrap elements in
+    /// [`SemanticKey`] to deduplicate them by `semantically_eq` instead.
+    /// Elements that are `==` always hash the same. For a hash that is
+    /// stable across runs and builds, use [`content_hash`](crate::content_hash).
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut attributes: Vec<_> = self.encoded_attributes().into_iter().collect();
+        attributes.sort_by_key(|(key, _)| *key);
+
+        self.name.hash(state);
+        attributes.len().hash(state);
+        for (key, value) in attributes {
+            key.hash(state);
+            hash_value(value, state);
+        }
+
+        self.children.as_deref().unwrap_or(&[]).hash(state);
+    }
 }
 
 impl DecodedElement {
@@ -20,16 +114,413 @@ impl DecodedElement {
             name: name.into(),
             attributes: HashMap::new(),
             children: None,
+            dirty: false,
+        }
+    }
+
+    /// Classifies the element by name, see [`ElementKind`]
+    pub fn kind(&self) -> ElementKind {
+        ElementKind::from_name(&self.name)
+    }
+
+    /// Sets an attribute and marks the element as dirty
+    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.attributes.insert(key.into(), value.into());
+        self.dirty = true;
+    }
+
+    /// Sets an integer attribute, encoded as the smallest of u8, i16 and i32
+    /// that holds it
+    ///
+    /// The format has no wider integer type: values outside the i32 range
+    /// are written as f32, as for any other JSON integer.
+    pub fn set_int(&mut self, key: impl Into<String>, value: i64) {
+        self.set_attribute(key, value);
+    }
+
+    /// Sets a float attribute, always encoded as f32
+    ///
+    /// Unlike `set_attribute(key, 16)`, `set_float(key, 16.0)` keeps the
+    /// value a float on save. Non-finite values have no JSON form and are
+    /// stored as `null`, which the encoder skips.
+    pub fn set_float(&mut self, key: impl Into<String>, value: f64) {
+        self.set_attribute(key, value);
+    }
+
+    /// Sets a boolean attribute
+    pub fn set_bool(&mut self, key: impl Into<String>, value: bool) {
+        self.set_attribute(key, value);
+    }
+
+    /// Sets a string attribute, kept a string even when it looks like a number
+    pub fn set_string(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.set_attribute(key, value.into());
+    }
+
+    /// Appends a child element and marks the element as dirty
+    pub fn add_child(&mut self, child: DecodedElement) {
+        self.children.get_or_insert_with(Vec::new).push(child);
+        self.dirty = true;
+    }
+
+    /// Removes and returns the child at `index`, marking the element as dirty
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove_child(&mut self, index: usize) -> DecodedElement {
+        let children = self.children.as_mut().expect("element has no children");
+        let child = children.remove(index);
+        self.dirty = true;
+        child
+    }
+
+    /// Returns true if this element or any of its descendants was modified
+    /// through `set_attribute`/`add_child` since it was decoded, deserialized,
+    /// cloned, or last marked clean.
+    ///
+    /// Direct writes to the public fields are not tracked.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+            || self
+                .children
+                .iter()
+                .flatten()
+                .any(|child| child.is_dirty())
+    }
+
+    /// Flags the element as modified, for edits made outside the mutators
+    pub(crate) fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Clears the dirty flag on this element and all of its descendants,
+    /// typically after the map has been re-encoded
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+
+        for child in self.children.iter_mut().flatten() {
+            child.mark_clean();
+        }
+    }
+
+    /// Reads a string attribute
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).and_then(Value::as_str)
+    }
+
+    /// Reads a numeric attribute as f64, whether stored as an integer or float
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.attributes.get(key).and_then(Value::as_f64)
+    }
+
+    /// Reads an integer attribute; whole floats such as `3.0` are accepted
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        let value = self.attributes.get(key)?;
+        value.as_i64().or_else(|| value.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+    }
+
+    /// Reads a boolean attribute stored either as a bool or as a string
+    ///
+    /// Accepts `Value::Bool` as well as the strings `"true"`/`"false"` in any
+    /// case (C# writes `"True"`). Returns `None` for anything else. This only
+    /// affects reading: encoding never converts between the two forms, so a
+    /// string stays a string and round-trips unchanged.
+    pub fn get_bool_lenient(&self, key: &str) -> Option<bool> {
+        match self.attributes.get(key)? {
+            Value::Bool(b) => Some(*b),
+            Value::String(s) if s.eq_ignore_ascii_case("true") => Some(true),
+            Value::String(s) if s.eq_ignore_ascii_case("false") => Some(false),
+            _ => None,
         }
     }
 
+    /// Attributes in key order, leaving out `__`-prefixed ones
+    pub fn sorted_attributes(&self) -> impl Iterator<Item = (&str, &Value)> {
+        let mut attributes: Vec<_> = self.attributes.iter()
+            .filter(|(key, _)| !key.starts_with("__"))
+            .map(|(key, value)| (key.as_str(), value))
+            .collect();
+        attributes.sort_by_key(|(key, _)| *key);
+        attributes.into_iter()
+    }
+
+    /// Attributes in key order with their [`TypedValue`], as a property
+    /// inspector would list them
+    ///
+    /// Follows [`DecodedElement::sorted_attributes`], skipping values with no
+    /// typed form: nulls, which the encoder drops, and arrays or objects.
+    pub fn typed_attributes(&self) -> impl Iterator<Item = (&str, TypedValue<'_>)> {
+        self.sorted_attributes().filter_map(|(key, value)| Some((key, TypedValue::from_value(value)?)))
+    }
+
+    /// Reads a hex color attribute such as `"ff0000"` or `"ffffff80"`
+    ///
+    /// Returns `None` when the attribute is missing, not a string, or not a
+    /// valid color; see [`Color::from_attr`].
+    pub fn get_color(&self, key: &str) -> Option<Color> {
+        self.get_str(key).and_then(Color::from_attr)
+    }
+
+    /// Sets a color attribute in the hex form of [`Color::to_attr`]
+    pub fn set_color(&mut self, key: impl Into<String>, color: Color) {
+        self.set_attribute(key, color.to_attr());
+    }
+
+    /// Reads the editor comment stored in `__comment`
+    pub fn comment(&self) -> Option<&str> {
+        self.attributes.get(COMMENT_ATTRIBUTE).and_then(Value::as_str)
+    }
+
+    /// Attaches an editor comment as `__comment`
+    ///
+    /// Like every `__`-prefixed attribute it is kept in JSON but never written
+    /// to the binary map; `json_to_bin` saves it to the metadata sidecar.
+    pub fn set_comment(&mut self, comment: impl Into<String>) {
+        self.set_attribute(COMMENT_ATTRIBUTE, comment.into());
+    }
+
+    /// Sets the map's `package`, normalized the way Everest expects
+    ///
+    /// Backslashes become forward slashes, whitespace becomes underscores,
+    /// and invalid file name characters and a `.bin` extension are dropped,
+    /// so `"My Mod\\1-Intro.bin"` is stored as `"My_Mod/1-Intro"`.
+    pub fn set_package(&mut self, package: &str) {
+        self.set_attribute("package", normalize_package(package));
+    }
+
+    /// Returns the first direct child with the given name
+    pub fn find_child(&self, name: &str) -> Option<&DecodedElement> {
+        self.children.iter().flatten().find(|child| child.name == name)
+    }
+
+    /// Iterates the children of this room's `entities` container
+    ///
+    /// Meant to be called on a `level` element; yields nothing when the
+    /// container is absent. Same for the other layer methods below.
+    pub fn entities(&self) -> impl Iterator<Item = &DecodedElement> {
+        self.container_children("entities")
+    }
+
+    /// Iterates the children of this room's `triggers` container
+    pub fn triggers(&self) -> impl Iterator<Item = &DecodedElement> {
+        self.container_children("triggers")
+    }
+
+    /// Iterates the children of this room's `fgdecals` container
+    pub fn fg_decals(&self) -> impl Iterator<Item = &DecodedElement> {
+        self.container_children("fgdecals")
+    }
+
+    /// Iterates the children of this room's `bgdecals` container
+    pub fn bg_decals(&self) -> impl Iterator<Item = &DecodedElement> {
+        self.container_children("bgdecals")
+    }
+
+    fn container_children(&self, name: &str) -> impl Iterator<Item = &DecodedElement> {
+        self.find_child(name)
+            .into_iter()
+            .flat_map(|container| container.children.iter().flatten())
+    }
+
+    /// Iterates the `level` elements inside the map's `levels` container
+    pub(crate) fn rooms(&self) -> impl Iterator<Item = &DecodedElement> {
+        self.children.iter().flatten()
+            .filter(|child| child.name == "levels")
+            .flat_map(|levels| levels.children.iter().flatten())
+    }
+
+    /// Mutable version of `rooms`
+    pub(crate) fn rooms_mut(&mut self) -> impl Iterator<Item = &mut DecodedElement> {
+        self.children.iter_mut().flatten()
+            .filter(|child| child.name == "levels")
+            .flat_map(|levels| levels.children.iter_mut().flatten())
+    }
+
+    /// Iterates all descendants depth-first, parents before children
+    ///
+    /// The element itself is not included.
+    pub fn descendants(&self) -> Descendants<'_> {
+        let mut stack: Vec<_> = self.children.iter().flatten().collect();
+        stack.reverse();
+        Descendants { stack }
+    }
+
+    /// Counts this element and all of its descendants
+    pub fn element_count(&self) -> usize {
+        1 + self.children.iter().flatten().map(|child| child.element_count()).sum::<usize>()
+    }
+
+    /// Deep-clones the subtree, giving every `id` attribute a fresh value
+    ///
+    /// `id_allocator` is called once per element that has an `id`, in
+    /// document order with parents before children. Nothing but `id` is
+    /// touched: attributes that refer to ids, such as those in
+    /// [`REFERENCE_ATTRIBUTES`](crate::REFERENCE_ATTRIBUTES), keep pointing
+    /// at the old ids and must be fixed up separately. Elements whose id was
+    /// replaced are marked dirty.
+    pub fn clone_with_new_ids(&self, id_allocator: &mut impl FnMut() -> i64) -> DecodedElement {
+        let mut copy = self.clone();
+
+        copy.visit_mut(&mut |element| {
+            if element.attributes.contains_key("id") {
+                element.set_attribute("id", id_allocator());
+            }
+        });
+
+        copy
+    }
+
+    /// Calls `f` on this element and every descendant, parents before children
+    pub fn visit_mut(&mut self, f: &mut impl FnMut(&mut DecodedElement)) {
+        f(self);
+
+        for child in self.children.iter_mut().flatten() {
+            child.visit_mut(f);
+        }
+    }
+
+    /// Like `visit_mut`, but also passes the path of ancestors to `f`
+    ///
+    /// Each path segment is the ancestor's `name` attribute when it has a
+    /// string one (so rooms show up as e.g. `lvl_3`), and its element name
+    /// otherwise. An entity in room `lvl_3` is visited with the path
+    /// `["Map", "levels", "lvl_3", "entities"]`; the root gets an empty path.
+    pub fn visit_mut_with_path(&mut self, f: &mut impl FnMut(&[&str], &mut DecodedElement)) {
+        let mut path = Vec::new();
+        self.visit_mut_with_path_inner(&mut path, f);
+    }
+
+    fn visit_mut_with_path_inner(
+        &mut self,
+        path: &mut Vec<String>,
+        f: &mut impl FnMut(&[&str], &mut DecodedElement),
+    ) {
+        let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+        f(&segments, self);
+
+        let Some(children) = self.children.as_mut() else {
+            return;
+        };
+
+        let segment = match self.attributes.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => self.name.clone(),
+        };
+
+        path.push(segment);
+        for child in children {
+            child.visit_mut_with_path_inner(path, f);
+        }
+        path.pop();
+    }
+
+    /// Compares two elements the way the binary encoder sees them.
+    ///
+    /// Names must match, attributes are compared regardless of order, and
+    /// children are compared recursively in order. Attributes that the encoder
+    /// drops are ignored on both sides: keys starting with `__` and `null`
+    /// values. A missing `__children` list equals an empty one, since both
+    /// encode to a child count of zero.
+    ///
+    /// Values must be exactly equal, so `1` and `1.0` differ; see
+    /// [`DecodedElement::semantically_eq_with_tolerance`] for float slack.
+    pub fn semantically_eq(&self, other: &Self) -> bool {
+        self.semantically_eq_with_tolerance(other, 0.0)
+    }
+
+    /// Like `semantically_eq`, but numbers within `epsilon` compare equal
+    ///
+    /// Two numeric attributes match when `|a - b| <= epsilon` after
+    /// conversion to f64, so `1.0000001` equals `1.0` with an epsilon of
+    /// `1e-6`, and an integer can equal a float. With an epsilon of `0.0`
+    /// the comparison is exact, as in `semantically_eq`. Non-numeric values,
+    /// including numbers nested in arrays or objects, are always compared
+    /// exactly.
+    pub fn semantically_eq_with_tolerance(&self, other: &Self, epsilon: f64) -> bool {
+        if self.name != other.name {
+            return false;
+        }
+
+        let attributes = self.encoded_attributes();
+        let other_attributes = other.encoded_attributes();
+
+        if attributes.len() != other_attributes.len() {
+            return false;
+        }
+
+        let attributes_eq = attributes.iter().all(|(key, value)| {
+            other_attributes.get(key).is_some_and(|other_value| values_eq(value, other_value, epsilon))
+        });
+        if !attributes_eq {
+            return false;
+        }
+
+        let children = self.children.as_deref().unwrap_or(&[]);
+        let other_children = other.children.as_deref().unwrap_or(&[]);
+
+        children.len() == other_children.len()
+            && children.iter().zip(other_children).all(|(a, b)| a.semantically_eq_with_tolerance(b, epsilon))
+    }
+
+    /// Sparse JSON of what differs from `base`, for reviewing a derived map
+    ///
+    /// Each changed element appears as an object with its `__name`, its
+    /// position in this tree as `__index` (except the root), and only the
+    /// attributes that were added or changed; removed attributes are
+    /// `null`. Its `__children` list only holds the children that differ.
+    /// Added children appear in full with `"__added": true`, removed ones as
+    /// `{"__name", "__index", "__removed": true}` with their index in `base`,
+    /// after the other entries.
+    ///
+    /// Children are matched by name and their `name` attribute (rooms) or
+    /// `id` (entities), falling back to their order among siblings of the
+    /// same name, so inserting an entity does not mark every later sibling
+    /// as changed. Attributes are compared as in `semantically_eq`. Returns
+    /// `null` when nothing differs.
+    pub fn to_json_diff_against(&self, base: &DecodedElement) -> Value {
+        if self.name != base.name {
+            let mut replaced = added_json(self);
+            replaced.insert("__replaces".to_string(), Value::String(base.name.clone()));
+            return Value::Object(replaced);
+        }
+
+        diff_element(self, base).map_or(Value::Null, Value::Object)
+    }
+
+    /// Attributes that survive binary encoding
+    fn encoded_attributes(&self) -> HashMap<&String, &Value> {
+        self.attributes.iter()
+            .filter(|(k, _)| !k.starts_with("__"))
+            .filter(|(_, v)| !v.is_null())
+            .collect()
+    }
+
     /// Collect all string keys for lookup table
     pub fn collect_keys(&self, seen: &mut HashSet<String>) {
+        self.collect_keys_with(seen, false, None);
+    }
+
+    /// Like `collect_keys`, optionally keeping `__`-prefixed attributes and
+    /// leaving out the subtrees of children matching `skip`
+    pub(crate) fn collect_keys_with(
+        &self,
+        seen: &mut HashSet<String>,
+        include_underscore: bool,
+        skip: Option<&dyn Fn(&DecodedElement) -> bool>,
+    ) {
         seen.insert(self.name.clone());
         
         for (key, value) in &self.attributes {
-            if !key.starts_with("__") {
-                seen.insert(key.clone());
+            // Editor-only attributes are never encoded, so neither their
+            // key nor their value belongs in the table
+            if key.starts_with("__") && !include_underscore {
+                continue;
+            }
+            
+            seen.insert(key.clone());
+            
+            // Tile data is unique per layer and run-length encoded instead
+            if key == RUN_LENGTH_ATTRIBUTE {
+                continue;
             }
             
             if let Value::String(s) = value {
@@ -39,8 +530,197 @@ impl DecodedElement {
         
         if let Some(children) = &self.children {
             for child in children {
-                child.collect_keys(seen);
+                if !skip.is_some_and(|skip| skip(child)) {
+                    child.collect_keys_with(seen, include_underscore, skip);
+                }
             }
         }
     }
+}
+
+/// A [`DecodedElement`] whose equality is [`DecodedElement::semantically_eq`]
+///
+/// As set entries or map keys, elements then match whenever they encode to
+/// the same binary: `__` attributes and `null` values are ignored on both
+/// sides, and no `__children` equals an empty list. Attribute order never
+/// matters and child order always does; `1` and `1.0` stay different. The
+/// hash is the element's own, which already skips what `semantically_eq`
+/// ignores.
+///
+/// ```
+/// use cairn::{DecodedElement, SemanticKey};
+/// use std::collections::HashSet;
+///
+/// let mut decal = DecodedElement::new("decal");
+/// decal.set_attribute("texture", "flag");
+/// let mut commented = decal.clone();
+/// commented.set_comment("keep");
+///
+/// let unique: HashSet<_> = [decal, commented].into_iter().map(SemanticKey).collect();
+/// assert_eq!(unique.len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SemanticKey(pub DecodedElement);
+
+impl PartialEq for SemanticKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.semantically_eq(&other.0)
+    }
+}
+
+impl Eq for SemanticKey {}
+
+impl Hash for SemanticKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// Attribute value comparison for `semantically_eq_with_tolerance`
+fn values_eq(a: &Value, b: &Value, epsilon: f64) -> bool {
+    if epsilon > 0.0 {
+        if let (Value::Number(a), Value::Number(b)) = (a, b) {
+            if let (Some(a), Some(b)) = (a.as_f64(), b.as_f64()) {
+                return (a - b).abs() <= epsilon;
+            }
+        }
+    }
+
+    a == b
+}
+
+/// Hashes a JSON value consistently with its `==`, which ignores the
+/// order of object keys
+fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    std::mem::discriminant(value).hash(state);
+
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => b.hash(state),
+        Value::Number(n) => n.hash(state),
+        Value::String(s) => s.hash(state),
+        Value::Array(items) => {
+            items.len().hash(state);
+            for item in items {
+                hash_value(item, state);
+            }
+        }
+        Value::Object(fields) => {
+            let mut keys: Vec<_> = fields.keys().collect();
+            keys.sort();
+
+            keys.len().hash(state);
+            for key in keys {
+                key.hash(state);
+                hash_value(&fields[key], state);
+            }
+        }
+    }
+}
+
+/// Iterator returned by [`DecodedElement::descendants`]
+pub struct Descendants<'a> {
+    stack: Vec<&'a DecodedElement>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a DecodedElement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let element = self.stack.pop()?;
+        self.stack.extend(element.children.iter().flatten().rev());
+        Some(element)
+    }
+}
+
+/// Identity used to match children between two trees
+fn child_key(element: &DecodedElement) -> (String, Option<Value>) {
+    let identity = ["name", "id"].iter()
+        .find_map(|key| element.attributes.get(*key).filter(|v| v.is_string() || v.is_number()));
+    (element.name.clone(), identity.cloned())
+}
+
+/// Pairs each child with its counterpart, by key and then by occurrence
+fn child_keys(children: &[DecodedElement]) -> Vec<((String, Option<Value>), usize)> {
+    let mut seen: HashMap<(String, Option<Value>), usize> = HashMap::new();
+    children.iter()
+        .map(|child| {
+            let key = child_key(child);
+            let occurrence = seen.entry(key.clone()).or_insert(0);
+            *occurrence += 1;
+            (key, *occurrence)
+        })
+        .collect()
+}
+
+/// Changed attributes and children of two elements with the same name
+fn diff_element(element: &DecodedElement, base: &DecodedElement) -> Option<Map<String, Value>> {
+    let attributes = element.encoded_attributes();
+    let base_attributes = base.encoded_attributes();
+
+    let mut changed: Vec<_> = attributes.iter()
+        .filter(|(key, value)| base_attributes.get(*key) != Some(*value))
+        .map(|(key, value)| ((*key).clone(), (*value).clone()))
+        .collect();
+    changed.extend(
+        base_attributes.keys()
+            .filter(|key| !attributes.contains_key(*key))
+            .map(|key| ((*key).clone(), Value::Null)),
+    );
+    changed.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let children = element.children.as_deref().unwrap_or(&[]);
+    let base_children = base.children.as_deref().unwrap_or(&[]);
+    let keys = child_keys(children);
+    let base_keys = child_keys(base_children);
+
+    // Keys are unique within a side, so each one maps to a single index
+    let base_indices: HashMap<_, _> = base_keys.iter().enumerate().map(|(index, key)| (key, index)).collect();
+    let key_set: HashSet<_> = keys.iter().collect();
+
+    let mut child_diffs = Vec::new();
+    for (index, (child, key)) in children.iter().zip(&keys).enumerate() {
+        let entry = match base_indices.get(key) {
+            Some(&base_index) => diff_element(child, &base_children[base_index]),
+            None => {
+                let mut added = added_json(child);
+                added.insert("__added".to_string(), Value::Bool(true));
+                Some(added)
+            }
+        };
+
+        if let Some(mut entry) = entry {
+            entry.insert("__index".to_string(), Value::from(index));
+            child_diffs.push(Value::Object(entry));
+        }
+    }
+    for (base_index, (base_child, base_key)) in base_children.iter().zip(&base_keys).enumerate() {
+        if !key_set.contains(base_key) {
+            let mut removed = Map::new();
+            removed.insert("__name".to_string(), Value::String(base_child.name.clone()));
+            removed.insert("__index".to_string(), Value::from(base_index));
+            removed.insert("__removed".to_string(), Value::Bool(true));
+            child_diffs.push(Value::Object(removed));
+        }
+    }
+
+    if changed.is_empty() && child_diffs.is_empty() {
+        return None;
+    }
+
+    let mut diff = Map::new();
+    diff.insert("__name".to_string(), Value::String(element.name.clone()));
+    diff.extend(changed);
+    if !child_diffs.is_empty() {
+        diff.insert("__children".to_string(), Value::Array(child_diffs));
+    }
+    Some(diff)
+}
+
+/// Full JSON object of an element, as `bin_to_json` writes it
+fn added_json(element: &DecodedElement) -> Map<String, Value> {
+    match serde_json::to_value(element) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
 }
\ No newline at end of file
@@ -0,0 +1,415 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::binary::{encode_element, write_var_length, CountingWriter, EncodeContext, DEFAULT_RLE_MIN_SAVINGS};
+use crate::decoder::{DEFAULT_BUFFER_SIZE, DEFAULT_MAGIC};
+use crate::element::DecodedElement;
+use crate::fields::FieldTypes;
+use crate::map::write_header;
+use crate::validate::package_attribute;
+
+/// Package and lookup table computed once per encode
+struct Prepared {
+    package: String,
+    lookup: Vec<String>,
+    lookup_map: HashMap<String, usize>,
+}
+
+/// Lookup strings an edit made unnecessary, see [`Encoder::dropped_strings`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DroppedStrings {
+    /// The strings no longer in the table, sorted
+    pub strings: Vec<String>,
+    /// Their combined length in bytes
+    pub total_len: u64,
+    /// Bytes the lookup table shrinks by, counting each length prefix
+    pub table_bytes: u64,
+}
+
+/// Predicate set with [`Encoder::skip_elements`]
+#[derive(Clone)]
+struct ElementFilter(Arc<dyn Fn(&DecodedElement) -> bool + Send + Sync>);
+
+impl fmt::Debug for ElementFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ElementFilter(..)")
+    }
+}
+
+/// Configurable binary map encoder
+///
+/// `encode_map` uses the default settings; build an `Encoder` to change them.
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    buffer_size: usize,
+    seed_lookup: Option<Vec<String>>,
+    field_types: Option<FieldTypes>,
+    canonical_child_order: bool,
+    sort_entities: bool,
+    strict_nulls: bool,
+    atomic: bool,
+    rle_min_savings: Option<usize>,
+    preserve_underscore_attrs: bool,
+    magic: String,
+    skip_elements: Option<ElementFilter>,
+    double_precision: bool,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            seed_lookup: None,
+            field_types: None,
+            canonical_child_order: false,
+            sort_entities: false,
+            strict_nulls: false,
+            atomic: false,
+            rle_min_savings: Some(DEFAULT_RLE_MIN_SAVINGS),
+            preserve_underscore_attrs: false,
+            magic: DEFAULT_MAGIC.to_string(),
+            skip_elements: None,
+            double_precision: false,
+        }
+    }
+}
+
+impl Encoder {
+    /// Creates an encoder with the default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Sets the write buffer size used by [`Encoder::encode`]
+    ///
+    /// Defaults to [`DEFAULT_BUFFER_SIZE`].
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size;
+        self
+    }
+    
+    /// Starts the lookup table from `lookup` instead of an empty table
+    ///
+    /// Seeded strings keep their indices, even when the map does not use them,
+    /// and strings missing from the seed are appended after it in sorted order.
+    /// Seeding with the table of a reference file reproduces its lookup
+    /// section byte for byte.
+    pub fn with_seed_lookup(mut self, lookup: Vec<String>) -> Self {
+        self.seed_lookup = Some(lookup);
+        self
+    }
+    
+    /// Forces the numeric encoding of the attributes listed in `field_types`
+    ///
+    /// Without a table every number is encoded by value, so `16` becomes a u8
+    /// and `16.0` an f32. [`FieldTypes::builtin`] covers the common fields.
+    pub fn field_types(mut self, field_types: FieldTypes) -> Self {
+        self.field_types = Some(field_types);
+        self
+    }
+    
+    /// Reorders known sibling elements into the sequence Celeste expects
+    ///
+    /// Currently covers the children of each `level`: tile layers first, then
+    /// entities, triggers and decals. Unknown children keep their positions.
+    /// The map itself is not modified.
+    pub fn canonical_child_order(mut self, enabled: bool) -> Self {
+        self.canonical_child_order = enabled;
+        self
+    }
+    
+    /// Writes the entities and triggers of each room in a stable order
+    ///
+    /// Uses the order of [`sort_entities`](crate::sort_entities), so editor
+    /// re-saves that shuffle entities produce the same file. The map itself
+    /// is not modified.
+    pub fn sort_entities(mut self, enabled: bool) -> Self {
+        self.sort_entities = enabled;
+        self
+    }
+    
+    /// Fails on `null` attributes instead of dropping them
+    ///
+    /// The binary format has no null type, so by default such attributes
+    /// are silently left out. In strict mode the encode fails with an error
+    /// naming the attribute and element, so placeholders are not lost
+    /// unnoticed.
+    pub fn strict_nulls(mut self, strict: bool) -> Self {
+        self.strict_nulls = strict;
+        self
+    }
+    
+    /// Only run-length encode strings when that saves at least `bytes`
+    ///
+    /// Applies to strings outside the lookup table, mainly tile data in
+    /// `innerText`. Defaults to [`DEFAULT_RLE_MIN_SAVINGS`]; `0` also uses
+    /// it when the sizes tie. Re-enables run-length encoding if it was
+    /// turned off.
+    pub fn rle_min_savings(mut self, bytes: usize) -> Self {
+        self.rle_min_savings = Some(bytes);
+        self
+    }
+    
+    /// Turns run-length encoding on or off
+    ///
+    /// With it off, such strings are written raw, which is easier to read in
+    /// a hex dump. Turning it on keeps a threshold set with
+    /// [`Encoder::rle_min_savings`], and restores the default one if it was
+    /// off.
+    pub fn run_length_encoding(mut self, enabled: bool) -> Self {
+        self.rle_min_savings = match enabled {
+            true => Some(self.rle_min_savings.unwrap_or(DEFAULT_RLE_MIN_SAVINGS)),
+            false => None,
+        };
+        self
+    }
+    
+    /// Writes `__`-prefixed attributes into the binary like any other
+    ///
+    /// By default they are editor-only and dropped, as Celeste does not know
+    /// them. With this enabled they are stored as ordinary attributes, keys
+    /// included in the lookup table, so a tool that controls both ends can
+    /// carry metadata inside the `.bin`. The game ignores unknown attributes
+    /// on most elements, but vanilla compatibility is not guaranteed.
+    pub fn preserve_underscore_attrs(mut self, enabled: bool) -> Self {
+        self.preserve_underscore_attrs = enabled;
+        self
+    }
+    
+    /// Stores floats that an f32 cannot hold exactly as 8-byte doubles
+    ///
+    /// Non-vanilla: such values get the extension type code
+    /// [`DOUBLE_TYPE_CODE`](crate::DOUBLE_TYPE_CODE), which Celeste cannot read, so only use this for
+    /// files that stay within your own tools. Cairn decodes them back to
+    /// the full f64. Numbers that fit an f32 or an integer type exactly,
+    /// and fields forced to integers by [`Encoder::field_types`], are
+    /// written as usual, so a map without such values encodes exactly as
+    /// with the default. Off by default.
+    pub fn double_precision(mut self, enabled: bool) -> Self {
+        self.double_precision = enabled;
+        self
+    }
+    
+    /// Sets the header string written at the start of the file
+    ///
+    /// Defaults to [`DEFAULT_MAGIC`]. Celeste only loads files with the
+    /// default; other values are for private format variants, read back
+    /// with `Decoder::magic`.
+    pub fn magic(mut self, magic: &str) -> Self {
+        self.magic = magic.to_string();
+        self
+    }
+    
+    /// Writes files through a temporary file that replaces the target on success
+    ///
+    /// The map is written to a hidden file in the destination directory,
+    /// synced to disk, then renamed over `path`. A failed encode or a crash
+    /// mid-write leaves any existing file untouched. Rename is atomic on most
+    /// filesystems, but the new file gets default permissions.
+    pub fn atomic(mut self, enabled: bool) -> Self {
+        self.atomic = enabled;
+        self
+    }
+    
+    /// Leaves out every element for which `skip` returns true
+    ///
+    /// A skipped element is dropped with all of its children, and its
+    /// strings stay out of the lookup table unless something else uses
+    /// them. The root element is always written. Handy for stripping
+    /// debug-only entities from a release build of an authoring map.
+    pub fn skip_elements(mut self, skip: impl Fn(&DecodedElement) -> bool + Send + Sync + 'static) -> Self {
+        self.skip_elements = Some(ElementFilter(Arc::new(skip)));
+        self
+    }
+    
+    /// Encode structure to a binary Celeste map file
+    pub fn encode<P: AsRef<Path>>(&self, map: &DecodedElement, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        
+        if !self.atomic {
+            return self.encode_to_file(map, path);
+        }
+        
+        let temp_path = temp_path_for(path);
+        let result = self.encode_to_file(map, &temp_path)
+            .and_then(|()| fs::rename(&temp_path, path));
+        
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        
+        result
+    }
+    
+    fn encode_to_file(&self, map: &DecodedElement, path: &Path) -> io::Result<()> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::with_capacity(self.buffer_size, file);
+        
+        self.encode_to_writer(map, &mut writer)?;
+        
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        if self.atomic {
+            file.sync_all()?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Encode structure to any writer
+    pub fn encode_to_writer<W: Write>(&self, map: &DecodedElement, writer: &mut W) -> io::Result<()> {
+        let prepared = self.prepare(map)?;
+        self.write_prepared(map, &prepared, writer)
+    }
+    
+    /// Encode structure into a new byte vector
+    ///
+    /// The output size is computed first with a counting dry run, so the
+    /// vector is allocated once at its final size and never grows. That
+    /// dry run walks the map a second time, which costs more time than the
+    /// reallocations it saves; encode into a `Vec` with
+    /// [`Encoder::encode_to_writer`] when speed matters more than an exact
+    /// capacity. `examples/encode_to_vec.rs` compares both.
+    pub fn encode_to_vec(&self, map: &DecodedElement) -> io::Result<Vec<u8>> {
+        let prepared = self.prepare(map)?;
+        
+        let mut counter = CountingWriter::new();
+        self.write_prepared(map, &prepared, &mut counter)?;
+        
+        let mut bytes = Vec::with_capacity(counter.count() as usize);
+        self.write_prepared(map, &prepared, &mut bytes)?;
+        
+        Ok(bytes)
+    }
+    
+    /// Size in bytes of the encoded map, without producing any output
+    pub fn encoded_size(&self, map: &DecodedElement) -> io::Result<u64> {
+        let mut counter = CountingWriter::new();
+        self.encode_to_writer(map, &mut counter)?;
+        Ok(counter.count())
+    }
+    
+    /// Encoded size in bytes of each room, largest first
+    ///
+    /// Each `level` element is encoded on its own against the map's lookup
+    /// table, through the same counting dry run as [`Encoder::encoded_size`].
+    /// The sizes add up to the room data in the file; the lookup strings a
+    /// room brings in are shared with the rest of the map and not counted.
+    /// Rooms of equal size keep their map order.
+    pub fn room_sizes(&self, map: &DecodedElement) -> io::Result<Vec<(String, u64)>> {
+        let prepared = self.prepare(map)?;
+        let ctx = self.context(&prepared);
+        
+        let mut sizes = Vec::new();
+        for room in map.rooms() {
+            if ctx.skip_elements.is_some_and(|skip| skip(room)) {
+                continue;
+            }
+            
+            let mut counter = CountingWriter::new();
+            encode_element(&mut counter, room, &ctx)?;
+            sizes.push((room.get_str("name").unwrap_or_default().to_string(), counter.count()));
+        }
+        
+        sizes.sort_by(|(_, a), (_, b)| b.cmp(a));
+        Ok(sizes)
+    }
+    
+    /// Lists the lookup strings `original` needed that `edited` does not
+    ///
+    /// Both lookup tables are built with this encoder's settings, so the
+    /// result is what re-encoding the edited map saves over encoding the
+    /// original. Strings the edit introduced are not counted against it.
+    pub fn dropped_strings(&self, original: &DecodedElement, edited: &DecodedElement) -> DroppedStrings {
+        let kept: HashSet<_> = self.build_lookup(edited).into_iter().collect();
+        
+        let mut strings: Vec<_> = self.build_lookup(original).into_iter().filter(|s| !kept.contains(s)).collect();
+        strings.sort();
+        strings.dedup();
+        
+        let mut dropped = DroppedStrings::default();
+        for s in &strings {
+            let mut prefix = CountingWriter::new();
+            write_var_length(&mut prefix, s.len() as u32).expect("counting never fails");
+            
+            dropped.total_len += s.len() as u64;
+            dropped.table_bytes += prefix.count() + s.len() as u64;
+        }
+        dropped.strings = strings;
+        
+        dropped
+    }
+    
+    /// Reads the package and builds the lookup table
+    fn prepare(&self, map: &DecodedElement) -> io::Result<Prepared> {
+        // Get package from metadata
+        let package = package_attribute(map.attributes.get("package"))
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?
+            .to_string();
+        
+        let lookup = self.build_lookup(map);
+        
+        // Seeds may contain duplicates; the first occurrence wins
+        let mut lookup_map = HashMap::with_capacity(lookup.len());
+        for (i, s) in lookup.iter().enumerate() {
+            lookup_map.entry(s.clone()).or_insert(i);
+        }
+        
+        Ok(Prepared { package, lookup, lookup_map })
+    }
+    
+    fn write_prepared<W: Write>(&self, map: &DecodedElement, prepared: &Prepared, writer: &mut W) -> io::Result<()> {
+        write_header(writer, &self.magic, &prepared.package, &prepared.lookup)?;
+        
+        // Write map data
+        encode_element(writer, map, &self.context(prepared))
+    }
+    
+    fn context<'a>(&'a self, prepared: &'a Prepared) -> EncodeContext<'a> {
+        EncodeContext {
+            lookup: &prepared.lookup_map,
+            field_types: self.field_types.as_ref(),
+            canonical_child_order: self.canonical_child_order,
+            sort_entities: self.sort_entities,
+            strict_nulls: self.strict_nulls,
+            rle_min_savings: self.rle_min_savings,
+            preserve_underscore_attrs: self.preserve_underscore_attrs,
+            skip_elements: self.skip(),
+            double_precision: self.double_precision,
+        }
+    }
+    
+    fn skip(&self) -> Option<&dyn Fn(&DecodedElement) -> bool> {
+        self.skip_elements.as_ref().map(|filter| &*filter.0 as &dyn Fn(&DecodedElement) -> bool)
+    }
+    
+    /// Collect all strings for the lookup table
+    fn build_lookup(&self, map: &DecodedElement) -> Vec<String> {
+        let mut seen = HashSet::new();
+        map.collect_keys_with(&mut seen, self.preserve_underscore_attrs, self.skip());
+        
+        let Some(seed) = &self.seed_lookup else {
+            return seen.into_iter().collect();
+        };
+        
+        for s in seed {
+            seen.remove(s);
+        }
+        
+        let mut appended: Vec<_> = seen.into_iter().collect();
+        appended.sort();
+        
+        let mut lookup = seed.clone();
+        lookup.extend(appended);
+        lookup
+    }
+}
+
+/// Hidden sibling of `path` used by atomic writes
+fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()))
+}
\ No newline at end of file
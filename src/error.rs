@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// A decode failure annotated with the byte offset where it happened
+///
+/// Decode functions still return `io::Error`; this type is carried inside it
+/// and can be recovered with [`DecodeError::from_io`].
+#[derive(Debug)]
+pub struct DecodeError {
+    offset: u64,
+    source: io::Error,
+}
+
+impl DecodeError {
+    /// Wraps `source` into an `io::Error` of the same kind, recording `offset`
+    pub(crate) fn wrap(source: io::Error, offset: u64) -> io::Error {
+        // Keep the innermost offset if the error was already annotated
+        if DecodeError::from_io(&source).is_some() {
+            return source;
+        }
+        
+        io::Error::new(source.kind(), DecodeError { offset, source })
+    }
+    
+    /// Returns the decode details carried by an `io::Error`, if any
+    pub fn from_io(error: &io::Error) -> Option<&DecodeError> {
+        error.get_ref().and_then(|e| e.downcast_ref::<DecodeError>())
+    }
+    
+    /// Byte offset from the start of the file at which decoding failed
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte offset {}", self.source, self.offset)
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
\ No newline at end of file
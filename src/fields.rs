@@ -0,0 +1,146 @@
+use serde::Serialize;
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+
+use crate::binary::number_type_code;
+use crate::element::DecodedElement;
+
+/// Numeric encoding forced for an attribute, regardless of how the JSON
+/// value happens to be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// Smallest integer type that fits (u8, i16 or i32); whole floats such as
+    /// `3.0` are converted, fractional values are rejected
+    Integer,
+    /// Always an f32 (type 4), even for whole numbers such as `16`
+    Float,
+}
+
+/// Table of attribute names whose numeric encoding is fixed
+///
+/// Only numbers are affected; strings and booleans always keep their own
+/// type so genuinely textual attributes are never clobbered.
+#[derive(Debug, Clone, Default)]
+pub struct FieldTypes {
+    types: HashMap<String, FieldType>,
+}
+
+impl FieldTypes {
+    /// Creates an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Creates a table with the fields whose type matters to Celeste
+    ///
+    /// Ids, sizes, depths and room colors are integers; styleground scroll
+    /// and speed factors, alpha and decal scales are floats.
+    pub fn builtin() -> Self {
+        let mut table = Self::new();
+        
+        for name in ["id", "width", "height", "depth", "c"] {
+            table.insert(name, FieldType::Integer);
+        }
+        
+        for name in ["scrollx", "scrolly", "speedx", "speedy", "alpha", "scaleX", "scaleY"] {
+            table.insert(name, FieldType::Float);
+        }
+        
+        table
+    }
+    
+    /// Adds or replaces the type for an attribute name
+    pub fn insert(&mut self, name: impl Into<String>, field_type: FieldType) -> &mut Self {
+        self.types.insert(name.into(), field_type);
+        self
+    }
+    
+    /// Returns the type forced for an attribute name, if any
+    pub fn get(&self, name: &str) -> Option<FieldType> {
+        self.types.get(name).copied()
+    }
+}
+
+/// A numeric attribute whose encoded type differs from the type Celeste expects
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeMismatch {
+    /// Name of the containing room, if the attribute is inside one
+    pub room: Option<String>,
+    /// Name of the element holding the attribute
+    pub element: String,
+    pub key: String,
+    pub value: Value,
+    /// Type byte the encoder will write
+    pub encoded_type: u8,
+    /// Type byte the attribute should have
+    pub expected_type: u8,
+}
+
+/// Lists the numeric attributes whose type would change on save
+///
+/// Simulates the type selection of an [`Encoder`](crate::Encoder) without a
+/// field table, where a number's type follows its JSON form, and compares it
+/// with [`FieldTypes::builtin`]: a whole `scrollx` of `1` is written as an
+/// integer although Celeste reads a float, and a `width` of `8.0` as a float
+/// although it reads an integer. Integers outside the i32 range, which can
+/// only be written as lossy floats, are listed for every attribute.
+///
+/// Analysis only; encoding with [`FieldTypes::builtin`] as the field table
+/// fixes the attributes whose expected type is known.
+pub fn type_roundtrip_report(map: &DecodedElement) -> Vec<TypeMismatch> {
+    let types = FieldTypes::builtin();
+    let mut mismatches = Vec::new();
+    collect_mismatches(map, None, &types, &mut mismatches);
+    mismatches
+}
+
+fn collect_mismatches(element: &DecodedElement, room: Option<&str>, types: &FieldTypes, out: &mut Vec<TypeMismatch>) {
+    let room = match element.name.as_str() {
+        "level" => element.get_str("name").or(room),
+        _ => room,
+    };
+    
+    let mut keys: Vec<_> = element.attributes.keys().collect();
+    keys.sort();
+    
+    for key in keys {
+        let Value::Number(n) = &element.attributes[key] else {
+            continue;
+        };
+        let Some(encoded_type) = number_type_code(n) else {
+            continue;
+        };
+        
+        let expected_type = match types.get(key) {
+            Some(FieldType::Float) => 4,
+            Some(FieldType::Integer) => integer_type_code(n),
+            None if n.is_f64() => continue,
+            None => integer_type_code(n),
+        };
+        
+        if encoded_type != expected_type {
+            out.push(TypeMismatch {
+                room: room.map(str::to_string),
+                element: element.name.clone(),
+                key: key.clone(),
+                value: Value::Number(n.clone()),
+                encoded_type,
+                expected_type,
+            });
+        }
+    }
+    
+    for child in element.children.iter().flatten() {
+        collect_mismatches(child, room, types, out);
+    }
+}
+
+/// Integer type code for a number's whole part, i32 when nothing smaller fits
+fn integer_type_code(n: &Number) -> u8 {
+    let whole = n.as_i64().or_else(|| n.as_f64().map(|f| f.trunc() as i64)).unwrap_or_default();
+    
+    match number_type_code(&Number::from(whole)) {
+        Some(code) if code != 4 => code,
+        _ => 3,
+    }
+}
\ No newline at end of file
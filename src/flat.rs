@@ -0,0 +1,387 @@
+use serde_json::{json, Value};
+use std::io::{self, Read};
+use std::ops::Range;
+
+use crate::binary::{
+    check_lookup_index, expand_run_length, read_lookup_len, read_magic, read_value, Format, PositionReader, RawValue,
+    Source, StreamSource,
+};
+use crate::decoder::{wrap_error, Decoder};
+use crate::element::DecodedElement;
+
+/// Reusable arena holding one decoded map in a handful of flat buffers
+///
+/// An alternative to [`DecodedElement`] for tools that decode many maps,
+/// inspect them and throw them away. All strings of a map share one
+/// `String`, and elements, attributes and child lists live in three `Vec`s,
+/// so a decode performs no per-element allocations. Decoding again into the
+/// same `FlatMap` clears the buffers but keeps their capacity; once they
+/// have grown to fit the largest map, further decodes allocate nothing.
+///
+/// Elements are read through [`FlatElement`] handles borrowed from the map.
+/// Use [`FlatMap::to_element`] to get the owned tree when needed.
+#[derive(Debug, Default)]
+pub struct FlatMap {
+    /// Every string of the map, back to back
+    text: String,
+    /// Byte ranges in `text`: the package, the lookup table, then inline strings
+    spans: Vec<Range<u32>>,
+    nodes: Vec<Node>,
+    attributes: Vec<(u32, Stored)>,
+    /// Node indices, each node's children stored contiguously
+    child_ids: Vec<u32>,
+    package: u32,
+    lookup_len: u32,
+    /// Reused buffer for reading raw string bytes
+    scratch: Vec<u8>,
+    /// Problems worked around in recovery mode
+    warnings: Vec<String>,
+    /// Recovery mode, for the decode in progress
+    recovering: bool,
+    /// Accept the non-vanilla type, for the decode in progress
+    double_precision: bool,
+    /// Set once recovery mode kept a partial element
+    cut_short: bool,
+}
+
+/// Span id of the first lookup table entry, right after the package
+const LOOKUP_START: u32 = 1;
+
+#[derive(Debug)]
+struct Node {
+    name: u32,
+    attributes: Range<u32>,
+    children: Range<u32>,
+}
+
+/// Attribute value as stored in the arena; strings are span ids
+#[derive(Debug, Clone, Copy)]
+enum Stored {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    Double(f64),
+    Str(u32),
+}
+
+/// Attribute value borrowed from a [`FlatMap`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlatValue<'a> {
+    Bool(bool),
+    /// Any of the u8, i16 and i32 encodings
+    Int(i32),
+    Float(f32),
+    /// The non-vanilla [`DOUBLE_TYPE_CODE`](crate::DOUBLE_TYPE_CODE)
+    Double(f64),
+    Str(&'a str),
+}
+
+/// Handle to one element of a [`FlatMap`]
+#[derive(Debug, Clone, Copy)]
+pub struct FlatElement<'a> {
+    map: &'a FlatMap,
+    index: u32,
+}
+
+impl FlatMap {
+    /// Creates an empty arena
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes a binary map into the arena, replacing its previous contents
+    ///
+    /// Errors carry a [`DecodeError`](crate::DecodeError) with the byte
+    /// offset, as with `decode_map`. After an error the arena is empty.
+    pub fn decode_from<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        self.decode_with(reader, &Decoder::new())
+    }
+
+    /// Like [`FlatMap::decode_from`], with the format and recovery settings of `decoder`
+    ///
+    /// [`Decoder::magic`], [`Decoder::skip_leading_bytes`],
+    /// [`Decoder::double_precision`] and [`Decoder::recover`] apply as they
+    /// do to `Decoder::decode`. In recovery
+    /// mode, the problems worked around are listed in [`FlatMap::warnings`].
+    /// The decoder's other settings are ignored, and so is data after the
+    /// root element.
+    pub fn decode_with<R: Read>(&mut self, reader: R, decoder: &Decoder) -> io::Result<()> {
+        self.clear();
+        self.recovering = decoder.recovers();
+        self.double_precision = decoder.format().double_precision;
+
+        let mut reader = PositionReader::new(reader);
+        let mut buffer = std::mem::take(&mut self.scratch);
+        let result = self.decode_body(&mut StreamSource::new(&mut reader, &mut buffer), &decoder.format());
+        self.scratch = buffer;
+
+        if let Err(e) = result {
+            self.clear();
+            return Err(wrap_error(e, &reader));
+        }
+
+        Ok(())
+    }
+
+    /// Number of elements, or zero when nothing is decoded
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// True when no map is decoded
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The map's package
+    pub fn package(&self) -> &str {
+        if self.is_empty() {
+            return "";
+        }
+        self.span(self.package)
+    }
+
+    /// Problems that recovery mode worked around in the last decode, one per
+    /// element cut short, as in
+    /// [`DecodeReport::warnings`](crate::DecodeReport::warnings)
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The root element, or `None` when nothing is decoded
+    pub fn root(&self) -> Option<FlatElement<'_>> {
+        (!self.is_empty()).then_some(FlatElement { map: self, index: 0 })
+    }
+
+    /// Builds the owned tree, identical to what `decode_map` returns
+    pub fn to_element(&self) -> Option<DecodedElement> {
+        let mut root = self.root()?.to_element();
+        root.attributes.insert("package".to_string(), Value::String(self.package().to_string()));
+        Some(root)
+    }
+
+    fn clear(&mut self) {
+        self.text.clear();
+        self.spans.clear();
+        self.nodes.clear();
+        self.attributes.clear();
+        self.child_ids.clear();
+        self.package = 0;
+        self.lookup_len = 0;
+        self.warnings.clear();
+        self.cut_short = false;
+    }
+
+    fn span(&self, id: u32) -> &str {
+        let range = &self.spans[id as usize];
+        &self.text[range.start as usize..range.end as usize]
+    }
+
+    fn decode_body<S: Source>(&mut self, source: &mut S, format: &Format) -> io::Result<()> {
+        read_magic(source, format)?;
+
+        // The package is span 0 and the lookup table follows
+        self.package = source.string().map(|package| self.push_span(package.as_ref()))?;
+
+        // The smallest root element takes five bytes
+        self.lookup_len = read_lookup_len(source, 5)? as u32;
+        for _ in 0..self.lookup_len {
+            source.string().map(|s| self.push_span(s.as_ref()))?;
+        }
+
+        self.decode_element(source)?;
+        Ok(())
+    }
+
+    /// Copies a string into `text`, returning its span id
+    fn push_span(&mut self, s: &str) -> u32 {
+        let start = self.text.len();
+        self.text.push_str(s);
+        self.end_span(start)
+    }
+
+    /// Records the text written since `start` as a new span
+    fn end_span(&mut self, start: usize) -> u32 {
+        self.spans.push(start as u32..self.text.len() as u32);
+        self.spans.len() as u32 - 1
+    }
+
+    fn lookup_id(&self, index: usize, what: &str) -> io::Result<u32> {
+        check_lookup_index(self.lookup_len as usize, index, what)?;
+        Ok(LOOKUP_START + index as u32)
+    }
+
+    /// Decodes one element and its subtree, returning its node index
+    ///
+    /// Recovery works as in `decode_element`: the element keeps what was
+    /// read of it, and nothing after the first error is read.
+    fn decode_element<S: Source>(&mut self, source: &mut S) -> io::Result<u32> {
+        let name = self.lookup_id(source.u16()? as usize, "element name")?;
+
+        // Pushed first so its ranges can grow as its attributes and children are read
+        let index = self.nodes.len() as u32;
+        let attributes_start = self.attributes.len() as u32;
+        let children_start = self.child_ids.len() as u32;
+        self.nodes.push(Node { name, attributes: attributes_start..attributes_start, children: children_start..children_start });
+
+        let attribute_count = match source.u8() {
+            Ok(count) => count,
+            Err(e) => return self.recover(index, e, "missing attribute count".to_string()),
+        };
+
+        for read in 0..attribute_count {
+            match self.read_attribute(source) {
+                Ok(attribute) => {
+                    self.attributes.push(attribute);
+                    self.nodes[index as usize].attributes.end += 1;
+                }
+                Err(e) => {
+                    let detail = format!("expected {} attributes, got {}", attribute_count, read);
+                    return self.recover(index, e, detail);
+                }
+            }
+        }
+
+        let child_count = match source.u16() {
+            Ok(count) => count as u32,
+            Err(e) => return self.recover(index, e, "missing child count".to_string()),
+        };
+
+        // Reserve the child slots now so they stay contiguous
+        self.child_ids.resize(self.child_ids.len() + child_count as usize, 0);
+
+        for read in 0..child_count {
+            match self.decode_element(source) {
+                Ok(child) => {
+                    self.child_ids[(children_start + read) as usize] = child;
+                    self.nodes[index as usize].children.end += 1;
+                }
+                Err(e) => {
+                    let detail = format!("expected {} children, got {}", child_count, read);
+                    return self.recover(index, e, detail);
+                }
+            }
+
+            // The child was cut short, so the stream is misaligned from here on
+            if self.cut_short {
+                let kept = read + 1;
+                if kept < child_count {
+                    self.warnings.push(format!(
+                        "Element {}: expected {} children, got {} (stopped after a damaged child)",
+                        self.span(name), child_count, kept,
+                    ));
+                }
+                break;
+            }
+        }
+
+        Ok(index)
+    }
+
+    fn read_attribute<S: Source>(&mut self, source: &mut S) -> io::Result<(u32, Stored)> {
+        let key = self.lookup_id(source.u16()? as usize, "attribute key")?;
+
+        let type_byte = source.u8()?;
+        let value = match read_value(source, type_byte, self.double_precision)? {
+            RawValue::Bool(b) => Stored::Bool(b),
+            RawValue::Int(i) => Stored::Int(i),
+            RawValue::Float(f) => Stored::Float(f),
+            RawValue::Double(f) => Stored::Double(f),
+            RawValue::Lookup(index) => Stored::Str(self.lookup_id(index, "lookup")?),
+            RawValue::Str(s) => Stored::Str(self.push_span(s.as_ref())),
+            RawValue::RunLength(runs) => {
+                let start = self.text.len();
+                expand_run_length(runs.as_ref(), &mut self.text);
+                Stored::Str(self.end_span(start))
+            }
+        };
+
+        Ok((key, value))
+    }
+
+    /// Keeps the partial element in recovery mode, or returns the error otherwise
+    fn recover(&mut self, index: u32, error: io::Error, detail: String) -> io::Result<u32> {
+        if !self.recovering {
+            return Err(error);
+        }
+
+        let name = self.span(self.nodes[index as usize].name);
+        let warning = format!("Element {}: {} ({})", name, detail, error);
+        self.warnings.push(warning);
+        self.cut_short = true;
+
+        Ok(index)
+    }
+}
+
+impl<'a> FlatElement<'a> {
+    fn node(&self) -> &'a Node {
+        &self.map.nodes[self.index as usize]
+    }
+
+    /// The element name
+    pub fn name(&self) -> &'a str {
+        self.map.span(self.node().name)
+    }
+
+    /// Iterates the attributes in file order
+    pub fn attributes(&self) -> impl Iterator<Item = (&'a str, FlatValue<'a>)> + 'a {
+        let map = self.map;
+        let range = self.node().attributes.clone();
+
+        map.attributes[range.start as usize..range.end as usize]
+            .iter()
+            .map(move |(key, value)| (map.span(*key), map.value(*value)))
+    }
+
+    /// Looks up one attribute by key
+    pub fn attribute(&self, key: &str) -> Option<FlatValue<'a>> {
+        self.attributes().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Iterates the direct children in file order
+    pub fn children(&self) -> impl Iterator<Item = FlatElement<'a>> + 'a {
+        let map = self.map;
+        let range = self.node().children.clone();
+
+        map.child_ids[range.start as usize..range.end as usize]
+            .iter()
+            .map(move |&index| FlatElement { map, index })
+    }
+
+    /// Copies this element and its subtree into an owned [`DecodedElement`]
+    pub fn to_element(&self) -> DecodedElement {
+        let mut element = DecodedElement::new(self.name());
+
+        for (key, value) in self.attributes() {
+            let value = match value {
+                FlatValue::Bool(b) => Value::Bool(b),
+                FlatValue::Int(i) => Value::from(i),
+                FlatValue::Float(f) if f.is_finite() => json!(f),
+                FlatValue::Float(_) => Value::Null,
+                FlatValue::Double(f) if f.is_finite() => json!(f),
+                FlatValue::Double(_) => Value::Null,
+                FlatValue::Str(s) => Value::String(s.to_string()),
+            };
+            element.attributes.insert(key.to_string(), value);
+        }
+
+        if !self.node().children.is_empty() {
+            element.children = Some(self.children().map(|child| child.to_element()).collect());
+        }
+
+        element
+    }
+}
+
+impl FlatMap {
+    fn value(&self, stored: Stored) -> FlatValue<'_> {
+        match stored {
+            Stored::Bool(b) => FlatValue::Bool(b),
+            Stored::Int(i) => FlatValue::Int(i),
+            Stored::Float(f) => FlatValue::Float(f),
+            Stored::Double(f) => FlatValue::Double(f),
+            Stored::Str(id) => FlatValue::Str(self.span(id)),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,190 @@
+use serde_json::Value;
+
+use crate::element::DecodedElement;
+
+/// Hash of a map's content, independent of attribute and lookup order
+///
+/// Walks the tree in canonical order (attributes sorted by key, children in
+/// document order) and feeds every name, key and value through SHA-256.
+/// What `semantically_eq` ignores is left out here too: `__` attributes,
+/// `null` values, and the difference between no `__children` and an empty
+/// list. Two semantically equal maps therefore hash the same, whatever
+/// order their attributes or lookup tables were in. As with
+/// `semantically_eq`, `1` and `1.0` are different values.
+pub fn content_hash(map: &DecodedElement) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hash_element(&mut hasher, map);
+    hasher.finish()
+}
+
+/// SHA-256 digest of `data`
+///
+/// The digest [`content_hash`] is built on, exposed so it can be checked
+/// against the published test vectors.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+fn hash_element(hasher: &mut Sha256, element: &DecodedElement) {
+    let mut attributes: Vec<_> = element.attributes.iter()
+        .filter(|(key, value)| !key.starts_with("__") && !value.is_null())
+        .collect();
+    attributes.sort_by_key(|(key, _)| key.as_str());
+
+    hasher.update(b"E");
+    hash_str(hasher, &element.name);
+    hash_len(hasher, attributes.len());
+    for (key, value) in attributes {
+        hash_str(hasher, key);
+        hash_value(hasher, value);
+    }
+
+    let children = element.children.as_deref().unwrap_or_default();
+    hash_len(hasher, children.len());
+    for child in children {
+        hash_element(hasher, child);
+    }
+}
+
+/// Every value starts with a type tag and every string with its length, so
+/// different trees never produce the same byte stream
+fn hash_value(hasher: &mut Sha256, value: &Value) {
+    match value {
+        Value::Null => hasher.update(b"z"),
+        Value::Bool(b) => hasher.update(if *b { b"t" } else { b"f" }),
+        Value::Number(n) => {
+            hasher.update(b"n");
+            hash_str(hasher, &n.to_string());
+        }
+        Value::String(s) => {
+            hasher.update(b"s");
+            hash_str(hasher, s);
+        }
+        Value::Array(items) => {
+            hasher.update(b"a");
+            hash_len(hasher, items.len());
+            for item in items {
+                hash_value(hasher, item);
+            }
+        }
+        Value::Object(fields) => {
+            let mut keys: Vec<_> = fields.keys().collect();
+            keys.sort();
+
+            hasher.update(b"o");
+            hash_len(hasher, keys.len());
+            for key in keys {
+                hash_str(hasher, key);
+                hash_value(hasher, &fields[key]);
+            }
+        }
+    }
+}
+
+fn hash_str(hasher: &mut Sha256, s: &str) {
+    hash_len(hasher, s.len());
+    hasher.update(s.as_bytes());
+}
+
+fn hash_len(hasher: &mut Sha256, len: usize) {
+    hasher.update(&(len as u64).to_le_bytes());
+}
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Minimal streaming SHA-256 (FIPS 180-4)
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha256 {
+    fn new() -> Self {
+        Self { state: INITIAL_STATE, block: [0; 64], block_len: 0, total_len: 0 }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        while !data.is_empty() {
+            let take = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+
+            if self.block_len == 64 {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bit_len = self.total_len * 8;
+
+        // A single 1 bit, zeros up to 56 bytes into a block, then the length
+        self.update(&[0x80]);
+        while self.block_len != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bit_len.to_be_bytes());
+
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in self.block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(ROUND_CONSTANTS[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,101 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::binary::{decode_element, index_element, DecodeContext, Format, LookupTable, PositionReader, StreamSource};
+use crate::decoder::{read_header, wrap_error, Decoder};
+use crate::element::DecodedElement;
+
+/// Byte offset of an element and of everything below it
+///
+/// Recorded by [`index_map`] without decoding any attribute values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementOffsets {
+    pub name: String,
+    /// Position of the element's first byte in the underlying reader
+    pub offset: u64,
+    pub children: Vec<ElementOffsets>,
+}
+
+impl ElementOffsets {
+    /// First direct child with the given name
+    pub fn find_child(&self, name: &str) -> Option<&ElementOffsets> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+/// Random access into a binary map over a seekable reader
+///
+/// Built by [`index_map`]. Keeps the reader, the lookup table and the
+/// offset of every element, so single elements can be decoded later by
+/// seeking straight to them instead of reading the whole map again.
+#[derive(Debug)]
+pub struct MapIndex<R> {
+    reader: R,
+    package: String,
+    lookup: LookupTable,
+    root: ElementOffsets,
+    double_precision: bool,
+}
+
+/// Index the elements of a binary map for random access
+///
+/// Walks the stream once like `count_elements`, recording where each
+/// element starts. Offsets are positions in `reader`, so a map embedded
+/// in a larger file works as long as the reader is positioned at its
+/// start. The reader is used as is; wrap it in a `BufReader` if it is
+/// unbuffered. Readers that cannot seek still go through `decode_map`
+/// and `decode_sections`. Expects the standard header; see
+/// [`Decoder::index_map`] for others.
+pub fn index_map<R: Read + Seek>(reader: R) -> io::Result<MapIndex<R>> {
+    Decoder::new().index_map(reader)
+}
+
+/// Index a map in the given `format`
+pub(crate) fn index_map_with<R: Read + Seek>(mut reader: R, format: &Format) -> io::Result<MapIndex<R>> {
+    let start = reader.stream_position()?;
+    let mut positioned = PositionReader::starting_at(&mut reader, start);
+    
+    let indexed = read_header(&mut positioned, format, None, 5).and_then(|(package, lookup)| {
+        let mut buffer = Vec::new();
+        let root = index_element(&mut StreamSource::new(&mut positioned, &mut buffer), &lookup, format.double_precision)?;
+        Ok((package, lookup, root))
+    });
+    
+    match indexed {
+        Ok((package, lookup, root)) => {
+            Ok(MapIndex { reader, package, lookup, root, double_precision: format.double_precision })
+        }
+        Err(e) => Err(wrap_error(e, &positioned)),
+    }
+}
+
+impl<R: Read + Seek> MapIndex<R> {
+    /// The map's package
+    pub fn package(&self) -> &str {
+        &self.package
+    }
+    
+    /// Offsets of the root element and its whole subtree
+    pub fn root(&self) -> &ElementOffsets {
+        &self.root
+    }
+    
+    /// Seeks to `offset` and decodes the element starting there
+    ///
+    /// `offset` should come from [`MapIndex::root`]. Decoding the root this
+    /// way gives the full tree, but without the `package` attribute that
+    /// `decode_map` adds.
+    pub fn decode_at(&mut self, offset: u64) -> io::Result<DecodedElement> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut reader = PositionReader::starting_at(&mut self.reader, offset);
+        
+        let mut ctx = DecodeContext::new(&self.lookup);
+        ctx.double_precision = self.double_precision;
+        
+        decode_element(&mut reader, &mut ctx).map_err(|e| wrap_error(e, &reader))
+    }
+    
+    /// Gives back the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
\ No newline at end of file
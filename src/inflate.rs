@@ -0,0 +1,301 @@
+use std::io;
+
+/// Longest Huffman code DEFLATE allows
+const MAX_BITS: usize = 15;
+
+/// Base lengths and extra bits for length symbols 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances and extra bits for distance symbols 0..=29
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+/// Order in which code length code lengths are stored in a dynamic block
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid deflate data: {}", message))
+}
+
+/// Least significant bit first reader over the compressed bytes
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, n: u32) -> io::Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.position).ok_or_else(|| invalid("unexpected end of stream"))?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+
+        let value = self.buffer & ((1u32 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drops the bits left in the current byte, for stored blocks
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn bytes(&mut self, n: usize) -> io::Result<&[u8]> {
+        let end = self.position + n;
+        let bytes = self.data.get(self.position..end).ok_or_else(|| invalid("unexpected end of stream"))?;
+        self.position = end;
+        Ok(bytes)
+    }
+}
+
+/// Canonical Huffman code, stored as symbol counts per code length
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // Reject over-subscribed codes; incomplete ones are allowed
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> io::Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for length in 1..=MAX_BITS {
+            code |= bits.take(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid("bad Huffman code"))
+    }
+}
+
+/// Decompresses a raw DEFLATE stream (RFC 1951)
+///
+/// Returns the decompressed bytes and the number of input bytes consumed,
+/// so callers can read a trailer that follows the stream. Output beyond
+/// `limit` bytes fails the call, so a small crafted stream cannot expand
+/// without bound.
+pub(crate) fn inflate(data: &[u8], limit: usize) -> io::Result<(Vec<u8>, usize)> {
+    let mut bits = Bits { data, position: 0, buffer: 0, count: 0 };
+    let mut out = Output { bytes: Vec::with_capacity(data.len().saturating_mul(4).min(limit)), limit };
+
+    loop {
+        let last = bits.take(1)? == 1;
+
+        match bits.take(2)? {
+            0 => stored_block(&mut bits, &mut out)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                compressed_block(&mut bits, &mut out, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                compressed_block(&mut bits, &mut out, &lengths, &distances)?;
+            }
+            _ => return Err(invalid("reserved block type")),
+        }
+
+        if last {
+            return Ok((out.bytes, bits.position));
+        }
+    }
+}
+
+/// Decompressed bytes so far, with the size they may not exceed
+struct Output {
+    bytes: Vec<u8>,
+    limit: usize,
+}
+
+impl Output {
+    /// Fails unless `n` more bytes fit within the limit
+    fn reserve(&self, n: usize) -> io::Result<()> {
+        if n > self.limit - self.bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Decompressed data exceeds the limit of {} bytes", self.limit),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn stored_block(bits: &mut Bits, out: &mut Output) -> io::Result<()> {
+    bits.align();
+
+    let header = bits.bytes(4)?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if length != !complement {
+        return Err(invalid("stored block length does not match its complement"));
+    }
+
+    out.reserve(length as usize)?;
+    out.bytes.extend_from_slice(bits.bytes(length as usize)?);
+    Ok(())
+}
+
+fn fixed_codes() -> io::Result<(Huffman, Huffman)> {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> io::Result<(Huffman, Huffman)> {
+    let literal_count = bits.take(5)? as usize + 257;
+    let distance_count = bits.take(5)? as usize + 1;
+    let code_length_count = bits.take(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &slot in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[slot] = bits.take(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    // Literal/length and distance code lengths form one run-length coded sequence
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_length_code.decode(bits)?;
+
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last().ok_or_else(|| invalid("repeat with no previous length"))?;
+                (previous, 3 + bits.take(2)? as usize)
+            }
+            17 => (0, 3 + bits.take(3)? as usize),
+            _ => (0, 11 + bits.take(7)? as usize),
+        };
+
+        if i + repeat > lengths.len() {
+            return Err(invalid("code lengths overflow"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid("missing end-of-block code"));
+    }
+
+    let (literal_lengths, distance_lengths) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literal_lengths)?, Huffman::new(distance_lengths)?))
+}
+
+fn compressed_block(bits: &mut Bits, out: &mut Output, lengths: &Huffman, distances: &Huffman) -> io::Result<()> {
+    loop {
+        let symbol = lengths.decode(bits)? as usize;
+
+        match symbol {
+            0..=255 => {
+                out.reserve(1)?;
+                out.bytes.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err(invalid("bad length symbol"));
+                }
+                let length = LENGTH_BASE[index] as usize + bits.take(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(bits)? as usize;
+                if index >= DISTANCE_BASE.len() {
+                    return Err(invalid("bad distance symbol"));
+                }
+                let distance = DISTANCE_BASE[index] as usize + bits.take(DISTANCE_EXTRA[index] as u32)? as usize;
+
+                if distance > out.bytes.len() {
+                    return Err(invalid("distance reaches before the start of the output"));
+                }
+                out.reserve(length)?;
+
+                // The source may overlap the bytes being written. It repeats
+                // every `distance` bytes, so copying whole periods from its
+                // start doubles the bytes available on each pass
+                let start = out.bytes.len() - distance;
+                let mut copied = 0;
+                while copied < length {
+                    let chunk = (copied + distance).min(length - copied);
+                    out.bytes.extend_from_within(start..start + chunk);
+                    copied += chunk;
+                }
+            }
+        }
+    }
+}
+
+/// CRC-32 as used by gzip and zip
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
\ No newline at end of file
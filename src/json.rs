@@ -0,0 +1,183 @@
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use crate::element::DecodedElement;
+
+/// What to do when a JSON object repeats a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Keep the last value silently, like plain `serde_json`
+    LastWins,
+    /// Keep the last value and report the duplicate as a warning
+    Warn,
+    /// Fail on the first element with a duplicate key
+    #[default]
+    Error,
+}
+
+/// Configurable loader for maps in the JSON format written by `bin_to_json`
+///
+/// Unlike deserializing a [`DecodedElement`] directly, duplicate keys are
+/// detected instead of silently keeping the last value, which usually means
+/// an attribute was pasted twice by hand.
+#[derive(Debug, Clone, Default)]
+pub struct JsonLoader {
+    duplicate_keys: DuplicateKeys,
+}
+
+/// Loaded map together with the duplicate key warnings
+#[derive(Debug, Clone)]
+pub struct JsonReport {
+    pub map: DecodedElement,
+    /// One message per repeated key, only filled in [`DuplicateKeys::Warn`] mode
+    pub warnings: Vec<String>,
+}
+
+impl JsonLoader {
+    /// Creates a loader that rejects duplicate keys
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how duplicate keys are handled. Defaults to [`DuplicateKeys::Error`].
+    pub fn duplicate_keys(mut self, mode: DuplicateKeys) -> Self {
+        self.duplicate_keys = mode;
+        self
+    }
+
+    /// Load a JSON map file
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> io::Result<DecodedElement> {
+        self.load_with_report(path).map(|report| report.map)
+    }
+
+    /// Like [`JsonLoader::load`], also returning the duplicate key warnings
+    pub fn load_with_report<P: AsRef<Path>>(&self, path: P) -> io::Result<JsonReport> {
+        let reader = BufReader::new(File::open(path)?);
+        self.load_from_reader(reader)
+    }
+
+    /// Load a JSON map from any reader
+    pub fn load_from_reader<R: Read>(&self, reader: R) -> io::Result<JsonReport> {
+        let mut warnings = Vec::new();
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+        let map = ElementSeed { mode: self.duplicate_keys, warnings: &mut warnings }.deserialize(&mut deserializer)?;
+        deserializer.end()?;
+
+        Ok(JsonReport { map, warnings })
+    }
+}
+
+/// Builds one element, checking its keys for duplicates
+struct ElementSeed<'a> {
+    mode: DuplicateKeys,
+    warnings: &'a mut Vec<String>,
+}
+
+impl<'de> DeserializeSeed<'de> for ElementSeed<'_> {
+    type Value = DecodedElement;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<DecodedElement, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ElementSeed<'_> {
+    type Value = DecodedElement;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map element object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<DecodedElement, A::Error> {
+        let mut element = DecodedElement::new(String::new());
+        let mut name = None;
+        let mut seen = HashSet::new();
+        let mut duplicates = Vec::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            if !seen.insert(key.clone()) {
+                duplicates.push(key.clone());
+            }
+
+            match key.as_str() {
+                "__name" => name = Some(map.next_value::<String>()?),
+                "__children" => {
+                    element.children = map.next_value_seed(ChildrenSeed { mode: self.mode, warnings: &mut *self.warnings })?;
+                }
+                _ => {
+                    let value: Value = map.next_value().map_err(|e| {
+                        // serde_json appends the position again to our message
+                        let message = e.to_string();
+                        let message = message.split(" at line ").next().unwrap_or_default();
+                        de::Error::custom(format!("Invalid value for attribute {:?}: {}", key, message))
+                    })?;
+                    element.attributes.insert(key, value);
+                }
+            }
+        }
+
+        element.name = name.ok_or_else(|| de::Error::missing_field("__name"))?;
+
+        for key in duplicates {
+            let message = format!("Duplicate key {:?} in element {}", key, element.name);
+
+            match self.mode {
+                DuplicateKeys::LastWins => {}
+                DuplicateKeys::Warn => self.warnings.push(message),
+                DuplicateKeys::Error => return Err(de::Error::custom(message)),
+            }
+        }
+
+        Ok(element)
+    }
+}
+
+struct ChildrenSeed<'a> {
+    mode: DuplicateKeys,
+    warnings: &'a mut Vec<String>,
+}
+
+/// `null` is no list, like the derived `Deserialize`
+impl<'de> DeserializeSeed<'de> for ChildrenSeed<'_> {
+    type Value = Option<Vec<DecodedElement>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ChildrenSeed<'_> {
+    type Value = Option<Vec<DecodedElement>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of child elements or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut children = Vec::new();
+
+        while let Some(child) = seq.next_element_seed(ElementSeed { mode: self.mode, warnings: &mut *self.warnings })? {
+            children.push(child);
+        }
+
+        Ok(Some(children))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,104 @@
+/// Well-known element names, for matching instead of comparing strings
+///
+/// Obtained from [`DecodedElement::kind`](crate::DecodedElement::kind). The
+/// element's `name` stays the source of truth; names without a variant map
+/// to `Other`. New variants may be added, so matches need a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ElementKind {
+    /// `Map`, the root element
+    Map,
+    /// `levels`, the room container
+    Levels,
+    /// `level`, a room
+    Level,
+    /// `Filler`
+    Filler,
+    /// `Style`
+    Style,
+    /// `Foregrounds`
+    Foregrounds,
+    /// `Backgrounds`
+    Backgrounds,
+    /// `parallax`, a textured styleground
+    Parallax,
+    /// `apply`, a styleground group
+    Apply,
+    /// `solids`, foreground tiles
+    Solids,
+    /// `bg`, background tiles
+    Bg,
+    /// `objtiles`
+    ObjTiles,
+    /// `fgtiles`
+    FgTiles,
+    /// `bgtiles`
+    BgTiles,
+    /// `entities`
+    Entities,
+    /// `triggers`
+    Triggers,
+    /// `fgdecals`
+    FgDecals,
+    /// `bgdecals`
+    BgDecals,
+    /// `decal`
+    Decal,
+    /// `player`, a spawn point
+    Player,
+    /// `strawberry`
+    Strawberry,
+    /// `spinner`, a crystal spinner
+    Spinner,
+    /// `refill`
+    Refill,
+    /// `spring`
+    Spring,
+    /// Any other name, such as modded entities
+    Other(String),
+}
+
+/// Names of the variants above, in the same order
+const KNOWN: &[(&str, ElementKind)] = &[
+    ("Map", ElementKind::Map),
+    ("levels", ElementKind::Levels),
+    ("level", ElementKind::Level),
+    ("Filler", ElementKind::Filler),
+    ("Style", ElementKind::Style),
+    ("Foregrounds", ElementKind::Foregrounds),
+    ("Backgrounds", ElementKind::Backgrounds),
+    ("parallax", ElementKind::Parallax),
+    ("apply", ElementKind::Apply),
+    ("solids", ElementKind::Solids),
+    ("bg", ElementKind::Bg),
+    ("objtiles", ElementKind::ObjTiles),
+    ("fgtiles", ElementKind::FgTiles),
+    ("bgtiles", ElementKind::BgTiles),
+    ("entities", ElementKind::Entities),
+    ("triggers", ElementKind::Triggers),
+    ("fgdecals", ElementKind::FgDecals),
+    ("bgdecals", ElementKind::BgDecals),
+    ("decal", ElementKind::Decal),
+    ("player", ElementKind::Player),
+    ("strawberry", ElementKind::Strawberry),
+    ("spinner", ElementKind::Spinner),
+    ("refill", ElementKind::Refill),
+    ("spring", ElementKind::Spring),
+];
+
+impl ElementKind {
+    /// Classifies an element name; matching is case-sensitive, like Celeste
+    pub fn from_name(name: &str) -> Self {
+        KNOWN.iter()
+            .find(|(known, _)| *known == name)
+            .map_or_else(|| ElementKind::Other(name.to_string()), |(_, kind)| kind.clone())
+    }
+
+    /// The element name this kind stands for
+    pub fn name(&self) -> &str {
+        match self {
+            ElementKind::Other(name) => name,
+            kind => KNOWN.iter().find(|(_, known)| known == kind).map_or("", |(name, _)| name),
+        }
+    }
+}
\ No newline at end of file
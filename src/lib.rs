@@ -1,12 +1,86 @@
 // Main library module that re-exports public API
 
 mod binary;
+mod borrowed;
+mod canonical;
+mod color;
+mod container;
+mod decoder;
+mod dump;
 mod element;
+mod encoder;
+mod error;
+mod fields;
+mod flat;
+mod hash;
+mod index;
+mod inflate;
+mod json;
+mod kind;
 mod map;
+mod order;
+mod patch;
+mod project;
+mod references;
+mod rooms;
+mod schema;
+mod sidecar;
+mod stream;
+mod style;
+mod tiles;
+mod transform;
+mod validate;
 
 // Re-export the primary types and functions
-pub use element::DecodedElement;
-pub use map::{bin_to_json, decode_map, encode_map, json_to_bin};
+pub use binary::{decode_value, number_type_code, DEFAULT_RLE_MIN_SAVINGS, DOUBLE_TYPE_CODE};
+pub use borrowed::{decode_map_borrowed, decode_map_sections, ElementRef, MapSections, ValueRef};
+pub use canonical::{canonicalize_json, to_canonical_json};
+pub use color::Color;
+pub use container::{decode_map_from_reader, decode_map_from_zip, unwrap_gzip, MAX_DECOMPRESSED_SIZE};
+pub use decoder::{
+    count_elements, decode_sections, DecodeReport, Decoder, Sections, DEFAULT_BUFFER_SIZE, DEFAULT_MAGIC,
+    TRUNCATED_ATTRIBUTE,
+};
+pub use dump::dump_structure;
+pub use element::{DecodedElement, Descendants, SemanticKey, TypedValue, COMMENT_ATTRIBUTE};
+pub use encoder::{DroppedStrings, Encoder};
+pub use error::DecodeError;
+pub use fields::{type_roundtrip_report, FieldType, FieldTypes, TypeMismatch};
+pub use flat::{FlatElement, FlatMap, FlatValue};
+pub use hash::{content_hash, sha256};
+pub use index::{index_map, ElementOffsets, MapIndex};
+pub use json::{DuplicateKeys, JsonLoader, JsonReport};
+pub use kind::ElementKind;
+pub use map::{
+    bin_to_json, decode_map, decode_map_with_stats, encode_map, encode_map_to_vec, encode_map_with_stats, encoded_size,
+    json_to_bin, repair_map, room_size_report, Map, MapBuilder, RepairReport, Stats,
+};
+pub use order::SORTABLE_CONTAINERS;
+pub use patch::apply_patch;
+pub use project::{load_project, save_project, Project, PROJECT_MAGIC};
+pub use references::{
+    collect_dialog_keys, find_references_to, find_references_to_in, DIALOG_ATTRIBUTES, REFERENCE_ATTRIBUTES,
+};
+pub use rooms::{
+    filler_rects, map_bounds, rename_room, rename_room_in, room_adjacency, room_adjacency_with_tolerance, room_bounds,
+    set_filler_rects, spawn_points, Rect, ADJACENCY_TOLERANCE, ROOM_REFERENCE_ATTRIBUTES, TILE_SIZE,
+};
+pub use schema::{attribute_keys, attribute_keys_with_internal};
+pub use sidecar::{read_sidecar, sidecar_path, write_sidecar};
+pub use stream::{bin_to_json_streaming, json_to_bin_streaming};
+pub use style::{stylegrounds, Styleground};
+pub use tiles::{
+    default_tile_chars, embed_tile_layers, extract_tile_layers, validate_tiles, ObjectTileGrid, TileError, TileGrid,
+    EMPTY_OBJECT_TILE, TILE_FILE_ATTRIBUTE,
+};
+pub use transform::{
+    coerce_numeric_strings, compact_ids, drop_default_attributes, geometry_only, sort_entities, truncate_long_strings,
+    DroppedDefaults, Truncated, NUMERIC_ATTRIBUTES,
+};
+pub use validate::{
+    check_decal_textures, check_entity_positions, check_entity_positions_with_threshold, check_float_precision,
+    validate_map, Issue, MissingTexture, OutOfRoomEntity, PrecisionWarning, Severity, OUT_OF_ROOM_THRESHOLD,
+};
 
 // Lib crate version of the package
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
\ No newline at end of file
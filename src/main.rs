@@ -1,40 +1,234 @@
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 // Import the functionality from our crate
-use cairn::{bin_to_json, json_to_bin};
+use cairn::{
+    bin_to_json, canonicalize_json, check_entity_positions, collect_dialog_keys, decode_map, dump_structure, encode_map,
+    encoded_size, json_to_bin, read_sidecar, repair_map, room_size_report, validate_map, write_sidecar, DecodeError, Decoder,
+    DuplicateKeys, Issue, JsonLoader, Severity, DIALOG_ATTRIBUTES,
+};
+
+/// Subcommands as (name, arguments, description), for the usage text and
+/// shell completions
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("bin2json", "<input.bin> [output.json]", "Convert binary map to JSON"),
+    ("json2bin", "<input.json> [output.bin]", "Convert JSON to binary map"),
+    ("info", "<input.bin>", "Print a summary of a binary map"),
+    ("validate", "<input.bin>", "Check a binary map for problems"),
+    ("hexdump", "<input.bin>", "Print an annotated byte-level breakdown"),
+    ("canonicalize", "<input.json>", "Rewrite a JSON map with sorted keys and canonical floats"),
+    ("dialog-keys", "<input.bin> [attr...]", "List the dialog keys a map references"),
+    ("repair", "<input.bin> <output.bin>", "Re-encode what can be recovered from a damaged map"),
+    ("room-sizes", "<input.bin>", "List rooms by encoded size, largest first"),
+    ("completions", "<bash|zsh|fish>", "Print a shell completion script"),
+];
+
+/// Global options as (name, value placeholder, description)
+const OPTIONS: &[(&str, &str, &str)] = &[
+    ("--format", "<text|json>", "Output format for info/validate/dialog-keys/room-sizes"),
+    ("--json", "", "Short for --format json"),
+    ("--dry-run", "", "json2bin only, report the encoded size and problems and write nothing"),
+    ("--help", "", "Print this usage text"),
+];
+
+/// Output style for the info/validate commands
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+/// Which way `bin2json` and `json2bin` convert
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    BinToJson,
+    JsonToBin,
+}
+
+impl Direction {
+    /// The subcommand name, also recorded in directory manifests
+    fn command(self) -> &'static str {
+        match self {
+            Direction::BinToJson => "bin2json",
+            Direction::JsonToBin => "json2bin",
+        }
+    }
+}
+
+/// A parsed command line, one variant per subcommand
+enum Command {
+    Convert(ConvertOptions),
+    Info(ReportOptions),
+    Validate(ReportOptions),
+    Hexdump(InputOptions),
+    Canonicalize(InputOptions),
+    DialogKeys(DialogKeysOptions),
+    Repair(RepairOptions),
+    RoomSizes(ReportOptions),
+    Completions(CompletionsOptions),
+}
+
+/// `bin2json` and `json2bin`, for a file or a directory tree
+struct ConvertOptions {
+    direction: Direction,
+    input: String,
+    /// Defaults to the input name with the other extension, or the input
+    /// directory itself
+    output: Option<String>,
+    /// json2bin only
+    dry_run: bool,
+}
+
+/// `info`, `validate` and `room-sizes`
+struct ReportOptions {
+    input: String,
+    format: Format,
+}
+
+/// Commands that only take the map to work on
+struct InputOptions {
+    input: String,
+}
+
+struct DialogKeysOptions {
+    input: String,
+    /// Replaces `DIALOG_ATTRIBUTES` when not empty
+    attributes: Vec<String>,
+    format: Format,
+}
+
+struct RepairOptions {
+    input: String,
+    output: String,
+}
+
+struct CompletionsOptions {
+    shell: String,
+}
 
 fn main() -> io::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    
-    if args.len() < 3 {
-        print_usage(&args[0]);
-        return Ok(());
+    let mut args = std::env::args();
+    let program_name = args.next().unwrap_or_else(|| "cairn".to_string());
+
+    let command = match parse_args(args.collect()) {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            print_usage(&program_name);
+            return Ok(());
+        }
+        Err(message) => {
+            eprintln!("{}", color::red(&message));
+            eprintln!("Run {} --help for usage", program_name);
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Convert(options) => convert(options),
+        Command::Info(options) => info(&options.input, options.format),
+        Command::Validate(options) => validate(&options.input, options.format),
+        Command::Hexdump(options) => hexdump(&options.input),
+        Command::Canonicalize(options) => canonicalize(&options.input),
+        Command::DialogKeys(options) => dialog_keys(&options.input, &options.attributes, options.format),
+        Command::Repair(options) => repair(&options.input, &options.output),
+        Command::RoomSizes(options) => room_sizes(&options.input, options.format),
+        Command::Completions(options) => completions(&options.shell),
+    }
+}
+
+/// Parses the arguments after the program name
+///
+/// Options may appear anywhere on the line. Returns `Ok(None)` when the
+/// usage text should be printed instead, and a message for command lines
+/// that are invalid.
+fn parse_args(mut args: Vec<String>) -> Result<Option<Command>, String> {
+    if take_flag(&mut args, "--help") || take_flag(&mut args, "-h") {
+        return Ok(None);
+    }
+
+    let json = take_flag(&mut args, "--json");
+    let dry_run = take_flag(&mut args, "--dry-run");
+    let format = take_option(&mut args, "--format")?;
+
+    if let Some(unknown) = args.iter().find(|arg| arg.starts_with('-') && arg.len() > 1) {
+        return Err(format!("Unknown option: {}", unknown));
     }
-    
-    let command = &args[1];
-    let input = &args[2];
-    let output = args.get(3).map(|s| s.to_string()).unwrap_or_else(|| {
-        generate_default_output_path(command, input)
-    });
-    
-    match command.as_str() {
-        "bin2json" => {
-            println!("Converting {} to {}", input, output);
-            bin_to_json(input, output)?;
-        }
-        "json2bin" => {
-            println!("Converting {} to {}", input, output);
-            json_to_bin(input, output)?;
+
+    let mut positional = args.into_iter();
+    let Some(name) = positional.next() else {
+        return Ok(None);
+    };
+    let Some((name, arguments, _)) = COMMANDS.iter().find(|(command, _, _)| *command == name) else {
+        return Err(format!("Unknown command: {}", name));
+    };
+    let mut positional = positional.peekable();
+    let Some(input) = positional.next() else {
+        return Err(format!("Missing argument for {}: {}", name, arguments));
+    };
+
+    let takes_format = matches!(*name, "info" | "validate" | "dialog-keys" | "room-sizes");
+    if (json || format.is_some()) && !takes_format {
+        return Err(format!("--format is not supported by {}", name));
+    }
+    if dry_run && *name != "json2bin" {
+        return Err("--dry-run is only supported by json2bin".to_string());
+    }
+
+    let format = match format.as_deref() {
+        None if json => Format::Json,
+        None | Some("text") => Format::Text,
+        Some("json") => Format::Json,
+        Some(other) => return Err(format!("Unknown format: {} (expected text or json)", other)),
+    };
+
+    let command = match *name {
+        "bin2json" | "json2bin" => {
+            let direction = if *name == "bin2json" { Direction::BinToJson } else { Direction::JsonToBin };
+            Command::Convert(ConvertOptions { direction, input, output: positional.next(), dry_run })
         }
-        _ => {
-            eprintln!("Unknown command: {}", command);
-            print_usage(&args[0]);
-            return Ok(());
+        "info" => Command::Info(ReportOptions { input, format }),
+        "validate" => Command::Validate(ReportOptions { input, format }),
+        "hexdump" => Command::Hexdump(InputOptions { input }),
+        "canonicalize" => Command::Canonicalize(InputOptions { input }),
+        "dialog-keys" => Command::DialogKeys(DialogKeysOptions { input, attributes: positional.by_ref().collect(), format }),
+        "repair" => {
+            let output = positional.next().ok_or("repair needs an output path, the input is never overwritten")?;
+            Command::Repair(RepairOptions { input, output })
         }
+        "room-sizes" => Command::RoomSizes(ReportOptions { input, format }),
+        "completions" => Command::Completions(CompletionsOptions { shell: input }),
+        _ => unreachable!("{} is listed in COMMANDS but not parsed", name),
+    };
+
+    if let Some(extra) = positional.next() {
+        return Err(format!("Unexpected argument for {}: {}", name, extra));
+    }
+
+    Ok(Some(command))
+}
+
+/// Runs `bin2json` or `json2bin`
+fn convert(options: ConvertOptions) -> io::Result<()> {
+    let ConvertOptions { direction, input, output, dry_run } = options;
+
+    if dry_run {
+        return dry_run_json2bin(&input);
     }
-    
-    println!("Conversion complete!");
+
+    if Path::new(&input).is_dir() {
+        let output = output.as_deref().unwrap_or(&input);
+        return convert_dir(direction, Path::new(&input), Path::new(output));
+    }
+
+    let output = output.unwrap_or_else(|| generate_default_output_path(direction, &input));
+
+    println!("Converting {} to {}", input, output);
+    match direction {
+        Direction::BinToJson => bin_to_json(&input, output)?,
+        Direction::JsonToBin => json_to_bin(&input, output)?,
+    }
+
+    println!("{}", color::green("Conversion complete!"));
     Ok(())
 }
 
@@ -42,13 +236,515 @@ fn print_usage(program_name: &str) {
     eprintln!("Celeste Map Encoder/Decoder v{}", cairn::VERSION);
     eprintln!("Usage: {} <command> <input> [output]", program_name);
     eprintln!("Commands:");
-    eprintln!("  bin2json <input.bin> [output.json]  - Convert binary map to JSON");
-    eprintln!("  json2bin <input.json> [output.bin]  - Convert JSON to binary map");
+    for (name, arguments, description) in COMMANDS {
+        eprintln!("  {:<35} - {}", format!("{} {}", name, arguments), description);
+        if *name == "json2bin" {
+            eprintln!("  bin2json|json2bin <dir> [out-dir]   - Convert a directory tree and write manifest.json");
+        }
+    }
+    eprintln!("Options:");
+    for (name, value, description) in OPTIONS {
+        eprintln!("  {:<35} - {}", format!("{} {}", name, value).trim_end(), description);
+    }
 }
 
-fn generate_default_output_path(command: &str, input: &str) -> String {
-    match command {
-        "bin2json" => {
+/// Space-separated choices of an option placeholder such as `<text|json>`
+fn choices(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('<')?.strip_suffix('>')?;
+    Some(inner.replace('|', " "))
+}
+
+/// Prints the completion script for `shell`, generated from [`COMMANDS`] and [`OPTIONS`]
+fn completions(shell: &str) -> io::Result<()> {
+    let names: Vec<_> = COMMANDS.iter().map(|(name, _, _)| *name).collect();
+    let options: Vec<_> = OPTIONS.iter().map(|(name, _, _)| *name).collect();
+
+    match shell {
+        "bash" => {
+            println!("_cairn() {{");
+            println!("    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"");
+            for (name, value, _) in OPTIONS {
+                if let Some(choices) = choices(value) {
+                    println!("    if [[ \"$prev\" == {} ]]; then", name);
+                    println!("        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", choices);
+                    println!("        return");
+                    println!("    fi");
+                }
+            }
+            println!("    if [[ \"$cur\" == -* ]]; then");
+            println!("        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", options.join(" "));
+            println!("    elif [[ $COMP_CWORD -eq 1 ]]; then");
+            println!("        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", names.join(" "));
+            println!("    elif [[ \"${{COMP_WORDS[1]}}\" == completions ]]; then");
+            println!("        COMPREPLY=($(compgen -W \"bash zsh fish\" -- \"$cur\"))");
+            println!("    else");
+            println!("        COMPREPLY=($(compgen -f -- \"$cur\"))");
+            println!("    fi");
+            println!("}}");
+            println!("complete -o filenames -F _cairn cairn");
+        }
+        "zsh" => {
+            println!("#compdef cairn");
+            println!();
+            println!("_cairn() {{");
+            println!("    local -a commands");
+            println!("    commands=(");
+            for (name, _, description) in COMMANDS {
+                println!("        '{}:{}'", name, description.replace('\'', "'\\''"));
+            }
+            println!("    )");
+            println!("    _arguments \\");
+            for (name, value, description) in OPTIONS {
+                let values = match choices(value) {
+                    Some(choices) => format!(":{}:({})", &name[2..], choices),
+                    None => String::new(),
+                };
+                println!("        '{}[{}]{}' \\", name, description.replace('\'', "'\\''"), values);
+            }
+            println!("        '1:command:->command' \\");
+            println!("        '*::argument:->argument'");
+            println!("    case $state in");
+            println!("        command) _describe 'command' commands ;;");
+            println!("        argument)");
+            println!("            case $words[1] in");
+            println!("                completions) _values 'shell' bash zsh fish ;;");
+            println!("                *) _files ;;");
+            println!("            esac ;;");
+            println!("    esac");
+            println!("}}");
+            println!();
+            println!("_cairn \"$@\"");
+        }
+        "fish" => {
+            println!("complete -c cairn -f");
+            for (name, _, description) in COMMANDS {
+                println!("complete -c cairn -n __fish_use_subcommand -a {} -d '{}'", name, description.replace('\'', "\\'"));
+            }
+            for (name, value, description) in OPTIONS {
+                let values = choices(value).map_or_else(String::new, |choices| format!(" -x -a '{}'", choices));
+                println!("complete -c cairn -l {}{} -d '{}'", &name[2..], values, description.replace('\'', "\\'"));
+            }
+            println!("complete -c cairn -n '__fish_seen_subcommand_from completions' -a 'bash zsh fish'");
+            println!("complete -c cairn -n 'not __fish_use_subcommand; and not __fish_seen_subcommand_from completions' -F");
+        }
+        _ => {
+            eprintln!("{}", color::red(&format!("Unknown shell: {} (expected bash, zsh or fish)", shell)));
+            std::process::exit(2);
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes every occurrence of `flag` from `args`, returning whether it was present
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != before
+}
+
+/// Removes `name <value>` or `name=<value>` from `args`, returning the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let prefix = format!("{}=", name);
+    let Some(position) = args.iter().position(|arg| arg == name || arg.starts_with(&prefix)) else {
+        return Ok(None);
+    };
+    let arg = args.remove(position);
+
+    if let Some(value) = arg.strip_prefix(&prefix) {
+        Ok(Some(value.to_string()))
+    } else if position < args.len() {
+        Ok(Some(args.remove(position)))
+    } else {
+        Err(format!("Missing value for {}", name))
+    }
+}
+
+fn room_count(map: &cairn::DecodedElement) -> usize {
+    map.find_child("levels")
+        .and_then(|levels| levels.children.as_ref())
+        .map_or(0, |rooms| rooms.len())
+}
+
+fn info(input: &str, format: Format) -> io::Result<()> {
+    let map = decode_map(input)?;
+
+    let package = map.attributes.get("package").and_then(|v| v.as_str()).unwrap_or("");
+    let rooms = room_count(&map);
+    let elements = map.element_count();
+
+    if format == Format::Json {
+        let summary = serde_json::json!({
+            "package": package,
+            "room_count": rooms,
+            "element_count": elements,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!("{}", color::bold(input));
+    println!("  {:<10} {}", "package", package);
+    println!("  {:<10} {}", "rooms", color::cyan(&rooms.to_string()));
+    println!("  {:<10} {}", "elements", color::cyan(&elements.to_string()));
+
+    Ok(())
+}
+
+fn repair(input: &str, output: &str) -> io::Result<()> {
+    let report = repair_map(input, output)?;
+
+    if !report.repaired() {
+        println!("{} no problems found in {}, wrote a re-encoded copy to {}", color::green("ok"), input, output);
+        return Ok(());
+    }
+
+    for problem in &report.problems {
+        println!("{} {}", color::yellow("dropped:"), problem);
+    }
+    println!(
+        "{} {} to {} ({} elements kept, {} -> {} bytes)",
+        color::yellow("repaired"),
+        input,
+        output,
+        report.elements,
+        report.input_bytes,
+        report.output_bytes,
+    );
+    println!("The repaired map may have lost data; open it in an editor and check it before replacing the original.");
+
+    Ok(())
+}
+
+/// Prints each room's encoded size and share of the file, largest first
+fn room_sizes(input: &str, format: Format) -> io::Result<()> {
+    let map = decode_map(input)?;
+    let sizes = room_size_report(&map)?;
+    let total = std::fs::metadata(input)?.len();
+
+    if format == Format::Json {
+        let rooms: Vec<_> = sizes.iter().map(|(name, bytes)| serde_json::json!({ "room": name, "bytes": bytes })).collect();
+        let report = serde_json::json!({ "total_bytes": total, "rooms": rooms });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("{} ({} bytes)", color::bold(input), total);
+    for (name, bytes) in &sizes {
+        let share = *bytes as f64 * 100.0 / total.max(1) as f64;
+        println!("  {} {:>6.1}%  {}", color::cyan(&format!("{:>10}", bytes)), share, name);
+    }
+
+    Ok(())
+}
+
+fn validate(input: &str, format: Format) -> io::Result<()> {
+    let (map, issues) = match Decoder::new().unused_lookup(true).strict_child_counts(true).strict_trailing(true).decode_with_report(input) {
+        Ok(report) => {
+            let mut issues: Vec<_> = report.warnings.into_iter().map(Issue::warning).collect();
+            issues.extend(validate_map(&report.map));
+            issues.extend(check_entity_positions(&report.map).into_iter().map(|entity| {
+                Issue::warning(format!(
+                    "Entity {} at ({}, {}) is {:.0} pixels outside room {:?}",
+                    entity.entity, entity.x, entity.y, entity.distance, entity.room,
+                ))
+            }));
+            (Some(report.map), issues)
+        }
+        Err(e) => {
+            let issue = match DecodeError::from_io(&e) {
+                Some(decode) => Issue::error(format!("Failed to decode: {}", e)).at_offset(decode.offset()),
+                None => Issue::error(format!("Failed to decode: {}", e)),
+            };
+            (None, vec![issue])
+        }
+    };
+
+    let errors: Vec<_> = issues.iter().filter(|i| i.severity == Severity::Error).collect();
+    let warnings: Vec<_> = issues.iter().filter(|i| i.severity == Severity::Warning).collect();
+
+    if format == Format::Json {
+        let report = serde_json::json!({
+            "ok": errors.is_empty(),
+            "package": map.as_ref().and_then(|m| m.attributes.get("package")),
+            "room_count": map.as_ref().map(room_count),
+            "errors": errors,
+            "warnings": warnings,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for issue in &issues {
+            match issue.severity {
+                Severity::Error => println!("{} {}", color::red("error:"), issue.message),
+                Severity::Warning => println!("{} {}", color::yellow("warning:"), issue.message),
+            }
+        }
+
+        let summary = format!("{} error(s), {} warning(s)", errors.len(), warnings.len());
+        if errors.is_empty() {
+            println!("{} {}", color::green("ok"), summary);
+        } else {
+            println!("{} {}", color::red("failed"), summary);
+        }
+    }
+
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn hexdump(input: &str) -> io::Result<()> {
+    let bytes = std::fs::read(input)?;
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+
+    let result = dump_structure(&bytes, &mut out);
+    io::Write::flush(&mut out)?;
+
+    if let Err(e) = result {
+        eprintln!("{} {}", color::red("error:"), e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Rewrites a JSON map in canonical form, in place
+fn canonicalize(input: &str) -> io::Result<()> {
+    match canonicalize_json(input) {
+        Ok(true) => println!("{} {}", color::green("canonicalized"), input),
+        Ok(false) => println!("{} {} is already canonical", color::green("ok"), input),
+        Err(e) => {
+            eprintln!("{} {}", color::red("error:"), e);
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the dialog keys referenced by a binary map, one per line
+///
+/// `attributes` replaces the default list of attributes holding keys.
+fn dialog_keys(input: &str, attributes: &[String], format: Format) -> io::Result<()> {
+    let map = decode_map(input)?;
+    let attributes: Vec<&str> = match attributes {
+        [] => DIALOG_ATTRIBUTES.to_vec(),
+        names => names.iter().map(String::as_str).collect(),
+    };
+
+    let keys = collect_dialog_keys(&map, &attributes);
+
+    if format == Format::Json {
+        println!("{}", serde_json::to_string_pretty(&keys)?);
+    } else {
+        for key in &keys {
+            println!("{}", key);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the full json2bin encode into a counting sink
+///
+/// Prints the size the binary would have and any validation issues, and
+/// exits with status 1 if the conversion would fail or the map has errors.
+fn dry_run_json2bin(input: &str) -> io::Result<()> {
+    let loader = JsonLoader::new().duplicate_keys(DuplicateKeys::LastWins);
+    let map = match loader.load(input) {
+        Ok(map) => map,
+        Err(e) => {
+            println!("{} Failed to parse {}: {}", color::red("error:"), input, e);
+            std::process::exit(1);
+        }
+    };
+
+    let issues = validate_map(&map);
+    for issue in &issues {
+        match issue.severity {
+            Severity::Error => println!("{} {}", color::red("error:"), issue.message),
+            Severity::Warning => println!("{} {}", color::yellow("warning:"), issue.message),
+        }
+    }
+
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+
+    match encoded_size(&map) {
+        Ok(size) if errors == 0 => {
+            println!("{} {} would encode to {} bytes", color::green("ok"), input, size);
+            Ok(())
+        }
+        Ok(size) => {
+            println!("{} {} would encode to {} bytes, but has {} error(s)", color::red("failed"), input, size, errors);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            println!("{} Failed to encode {}: {}", color::red("failed"), input, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Name of the index written to the output root by directory conversions
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Converts every map under `input` into the same layout under `output`
+///
+/// Writes a manifest with the package, room count and status of each file,
+/// and exits with status 1 if any conversion failed.
+fn convert_dir(direction: Direction, input: &Path, output: &Path) -> io::Result<()> {
+    let (from, to) = match direction {
+        Direction::BinToJson => ("bin", "json"),
+        Direction::JsonToBin => ("json", "bin"),
+    };
+
+    let mut sources = Vec::new();
+    collect_files(input, from, &mut sources)?;
+    sources.sort();
+
+    let mut entries = Vec::new();
+    let mut failed = 0;
+
+    for source in &sources {
+        let relative = source.strip_prefix(input).unwrap_or(source);
+        let target = output.join(relative).with_extension(to);
+
+        let result = convert_file(direction, source, &target);
+        let entry = match &result {
+            Ok(map) => serde_json::json!({
+                "input": relative,
+                "output": target.strip_prefix(output).unwrap_or(&target),
+                "package": map.attributes.get("package"),
+                "room_count": room_count(map),
+                "status": "ok",
+            }),
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} {}: {}", color::red("error:"), source.display(), e);
+                serde_json::json!({
+                    "input": relative,
+                    "status": "error",
+                    "error": e.to_string(),
+                })
+            }
+        };
+        entries.push(entry);
+    }
+
+    std::fs::create_dir_all(output)?;
+    let manifest = serde_json::json!({ "command": direction.command(), "files": entries });
+    std::fs::write(output.join(MANIFEST_FILE), serde_json::to_string_pretty(&manifest)?)?;
+
+    let summary = format!("{} converted, {} failed", sources.len() - failed, failed);
+    if failed > 0 {
+        println!("{} {}", color::red("failed"), summary);
+        std::process::exit(1);
+    }
+
+    println!("{} {}", color::green("Conversion complete!"), summary);
+    Ok(())
+}
+
+/// Converts one file like `bin_to_json` or `json_to_bin`, returning the
+/// map it read for the manifest
+fn convert_file(direction: Direction, source: &Path, target: &Path) -> io::Result<cairn::DecodedElement> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    if direction == Direction::BinToJson {
+        let mut map = decode_map(source)?;
+        read_sidecar(&mut map, source)?;
+        std::fs::write(target, serde_json::to_string_pretty(&map)?)?;
+        Ok(map)
+    } else {
+        let map = JsonLoader::new().duplicate_keys(DuplicateKeys::LastWins).load(source)?;
+        encode_map(&map, target)?;
+        write_sidecar(&map, target)?;
+        Ok(map)
+    }
+}
+
+/// Recursively collects files with the given extension, skipping the
+/// manifest and metadata sidecars a previous run may have left behind
+fn collect_files(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_files(&path, extension, files)?;
+            continue;
+        }
+
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name == MANIFEST_FILE || name.ends_with(".meta.json") {
+            continue;
+        }
+
+        if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// ANSI styling for terminal output
+///
+/// Styling is only applied when stdout is a terminal and `NO_COLOR` is unset,
+/// and is compiled out entirely without the `color` feature.
+mod color {
+    #[cfg(feature = "color")]
+    fn enabled() -> bool {
+        use std::io::IsTerminal;
+        use std::sync::OnceLock;
+
+        static ENABLED: OnceLock<bool> = OnceLock::new();
+        *ENABLED.get_or_init(|| {
+            std::env::var_os("NO_COLOR").map_or(true, |v| v.is_empty()) && std::io::stdout().is_terminal()
+        })
+    }
+
+    #[cfg(not(feature = "color"))]
+    fn enabled() -> bool {
+        false
+    }
+
+    fn paint(code: &str, text: &str) -> String {
+        if enabled() {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn red(text: &str) -> String {
+        paint("31", text)
+    }
+
+    pub fn green(text: &str) -> String {
+        paint("32", text)
+    }
+
+    pub fn yellow(text: &str) -> String {
+        paint("33", text)
+    }
+
+    pub fn cyan(text: &str) -> String {
+        paint("36", text)
+    }
+
+    pub fn bold(text: &str) -> String {
+        paint("1", text)
+    }
+}
+
+fn generate_default_output_path(direction: Direction, input: &str) -> String {
+    match direction {
+        Direction::BinToJson => {
             if let Some(stem) = Path::new(input).file_stem() {
                 if let Some(stem_str) = stem.to_str() {
                     format!("{}.json", stem_str)
@@ -59,7 +755,7 @@ fn generate_default_output_path(command: &str, input: &str) -> String {
                 "output.json".to_string()
             }
         }
-        "json2bin" => {
+        Direction::JsonToBin => {
             if let Some(stem) = Path::new(input).file_stem() {
                 if let Some(stem_str) = stem.to_str() {
                     format!("{}.bin", stem_str)
@@ -70,6 +766,79 @@ fn generate_default_output_path(command: &str, input: &str) -> String {
                 "output.bin".to_string()
             }
         }
-        _ => "output".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Command>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    /// The message of a command line that exits with status 2
+    fn error(args: &[&str]) -> String {
+        match parse(args) {
+            Err(message) => message,
+            Ok(_) => panic!("{:?} should not parse", args),
+        }
+    }
+
+    #[test]
+    fn invalid_command_lines_are_errors() {
+        assert_eq!(error(&["frobnicate", "a.bin"]), "Unknown command: frobnicate");
+        assert_eq!(error(&["info", "a.bin", "--verbose"]), "Unknown option: --verbose");
+        assert_eq!(error(&["info"]), "Missing argument for info: <input.bin>");
+        assert_eq!(error(&["info", "a.bin", "--format"]), "Missing value for --format");
+        assert_eq!(error(&["info", "a.bin", "--format", "xml"]), "Unknown format: xml (expected text or json)");
+        assert_eq!(error(&["hexdump", "a.bin", "--json"]), "--format is not supported by hexdump");
+        assert_eq!(error(&["bin2json", "a.bin", "--dry-run"]), "--dry-run is only supported by json2bin");
+        assert_eq!(error(&["repair", "a.bin"]), "repair needs an output path, the input is never overwritten");
+        assert_eq!(error(&["info", "a.bin", "b.bin"]), "Unexpected argument for info: b.bin");
+    }
+
+    #[test]
+    fn help_and_empty_command_lines_print_usage() {
+        assert!(matches!(parse(&[]), Ok(None)));
+        assert!(matches!(parse(&["--help"]), Ok(None)));
+        assert!(matches!(parse(&["info", "a.bin", "-h"]), Ok(None)));
+    }
+
+    #[test]
+    fn every_command_parses() {
+        for (name, _, _) in COMMANDS {
+            let args: &[&str] = if *name == "repair" { &[name, "a", "b"] } else { &[name, "a"] };
+            assert!(matches!(parse(args), Ok(Some(_))), "{}", name);
+        }
+
+        assert!(matches!(
+            parse(&["completions", "fish"]),
+            Ok(Some(Command::Completions(CompletionsOptions { shell }))) if shell == "fish"
+        ));
+        assert!(matches!(
+            parse(&["--format=json", "validate", "a.bin"]),
+            Ok(Some(Command::Validate(ReportOptions { format: Format::Json, .. })))
+        ));
+    }
+
+    #[test]
+    fn conversions_default_their_output_path() {
+        let Ok(Some(Command::Convert(options))) = parse(&["bin2json", "maps/1-Intro.bin"]) else {
+            panic!("bin2json should parse");
+        };
+        assert!(options.output.is_none());
+        assert!(!options.dry_run);
+
+        assert_eq!(generate_default_output_path(Direction::BinToJson, "maps/1-Intro.bin"), "1-Intro.json");
+        assert_eq!(generate_default_output_path(Direction::JsonToBin, "1-Intro.json"), "1-Intro.bin");
+        assert_eq!(generate_default_output_path(Direction::BinToJson, ""), "output.json");
+        assert_eq!(generate_default_output_path(Direction::JsonToBin, ".."), "output.bin");
+
+        let Ok(Some(Command::Convert(options))) = parse(&["json2bin", "a.json", "out.bin", "--dry-run"]) else {
+            panic!("json2bin should parse");
+        };
+        assert_eq!(options.output.as_deref(), Some("out.bin"));
+        assert!(options.dry_run);
     }
 }
\ No newline at end of file
@@ -1,83 +1,400 @@
-use serde_json::Value;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::io::{self, Write};
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-use crate::binary::{decode_element, encode_element, read_string, write_string};
+use crate::binary::{write_string, SliceSource, Source};
+use crate::decoder::Decoder;
 use crate::element::DecodedElement;
+use crate::encoder::Encoder;
+use crate::json::{DuplicateKeys, JsonLoader};
+use crate::sidecar::{read_sidecar, write_sidecar};
 
-/// Decode binary Celeste map to structure
-pub fn decode_map<P: AsRef<Path>>(path: P) -> io::Result<DecodedElement> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
+/// High-level view of a whole map
+///
+/// Wraps the root [`DecodedElement`] and exposes the operations most tools
+/// need by name, so rooms can be found and edited without walking
+/// `__children` by hand. The tree stays reachable through [`Map::root`] for
+/// anything not covered here.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    root: DecodedElement,
+}
+
+impl Map {
+    /// Creates an empty map with the given package and no rooms
+    ///
+    /// The package is normalized as by [`DecodedElement::set_package`].
+    pub fn new(package: &str) -> Self {
+        let mut root = DecodedElement::new("Map");
+        root.set_package(package);
+        root.add_child(DecodedElement::new("levels"));
+        Self { root }
+    }
+    
+    /// Wraps an existing root element
+    pub fn from_element(root: DecodedElement) -> Self {
+        Self { root }
+    }
+    
+    /// Decodes a binary map file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        decode_map(path).map(Self::from_element)
+    }
     
-    // Read header
-    let header = read_string(&mut reader)?;
-    if header != "CELESTE MAP" {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid Celeste map file"));
+    /// Encodes the map to a binary file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        encode_map(&self.root, path)
     }
     
-    let package = read_string(&mut reader)?;
+    /// The underlying root element
+    pub fn root(&self) -> &DecodedElement {
+        &self.root
+    }
     
-    let mut lookup_length = [0u8; 2];
-    reader.read_exact(&mut lookup_length)?;
-    let lookup_length = u16::from_le_bytes(lookup_length) as usize;
+    /// Mutable access to the underlying root element
+    pub fn root_mut(&mut self) -> &mut DecodedElement {
+        &mut self.root
+    }
     
-    let mut lookup = Vec::with_capacity(lookup_length);
-    for _ in 0..lookup_length {
-        let s = read_string(&mut reader)?;
-        lookup.push(s);
+    /// Unwraps the root element
+    pub fn into_element(self) -> DecodedElement {
+        self.root
     }
     
-    let mut map = decode_element(&mut reader, &lookup)?;
-    map.attributes.insert("package".to_string(), Value::String(package));
+    /// The map's package, e.g. `Celeste/1-ForsakenCity`
+    pub fn package(&self) -> Option<&str> {
+        self.root.get_str("package")
+    }
+    
+    /// Iterates the rooms in document order
+    pub fn rooms(&self) -> impl Iterator<Item = &DecodedElement> {
+        self.root.rooms()
+    }
+    
+    /// Finds a room by its `name` attribute
+    pub fn room(&self, name: &str) -> Option<&DecodedElement> {
+        self.rooms().find(|room| room.get_str("name") == Some(name))
+    }
+    
+    /// Mutable version of [`Map::room`]
+    pub fn room_mut(&mut self, name: &str) -> Option<&mut DecodedElement> {
+        self.root.rooms_mut().find(|room| room.get_str("name") == Some(name))
+    }
+    
+    /// Appends a room, creating the `levels` container if needed
+    pub fn add_room(&mut self, room: DecodedElement) {
+        let children = self.root.children.get_or_insert_with(Vec::new);
+        
+        match children.iter_mut().find(|child| child.name == "levels") {
+            Some(levels) => levels.add_child(room),
+            None => {
+                let mut levels = DecodedElement::new("levels");
+                levels.add_child(room);
+                self.root.add_child(levels);
+            }
+        }
+    }
+    
+    /// Removes the first room with the given name and returns it
+    pub fn remove_room(&mut self, name: &str) -> Option<DecodedElement> {
+        let levels = self.root.children.iter_mut().flatten().find(|child| child.name == "levels")?;
+        let index = levels.children.iter().flatten().position(|room| room.get_str("name") == Some(name))?;
+        
+        Some(levels.remove_child(index))
+    }
+}
+
+/// Assembles a map from separately built rooms
+///
+/// Adds the scaffolding Celeste expects around the rooms: an empty
+/// `Filler`, the `levels` container and a `Style` with empty `Foregrounds`
+/// and `Backgrounds` unless [`MapBuilder::with_style`] supplies one. Rooms
+/// are checked when the map is built.
+#[derive(Debug, Clone)]
+pub struct MapBuilder {
+    package: String,
+    rooms: Vec<DecodedElement>,
+    style: Option<DecodedElement>,
+}
+
+impl MapBuilder {
+    /// Starts a map with the given package, normalized as by
+    /// [`DecodedElement::set_package`]
+    pub fn new(package: &str) -> Self {
+        Self { package: package.to_string(), rooms: Vec::new(), style: None }
+    }
+    
+    /// Appends a room; rooms keep the order they were added in
+    pub fn add_room(mut self, room: DecodedElement) -> Self {
+        self.rooms.push(room);
+        self
+    }
+    
+    /// Uses `style` as the map's `Style` element instead of an empty one
+    pub fn with_style(mut self, style: DecodedElement) -> Self {
+        self.style = Some(style);
+        self
+    }
+    
+    /// Builds the map tree, ready for [`encode_map`]
+    ///
+    /// Fails with `InvalidInput` when a room is not a `level` element, has
+    /// no name or no numeric `x`, `y`, `width` and `height`, or shares its
+    /// name with an earlier room, and when the style is not named `Style`.
+    pub fn build(self) -> io::Result<DecodedElement> {
+        let mut names = HashSet::new();
+        for room in &self.rooms {
+            let name = check_room(room)?;
+            if !names.insert(name) {
+                return Err(invalid_input(format!("Duplicate room name {:?}", name)));
+            }
+        }
+        
+        let style = match self.style {
+            Some(style) if style.name != "Style" => {
+                return Err(invalid_input(format!("Style element is named {:?}, expected \"Style\"", style.name)));
+            }
+            Some(style) => style,
+            None => {
+                let mut style = DecodedElement::new("Style");
+                style.add_child(DecodedElement::new("Foregrounds"));
+                style.add_child(DecodedElement::new("Backgrounds"));
+                style
+            }
+        };
+        
+        let mut levels = DecodedElement::new("levels");
+        for room in self.rooms {
+            levels.add_child(room);
+        }
+        
+        let mut root = DecodedElement::new("Map");
+        root.set_package(&self.package);
+        root.add_child(DecodedElement::new("Filler"));
+        root.add_child(levels);
+        root.add_child(style);
+        Ok(root)
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Checks that `room` is a well-formed `level` and returns its name
+fn check_room(room: &DecodedElement) -> io::Result<&str> {
+    if room.name != "level" {
+        return Err(invalid_input(format!("Room element is named {:?}, expected \"level\"", room.name)));
+    }
+    
+    let name = room.get_str("name").filter(|name| !name.is_empty())
+        .ok_or_else(|| invalid_input("Room without a name attribute".to_string()))?;
+    
+    for key in ["x", "y", "width", "height"] {
+        if room.get_f64(key).is_none() {
+            return Err(invalid_input(format!("Room {:?} has no numeric {} attribute", name, key)));
+        }
+    }
     
-    Ok(map)
+    Ok(name)
+}
+
+impl From<DecodedElement> for Map {
+    fn from(root: DecodedElement) -> Self {
+        Self::from_element(root)
+    }
+}
+
+/// Decode binary Celeste map to structure
+///
+/// Errors raised while parsing carry a [`DecodeError`](crate::DecodeError)
+/// with the byte offset at which decoding stopped. Truncated files are
+/// reported as `UnexpectedEof` errors, as early as the declared sizes allow.
+pub fn decode_map<P: AsRef<Path>>(path: P) -> io::Result<DecodedElement> {
+    Decoder::new().decode(path)
 }
 
 /// Encode structure to binary Celeste map
 pub fn encode_map<P: AsRef<Path>>(map: &DecodedElement, path: P) -> io::Result<()> {
-    let file = File::create(path)?;
-    let mut writer = BufWriter::new(file);
-    
-    // Get package from metadata
-    let package = match map.attributes.get("package") {
-        Some(Value::String(s)) => s.clone(),
-        _ => {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Missing package attribute",
-            ));
-        }
+    Encoder::new().encode(map, path)
+}
+
+/// Encode structure to an in-memory binary Celeste map
+pub fn encode_map_to_vec(map: &DecodedElement) -> io::Result<Vec<u8>> {
+    Encoder::new().encode_to_vec(map)
+}
+
+/// Size in bytes that `encode_map` would write for this map
+pub fn encoded_size(map: &DecodedElement) -> io::Result<u64> {
+    Encoder::new().encoded_size(map)
+}
+
+/// Encoded size in bytes of each room, largest first
+///
+/// Shows which rooms to trim when a map grows too large; see
+/// [`Encoder::room_sizes`].
+pub fn room_size_report(map: &DecodedElement) -> io::Result<Vec<(String, u64)>> {
+    Encoder::new().room_sizes(map)
+}
+
+/// Metrics of one in-memory decode or encode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Size of the binary map read or written
+    pub bytes: u64,
+    /// Elements in the map, root included
+    pub elements: usize,
+    /// Strings in the binary map's lookup table
+    pub lookup_size: usize,
+    /// Time spent decoding or encoding, excluding gathering these stats
+    pub elapsed: Duration,
+}
+
+/// Decode an in-memory binary map, also returning [`Stats`] about it
+pub fn decode_map_with_stats(bytes: &[u8]) -> io::Result<(DecodedElement, Stats)> {
+    let start = Instant::now();
+    let map = Decoder::new().decode_from_reader(bytes)?;
+    let elapsed = start.elapsed();
+    
+    let stats = Stats {
+        bytes: bytes.len() as u64,
+        elements: map.element_count(),
+        lookup_size: header_lookup_size(bytes)?,
+        elapsed,
     };
+    Ok((map, stats))
+}
+
+/// Like [`encode_map_to_vec`], also returning [`Stats`] about the output
+pub fn encode_map_with_stats(map: &DecodedElement) -> io::Result<(Vec<u8>, Stats)> {
+    let start = Instant::now();
+    let bytes = Encoder::new().encode_to_vec(map)?;
+    let elapsed = start.elapsed();
     
-    // Collect all strings for lookup table
-    let mut seen = HashSet::new();
-    map.collect_keys(&mut seen);
+    let stats = Stats {
+        bytes: bytes.len() as u64,
+        elements: map.element_count(),
+        lookup_size: header_lookup_size(&bytes)?,
+        elapsed,
+    };
+    Ok((bytes, stats))
+}
+
+/// Reads the lookup table size from a binary map's header, skipping the
+/// strings before it
+fn header_lookup_size(bytes: &[u8]) -> io::Result<usize> {
+    let mut source = SliceSource::new(bytes);
     
-    let lookup: Vec<_> = seen.into_iter().collect();
-    let lookup_map: HashMap<_, _> = lookup.iter().enumerate().map(|(i, s)| (s.clone(), i)).collect();
+    // Magic string, then package
+    for _ in 0..2 {
+        let len = source.var_length()?;
+        source.bytes(len as usize)?;
+    }
     
-    // Write header
-    write_string(&mut writer, "CELESTE MAP")?;
-    write_string(&mut writer, &package)?;
+    Ok(source.u16()? as usize)
+}
+
+/// Write the magic string, package name and lookup table
+pub(crate) fn write_header<W: Write>(writer: &mut W, magic: &str, package: &str, lookup: &[String]) -> io::Result<()> {
+    write_string(writer, magic)?;
+    write_string(writer, package)?;
     
-    // Write lookup table
-    writer.write_all(&(lookup.len() as u16).to_le_bytes())?;
-    for s in &lookup {
-        write_string(&mut writer, s)?;
+    let lookup_len = u16::try_from(lookup.len()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Lookup table has {} strings, more than the 65535 the format allows", lookup.len()),
+        )
+    })?;
+    writer.write_all(&lookup_len.to_le_bytes())?;
+    for s in lookup {
+        write_string(writer, s)?;
     }
     
-    // Write map data
-    encode_element(&mut writer, map, &lookup_map)?;
-    
     Ok(())
 }
 
+/// What [`repair_map`] recovered from a damaged file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    /// One message per problem worked around, as in
+    /// [`DecodeReport::warnings`](crate::DecodeReport::warnings); empty when
+    /// the input was intact
+    pub problems: Vec<String>,
+    /// Elements in the repaired map, root included
+    pub elements: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl RepairReport {
+    /// True when the input had problems, so data may have been lost
+    pub fn repaired(&self) -> bool {
+        !self.problems.is_empty()
+    }
+}
+
+/// Re-encodes whatever can be recovered from a damaged binary map
+///
+/// Decodes `input` in recovery mode, keeping every element read before the
+/// damage, skipping a byte order mark or whitespace before the header and
+/// ignoring data after the root element, then writes a valid map to
+/// `output`. Elements cut short by truncation or corrupt bytes are kept
+/// with what was read of them; everything after that point is lost, and
+/// each such loss is listed in [`RepairReport::problems`]. The header and
+/// lookup table must be intact. `input` is never modified: an `output`
+/// that resolves to the same file, through `.`, `..` or symbolic links,
+/// is an error.
+pub fn repair_map<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> io::Result<RepairReport> {
+    if same_file(input.as_ref(), output.as_ref())? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Repair output {} is the input file", output.as_ref().display()),
+        ));
+    }
+    
+    let input_bytes = std::fs::metadata(&input)?.len();
+    let report = Decoder::new().recover(true).skip_leading_bytes(true).decode_with_report(input)?;
+    
+    encode_map(&report.map, &output)?;
+    
+    Ok(RepairReport {
+        problems: report.warnings,
+        elements: report.map.element_count(),
+        input_bytes,
+        output_bytes: std::fs::metadata(output)?.len(),
+    })
+}
+
+/// Whether `output` resolves to the existing file `input`
+///
+/// An output that does not exist yet is resolved through its directory.
+fn same_file(input: &Path, output: &Path) -> io::Result<bool> {
+    let input = std::fs::canonicalize(input)?;
+    
+    let output = match std::fs::canonicalize(output) {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let Some(name) = output.file_name() else {
+                return Ok(false);
+            };
+            let parent = output.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            std::fs::canonicalize(parent)?.join(name)
+        }
+        Err(e) => return Err(e),
+    };
+    
+    Ok(input == output)
+}
+
 /// Convert binary map to JSON
+///
+/// Editor-only `__` attributes saved by `json_to_bin` in the map's
+/// `.meta.json` sidecar are restored into the JSON.
 pub fn bin_to_json<P: AsRef<Path>, Q: AsRef<Path>>(bin_path: P, json_path: Q) -> io::Result<()> {
-    let map = decode_map(bin_path)?;
+    let mut map = decode_map(&bin_path)?;
+    read_sidecar(&mut map, bin_path.as_ref())?;
     let json = serde_json::to_string_pretty(&map)?;
     
     let mut file = File::create(json_path)?;
@@ -87,12 +404,17 @@ pub fn bin_to_json<P: AsRef<Path>, Q: AsRef<Path>>(bin_path: P, json_path: Q) ->
 }
 
 /// Convert JSON to binary map
+///
+/// `__`-prefixed attributes such as `__comment` cannot be stored in the
+/// binary, so they are written to a `.meta.json` sidecar next to it (see
+/// [`sidecar_path`](crate::sidecar_path)) for `bin_to_json` to restore.
 pub fn json_to_bin<P: AsRef<Path>, Q: AsRef<Path>>(json_path: P, bin_path: Q) -> io::Result<()> {
-    let file = File::open(json_path)?;
-    let reader = BufReader::new(file);
-    let map: DecodedElement = serde_json::from_reader(reader)?;
+    // Duplicate keys keep the last value, like plain serde_json, but bad
+    // values are reported with the attribute they belong to
+    let map = JsonLoader::new().duplicate_keys(DuplicateKeys::LastWins).load(json_path)?;
     
-    encode_map(&map, bin_path)?;
+    encode_map(&map, &bin_path)?;
+    write_sidecar(&map, bin_path.as_ref())?;
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+use std::cmp::Ordering;
+
+use crate::element::DecodedElement;
+
+/// Order in which Celeste writes the children of a `level`
+const LEVEL_CHILD_ORDER: &[&str] = &[
+    "solids", "bg", "objtiles", "fgtiles", "bgtiles",
+    "entities", "triggers", "fgdecals", "bgdecals",
+];
+
+/// Canonical child sequence for a parent element, if one is known
+fn child_order(parent: &str) -> Option<&'static [&'static str]> {
+    match parent {
+        "level" => Some(LEVEL_CHILD_ORDER),
+        _ => None,
+    }
+}
+
+/// Returns `children` with known element types in canonical order
+///
+/// Known children are sorted among the slots they already occupy, so
+/// unknown children keep their exact positions. Parents without a known
+/// order are returned unchanged.
+pub(crate) fn canonical_children<'a>(parent: &str, children: &'a [DecodedElement]) -> Vec<&'a DecodedElement> {
+    let mut ordered: Vec<_> = children.iter().collect();
+    
+    let Some(order) = child_order(parent) else {
+        return ordered;
+    };
+    
+    let rank = |child: &DecodedElement| order.iter().position(|name| *name == child.name);
+    
+    let slots: Vec<usize> = (0..children.len()).filter(|&i| rank(&children[i]).is_some()).collect();
+    let mut known: Vec<_> = slots.iter().map(|&i| &children[i]).collect();
+    known.sort_by_key(|child| rank(child));
+    
+    for (slot, child) in slots.into_iter().zip(known) {
+        ordered[slot] = child;
+    }
+    
+    ordered
+}
+
+/// Room containers whose children may be reordered freely
+///
+/// Celeste adds entities and triggers to the scene one by one and then draws
+/// and updates them by depth, so their order in the file has no effect
+/// beyond ties between equal depths. Decals (`fgdecals`, `bgdecals`) and
+/// stylegrounds are drawn in file order and are never sorted.
+pub const SORTABLE_CONTAINERS: &[&str] = &["entities", "triggers"];
+
+/// Sorts entities by `id`, then name, then `x` and `y`
+///
+/// Entities without an `id` go after those with one. The order is total and
+/// stable sorts keep otherwise equal entities in place, so sorting twice
+/// changes nothing.
+pub(crate) fn entity_order(a: &DecodedElement, b: &DecodedElement) -> Ordering {
+    let id = |e: &DecodedElement| e.get_i64("id");
+    let coordinate = |e: &DecodedElement, key| e.get_f64(key).unwrap_or_default();
+    
+    id(a).is_none().cmp(&id(b).is_none())
+        .then_with(|| id(a).cmp(&id(b)))
+        .then_with(|| a.name.cmp(&b.name))
+        .then_with(|| coordinate(a, "x").total_cmp(&coordinate(b, "x")))
+        .then_with(|| coordinate(a, "y").total_cmp(&coordinate(b, "y")))
+}
\ No newline at end of file
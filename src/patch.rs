@@ -0,0 +1,179 @@
+use serde_json::{Map, Value};
+use std::io;
+
+use crate::element::DecodedElement;
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Applies a JSON patch to a map, in the JSON form `bin_to_json` writes
+///
+/// An object is a JSON Merge Patch (RFC 7396): its keys are merged into the
+/// root element, recursively for nested objects, and `null` removes a key.
+/// As merge patches replace arrays wholesale, an array patch is read as a
+/// JSON Patch (RFC 6902) instead, whose `add`, `remove`, `replace`, `move`,
+/// `copy` and `test` operations address single children through pointers
+/// such as `/__children/0/__children/2/color`.
+///
+/// Operations apply in order. When one fails, or the result is no longer a
+/// valid element tree, an error naming the operation and path is returned
+/// and the map is left unchanged. A map that changed is marked dirty.
+pub fn apply_patch(map: &mut DecodedElement, patch: &Value) -> io::Result<()> {
+    let mut document = serde_json::to_value(&*map)?;
+
+    match patch {
+        Value::Object(_) => merge_patch(&mut document, patch),
+        Value::Array(operations) => {
+            for (i, operation) in operations.iter().enumerate() {
+                apply_operation(&mut document, operation).map_err(|reason| {
+                    let op = operation.get("op").and_then(Value::as_str).unwrap_or("?");
+                    let path = operation.get("path").and_then(Value::as_str).unwrap_or("?");
+                    invalid(format!("Patch operation {} ({} {:?}) failed: {}", i, op, path, reason))
+                })?;
+            }
+        }
+        _ => return Err(invalid("Patch must be a merge patch object or a JSON Patch array".to_string())),
+    }
+
+    check_element(&document, "").map_err(|reason| invalid(format!("Patched map is invalid: {}", reason)))?;
+
+    let patched: DecodedElement = serde_json::from_value(document)?;
+    if patched != *map {
+        *map = patched;
+        map.mark_dirty();
+    }
+
+    Ok(())
+}
+
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch_fields) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(fields) = target else {
+        return;
+    };
+
+    for (key, value) in patch_fields {
+        if value.is_null() {
+            fields.remove(key);
+        } else {
+            merge_patch(fields.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+fn apply_operation(document: &mut Value, operation: &Value) -> Result<(), String> {
+    let field = |name: &str| operation.get(name).ok_or_else(|| format!("missing {:?}", name));
+    let pointer = |name: &str| field(name)?.as_str().ok_or_else(|| format!("{:?} must be a string", name));
+
+    let op = pointer("op")?;
+    let path = pointer("path")?;
+
+    match op {
+        "add" => add(document, path, field("value")?.clone()),
+        "remove" => remove(document, path).map(drop),
+        "replace" => {
+            let target = document.pointer_mut(path).ok_or_else(|| format!("no value at {:?}", path))?;
+            *target = field("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let value = remove(document, pointer("from")?)?;
+            add(document, path, value)
+        }
+        "copy" => {
+            let from = pointer("from")?;
+            let value = document.pointer(from).cloned().ok_or_else(|| format!("no value at {:?}", from))?;
+            add(document, path, value)
+        }
+        "test" => match document.pointer(path) {
+            Some(value) if value == field("value")? => Ok(()),
+            Some(value) => Err(format!("value is {}", value)),
+            None => Err(format!("no value at {:?}", path)),
+        },
+        other => Err(format!("unknown operation {:?}", other)),
+    }
+}
+
+/// Splits a pointer into its parent pointer and unescaped last token
+///
+/// Returns `None` for the empty pointer, which addresses the whole map.
+fn split_pointer(path: &str) -> Result<Option<(&str, String)>, String> {
+    if path.is_empty() {
+        return Ok(None);
+    }
+    if !path.starts_with('/') {
+        return Err(format!("pointer {:?} must start with '/'", path));
+    }
+
+    let (parent, token) = path.rsplit_once('/').unwrap_or_default();
+    Ok(Some((parent, token.replace("~1", "/").replace("~0", "~"))))
+}
+
+/// Parses an array index token, which must be below `limit`
+fn array_index(token: &str, limit: usize) -> Result<usize, String> {
+    token.parse().ok().filter(|&i| i < limit).ok_or_else(|| format!("index {:?} out of range", token))
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), String> {
+    let Some((parent, token)) = split_pointer(path)? else {
+        *document = value;
+        return Ok(());
+    };
+
+    match document.pointer_mut(parent) {
+        Some(Value::Object(fields)) => {
+            fields.insert(token, value);
+        }
+        Some(Value::Array(items)) => {
+            let index = if token == "-" { items.len() } else { array_index(&token, items.len() + 1)? };
+            items.insert(index, value);
+        }
+        Some(_) => return Err(format!("{:?} is not an object or array", parent)),
+        None => return Err(format!("no value at {:?}", parent)),
+    }
+
+    Ok(())
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, String> {
+    let Some((parent, token)) = split_pointer(path)? else {
+        return Err("cannot remove the whole map".to_string());
+    };
+
+    match document.pointer_mut(parent) {
+        Some(Value::Object(fields)) => fields.remove(&token).ok_or_else(|| format!("no value at {:?}", path)),
+        Some(Value::Array(items)) => {
+            let index = array_index(&token, items.len())?;
+            Ok(items.remove(index))
+        }
+        _ => Err(format!("no value at {:?}", path)),
+    }
+}
+
+/// Checks that a patched value still describes an element tree
+fn check_element(value: &Value, path: &str) -> Result<(), String> {
+    let Value::Object(fields) = value else {
+        return Err(format!("{:?} is not an element object", path));
+    };
+
+    if !fields.get("__name").is_some_and(Value::is_string) {
+        return Err(format!("\"{}/__name\" must be a string", path));
+    }
+
+    match fields.get("__children") {
+        None => Ok(()),
+        Some(Value::Array(children)) => children
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, child)| check_element(child, &format!("{}/__children/{}", path, i))),
+        Some(_) => Err(format!("\"{}/__children\" must be an array", path)),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::decoder::Decoder;
+use crate::element::DecodedElement;
+use crate::encoder::Encoder;
+
+/// First bytes of every project file
+///
+/// A map starts with the length byte of its header string, 11 for
+/// `CELESTE MAP`. The high first byte rules that out, and the CR LF, EOF
+/// and LF bytes catch transfers that mangle line endings, as in PNG.
+pub const PROJECT_MAGIC: &[u8; 8] = b"\x89CRN\r\n\x1a\n";
+
+/// Current project format version
+const PROJECT_VERSION: u8 = 1;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// A map bundled with editor state, see [`save_project`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    pub map: DecodedElement,
+    /// Free-form editor state, such as the camera position or the selected room
+    pub metadata: Value,
+}
+
+/// Saves a map and editor metadata together in one project file
+///
+/// The file holds [`PROJECT_MAGIC`], a version byte, then two sections,
+/// each a little-endian u32 length followed by its bytes: the map encoded
+/// exactly as `encode_map` writes it, and the metadata as JSON. A project
+/// file is never a valid map, so `decode_map` rejects it instead of
+/// misreading it; use [`load_project`].
+pub fn save_project<P: AsRef<Path>>(path: P, map: &DecodedElement, metadata: &Value) -> io::Result<()> {
+    let map_bytes = Encoder::new().encode_to_vec(map)?;
+    let metadata_bytes = serde_json::to_vec(metadata)?;
+    
+    let mut bytes = Vec::with_capacity(PROJECT_MAGIC.len() + 9 + map_bytes.len() + metadata_bytes.len());
+    bytes.extend_from_slice(PROJECT_MAGIC);
+    bytes.push(PROJECT_VERSION);
+    
+    for section in [&map_bytes, &metadata_bytes] {
+        let len = u32::try_from(section.len()).map_err(|_| invalid("Project section larger than 4 GiB"))?;
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(section);
+    }
+    
+    fs::write(path, bytes)
+}
+
+/// Loads a project file written by [`save_project`]
+///
+/// Fails on plain map files and on projects from a newer format version.
+pub fn load_project<P: AsRef<Path>>(path: P) -> io::Result<Project> {
+    let bytes = fs::read(path)?;
+    
+    let Some(rest) = bytes.strip_prefix(PROJECT_MAGIC) else {
+        return Err(invalid("Not a project file"));
+    };
+    let Some((&version, rest)) = rest.split_first() else {
+        return Err(invalid("Project file truncated"));
+    };
+    if version != PROJECT_VERSION {
+        return Err(invalid(format!("Unsupported project version {}", version)));
+    }
+    
+    let (mut map_bytes, rest) = split_section(rest, "map")?;
+    let (metadata_bytes, rest) = split_section(rest, "metadata")?;
+    if !rest.is_empty() {
+        return Err(invalid(format!("{} trailing bytes after the project metadata", rest.len())));
+    }
+    
+    let map = Decoder::new().decode_from_reader(&mut map_bytes)?;
+    if !map_bytes.is_empty() {
+        return Err(invalid(format!("{} unused bytes at the end of the project map", map_bytes.len())));
+    }
+    let metadata = serde_json::from_slice(metadata_bytes)?;
+    
+    Ok(Project { map, metadata })
+}
+
+/// Splits a length-prefixed section off the front of `bytes`
+fn split_section<'a>(bytes: &'a [u8], name: &str) -> io::Result<(&'a [u8], &'a [u8])> {
+    let truncated = || invalid(format!("Project {} section truncated", name));
+    
+    let len = bytes.get(..4).ok_or_else(truncated)?;
+    let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+    
+    let section = bytes.get(4..4 + len).ok_or_else(truncated)?;
+    Ok((section, &bytes[4 + len..]))
+}
\ No newline at end of file
@@ -0,0 +1,47 @@
+use std::collections::BTreeSet;
+
+use crate::element::DecodedElement;
+
+/// Attributes that commonly hold the id of another entity
+///
+/// Vanilla Celeste has no generic linking scheme, so this covers the names
+/// used by popular helper mods. Use [`find_references_to_in`] for others.
+pub const REFERENCE_ATTRIBUTES: &[&str] = &["targetId", "targetID", "linkedId", "linkId", "parentId", "entityId"];
+
+/// Attributes that commonly hold a dialog key
+///
+/// Covers vanilla signs and memorials and the dialog triggers and NPCs of
+/// Everest and popular helper mods. Pass your own list to
+/// [`collect_dialog_keys`] for others.
+pub const DIALOG_ATTRIBUTES: &[&str] = &["dialog", "dialogId", "dialogID", "dialogKey", "text"];
+
+/// Finds elements whose reference attributes point at entity `id`
+///
+/// Checks the attributes in [`REFERENCE_ATTRIBUTES`]; see
+/// [`find_references_to_in`].
+pub fn find_references_to(map: &DecodedElement, id: i64) -> Vec<&DecodedElement> {
+    find_references_to_in(map, id, REFERENCE_ATTRIBUTES)
+}
+
+/// Finds elements with any of `attributes` equal to `id`
+///
+/// Searches every descendant of `map` in document order. Values are read
+/// with [`DecodedElement::get_i64`], so `12` and `12.0` both match; the `id`
+/// attribute itself is only checked if it is listed.
+pub fn find_references_to_in<'a>(map: &'a DecodedElement, id: i64, attributes: &[&str]) -> Vec<&'a DecodedElement> {
+    map.descendants()
+        .filter(|element| attributes.iter().any(|key| element.get_i64(key) == Some(id)))
+        .collect()
+}
+
+/// Collects the dialog keys a map references, sorted and without duplicates
+///
+/// Gathers the string values of `attributes` on every descendant of `map`;
+/// [`DIALOG_ATTRIBUTES`] covers the common ones. Empty strings are skipped.
+pub fn collect_dialog_keys(map: &DecodedElement, attributes: &[&str]) -> BTreeSet<String> {
+    map.descendants()
+        .flat_map(|element| attributes.iter().filter_map(|key| element.get_str(key)))
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
\ No newline at end of file
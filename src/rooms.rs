@@ -0,0 +1,226 @@
+use std::io;
+
+use crate::element::DecodedElement;
+use crate::kind::ElementKind;
+
+/// Axis-aligned rectangle in map pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self { x, y, width, height }
+    }
+    
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+    
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+    
+    /// Returns true if the point lies inside the rectangle or on its edge
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.right() && y >= self.y && y <= self.bottom()
+    }
+    
+    /// Smallest rectangle containing both
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(x, y, self.right().max(other.right()) - x, self.bottom().max(other.bottom()) - y)
+    }
+}
+
+/// Attributes that commonly hold the name of another room
+///
+/// Vanilla triggers rarely name rooms, so this covers the names used by
+/// popular helper mods for teleports and room transitions. Use
+/// [`rename_room_in`] for others.
+pub const ROOM_REFERENCE_ATTRIBUTES: &[&str] = &["room", "targetRoom", "toRoom", "toLevel", "nextLevel", "levelName"];
+
+/// Size of a tile in pixels; `Filler` rectangles are stored in tiles
+pub const TILE_SIZE: f64 = 8.0;
+
+/// Gap allowed between two room edges for [`room_adjacency`], in pixels
+pub const ADJACENCY_TOLERANCE: f64 = 1.0;
+
+/// Bounds of a `level` element from its `x`, `y`, `width` and `height`
+///
+/// Returns `None` when any of the four is missing or not a number.
+pub fn room_bounds(room: &DecodedElement) -> Option<Rect> {
+    Some(Rect::new(
+        room.get_f64("x")?,
+        room.get_f64("y")?,
+        room.get_f64("width")?,
+        room.get_f64("height")?,
+    ))
+}
+
+/// Bounding box of every room in the map, or `None` if no room has bounds
+pub fn map_bounds(map: &DecodedElement) -> Option<Rect> {
+    map.rooms()
+        .filter_map(room_bounds)
+        .reduce(|acc, bounds| acc.union(&bounds))
+}
+
+/// Pairs of rooms that share an edge, by room name
+///
+/// Uses [`ADJACENCY_TOLERANCE`]; see [`room_adjacency_with_tolerance`].
+pub fn room_adjacency(map: &DecodedElement) -> Vec<(String, String)> {
+    room_adjacency_with_tolerance(map, ADJACENCY_TOLERANCE)
+}
+
+/// Pairs of rooms that share an edge, by room name
+///
+/// Two rooms are adjacent when a vertical or horizontal edge of one lies
+/// within `tolerance` pixels of the opposite edge of the other and the
+/// edges overlap by more than `tolerance` along their length, so rooms that
+/// only touch at a corner are not adjacent. Each pair is listed once, in
+/// map order. Rooms without a name or bounds are skipped.
+pub fn room_adjacency_with_tolerance(map: &DecodedElement, tolerance: f64) -> Vec<(String, String)> {
+    let rooms: Vec<_> = map.rooms()
+        .filter_map(|room| Some((room.get_str("name")?, room_bounds(room)?)))
+        .collect();
+    
+    let overlap = |a_start: f64, a_end: f64, b_start: f64, b_end: f64| a_end.min(b_end) - a_start.max(b_start);
+    let touches = |a: f64, b: f64| (a - b).abs() <= tolerance;
+    
+    let mut pairs = Vec::new();
+    
+    for (i, (name_a, a)) in rooms.iter().enumerate() {
+        for (name_b, b) in &rooms[i + 1..] {
+            let side_by_side = (touches(a.right(), b.x) || touches(b.right(), a.x))
+                && overlap(a.y, a.bottom(), b.y, b.bottom()) > tolerance;
+            let stacked = (touches(a.bottom(), b.y) || touches(b.bottom(), a.y))
+                && overlap(a.x, a.right(), b.x, b.right()) > tolerance;
+            
+            if side_by_side || stacked {
+                pairs.push((name_a.to_string(), name_b.to_string()));
+            }
+        }
+    }
+    
+    pairs
+}
+
+/// The map's filler regions, in map pixels
+///
+/// Reads the `rect` children of the root's `Filler` element, whose `x`,
+/// `y`, `w` and `h` are in tiles, and scales them by [`TILE_SIZE`]. Rects
+/// missing any of the four are skipped. A map without `Filler` has no
+/// filler regions, so the result is empty.
+pub fn filler_rects(map: &DecodedElement) -> Vec<Rect> {
+    let Some(filler) = map.find_child("Filler") else {
+        return Vec::new();
+    };
+    
+    filler.children.iter().flatten()
+        .filter(|rect| rect.name == "rect")
+        .filter_map(|rect| {
+            Some(Rect::new(
+                rect.get_f64("x")? * TILE_SIZE,
+                rect.get_f64("y")? * TILE_SIZE,
+                rect.get_f64("w")? * TILE_SIZE,
+                rect.get_f64("h")? * TILE_SIZE,
+            ))
+        })
+        .collect()
+}
+
+/// Replaces the map's filler regions with `rects`, given in map pixels
+///
+/// The inverse of [`filler_rects`]: each rect is written as a `rect` child
+/// of `Filler` in whole tiles, rounding to the nearest tile. A missing
+/// `Filler` is added as the root's first child, where Celeste writes it,
+/// unless `rects` is empty.
+pub fn set_filler_rects(map: &mut DecodedElement, rects: &[Rect]) {
+    let tiles = |pixels: f64| (pixels / TILE_SIZE).round() as i64;
+    let children: Vec<_> = rects.iter()
+        .map(|r| {
+            let mut rect = DecodedElement::new("rect");
+            rect.set_attribute("x", tiles(r.x));
+            rect.set_attribute("y", tiles(r.y));
+            rect.set_attribute("w", tiles(r.width));
+            rect.set_attribute("h", tiles(r.height));
+            rect
+        })
+        .collect();
+    
+    if children.is_empty() && map.find_child("Filler").is_none() {
+        return;
+    }
+    
+    let sections = map.children.get_or_insert_with(Vec::new);
+    let filler = match sections.iter().position(|child| child.name == "Filler") {
+        Some(index) => &mut sections[index],
+        None => {
+            sections.insert(0, DecodedElement::new("Filler"));
+            &mut sections[0]
+        }
+    };
+    
+    filler.children = Some(children);
+    filler.mark_dirty();
+}
+
+/// Spawn points of the map as (room name, x, y)
+///
+/// Every `player` entity is a spawn point, and Celeste picks the one
+/// closest to where the player enters a room, so all of them are returned,
+/// in map order. Coordinates are room-relative, as stored; add the room's
+/// [`room_bounds`] origin for map pixels. Players without `x` and `y` are
+/// skipped.
+pub fn spawn_points(map: &DecodedElement) -> Vec<(String, f64, f64)> {
+    map.rooms()
+        .flat_map(|room| {
+            let room_name = room.get_str("name").unwrap_or_default();
+            room.entities()
+                .filter(|entity| entity.kind() == ElementKind::Player)
+                .filter_map(move |player| Some((room_name.to_string(), player.get_f64("x")?, player.get_f64("y")?)))
+        })
+        .collect()
+}
+
+/// Renames room `old` to `new` and updates references to it
+///
+/// Checks the attributes in [`ROOM_REFERENCE_ATTRIBUTES`]; see
+/// [`rename_room_in`].
+pub fn rename_room(map: &mut DecodedElement, old: &str, new: &str) -> io::Result<usize> {
+    rename_room_in(map, old, new, ROOM_REFERENCE_ATTRIBUTES)
+}
+
+/// Renames room `old` to `new` and updates any of `attributes` equal to `old`
+///
+/// References are updated on every element of the map, not only inside
+/// rooms. Returns the number of attribute values updated, not counting the
+/// room's own `name`. Fails without changing the map when no room is named
+/// `old`, or when another room is already named `new`.
+pub fn rename_room_in(map: &mut DecodedElement, old: &str, new: &str, attributes: &[&str]) -> io::Result<usize> {
+    if old != new && map.rooms().any(|room| room.get_str("name") == Some(new)) {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("A room named {:?} already exists", new)));
+    }
+    
+    let room = map.rooms_mut()
+        .find(|room| room.get_str("name") == Some(old))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No room named {:?}", old)))?;
+    room.set_attribute("name", new);
+    
+    let mut updated = 0;
+    map.visit_mut(&mut |element| {
+        for key in attributes {
+            if element.get_str(key) == Some(old) {
+                element.set_attribute(*key, new);
+                updated += 1;
+            }
+        }
+    });
+    
+    Ok(updated)
+}
\ No newline at end of file
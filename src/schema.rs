@@ -0,0 +1,34 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter;
+
+use crate::element::DecodedElement;
+
+/// Attribute keys seen on each element name, over the whole map
+///
+/// Maps every element name, the root's included, to the keys found on
+/// elements of that name. An element type with no attributes still gets
+/// an empty set. `__`-prefixed editor keys are left out; see
+/// [`attribute_keys_with_internal`] to keep them.
+pub fn attribute_keys(map: &DecodedElement) -> BTreeMap<String, BTreeSet<String>> {
+    collect_attribute_keys(map, false)
+}
+
+/// Like [`attribute_keys`], also listing `__`-prefixed keys
+pub fn attribute_keys_with_internal(map: &DecodedElement) -> BTreeMap<String, BTreeSet<String>> {
+    collect_attribute_keys(map, true)
+}
+
+fn collect_attribute_keys(map: &DecodedElement, include_internal: bool) -> BTreeMap<String, BTreeSet<String>> {
+    let mut keys: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    
+    for element in iter::once(map).chain(map.descendants()) {
+        let seen = keys.entry(element.name.clone()).or_default();
+        seen.extend(
+            element.attributes.keys()
+                .filter(|key| include_internal || !key.starts_with("__"))
+                .cloned(),
+        );
+    }
+    
+    keys
+}
\ No newline at end of file
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::element::DecodedElement;
+
+/// Editor-only attributes that the binary format cannot hold
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Sidecar {
+    attributes: Vec<SidecarAttribute>,
+}
+
+/// One `__`-prefixed attribute and the element it belongs to
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SidecarAttribute {
+    /// Child indices from the root down to the element
+    pub path: Vec<usize>,
+    /// Element name, checked when re-attaching
+    pub element: String,
+    pub key: String,
+    pub value: Value,
+}
+
+/// Path of the metadata sidecar kept next to a binary map
+///
+/// `maps/1-City.bin` has its sidecar at `maps/1-City.bin.meta.json`.
+pub fn sidecar_path<P: AsRef<Path>>(bin_path: P) -> PathBuf {
+    let mut path = OsString::from(bin_path.as_ref().as_os_str());
+    path.push(".meta.json");
+    PathBuf::from(path)
+}
+
+fn collect(element: &DecodedElement, path: &mut Vec<usize>, out: &mut Vec<SidecarAttribute>) {
+    let mut keys: Vec<_> = element.attributes.keys().filter(|k| k.starts_with("__")).collect();
+    keys.sort();
+    
+    for key in keys {
+        out.push(SidecarAttribute {
+            path: path.clone(),
+            element: element.name.clone(),
+            key: key.clone(),
+            value: element.attributes[key].clone(),
+        });
+    }
+    
+    for (i, child) in element.children.iter().flatten().enumerate() {
+        path.push(i);
+        collect(child, path, out);
+        path.pop();
+    }
+}
+
+/// Writes the `__` attributes of `map` to the sidecar of `bin_path`
+///
+/// This is the sidecar half of [`json_to_bin`](crate::json_to_bin), for
+/// callers that encode the map themselves. A stale sidecar is removed when
+/// the map has no such attributes.
+pub fn write_sidecar(map: &DecodedElement, bin_path: &Path) -> io::Result<()> {
+    let mut attributes = Vec::new();
+    collect(map, &mut Vec::new(), &mut attributes);
+    write_sidecar_attributes(bin_path, attributes)
+}
+
+/// Writes `attributes` to the sidecar of `bin_path`, removing it when empty
+pub(crate) fn write_sidecar_attributes(bin_path: &Path, attributes: Vec<SidecarAttribute>) -> io::Result<()> {
+    let path = sidecar_path(bin_path);
+    let sidecar = Sidecar { attributes };
+    
+    if sidecar.attributes.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &sidecar)?;
+    
+    Ok(())
+}
+
+/// Re-attaches attributes from the sidecar of `bin_path`, if there is one
+///
+/// This is the sidecar half of [`bin_to_json`](crate::bin_to_json), for
+/// callers that decode the map themselves. Entries whose path no longer
+/// leads to an element of the recorded name are skipped, so a sidecar that
+/// went stale after edits cannot misplace data.
+pub fn read_sidecar(map: &mut DecodedElement, bin_path: &Path) -> io::Result<()> {
+    for attribute in read_sidecar_attributes(bin_path)? {
+        let mut element = &mut *map;
+        let mut found = true;
+        
+        for &index in &attribute.path {
+            match element.children.as_mut().and_then(|children| children.get_mut(index)) {
+                Some(child) => element = child,
+                None => {
+                    found = false;
+                    break;
+                }
+            }
+        }
+        
+        if found && attribute.applies_to(&element.name) {
+            element.attributes.insert(attribute.key, attribute.value);
+        }
+    }
+    
+    Ok(())
+}
+
+/// The entries of the sidecar of `bin_path`, empty when there is none
+pub(crate) fn read_sidecar_attributes(bin_path: &Path) -> io::Result<Vec<SidecarAttribute>> {
+    let file = match File::open(sidecar_path(bin_path)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    
+    let sidecar: Sidecar = serde_json::from_reader(BufReader::new(file))?;
+    Ok(sidecar.attributes)
+}
+
+impl SidecarAttribute {
+    /// Whether the entry belongs on the element named `name` at its path
+    pub(crate) fn applies_to(&self, name: &str) -> bool {
+        name == self.element && self.key.starts_with("__")
+    }
+}
\ No newline at end of file
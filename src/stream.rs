@@ -0,0 +1,453 @@
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::binary::{decode_element_head, encode_element_header, DecodeContext, EncodeContext, Format, PositionReader};
+use crate::decoder::{read_header, wrap_error, DEFAULT_MAGIC};
+use crate::element::{DecodedElement, RUN_LENGTH_ATTRIBUTE};
+use crate::map::write_header;
+use crate::sidecar::{read_sidecar_attributes, write_sidecar_attributes, SidecarAttribute};
+use crate::validate::package_attribute;
+
+/// Convert JSON to binary map without building the element tree
+///
+/// The JSON file is read twice: the first pass collects the strings for the
+/// lookup table and the package name, the second encodes each element as soon
+/// as it has been read. Only one element's attributes are held in memory at a
+/// time, so peak memory no longer grows with the size of the map
+/// (`examples/streaming_memory.rs` measures it against `json_to_bin`).
+///
+/// Child counts are written as placeholders and patched once each
+/// `__children` array ends, which is why the output must be seekable. Within
+/// an element, `__name` and all attributes must come before `__children`, as
+/// they do in files written by `bin_to_json`. Like `json_to_bin`, it always
+/// writes the standard [`DEFAULT_MAGIC`] header and saves `__` attributes
+/// to the `.meta.json` sidecar, collecting them during the first pass.
+pub fn json_to_bin_streaming<P: AsRef<Path>, Q: AsRef<Path>>(json_path: P, bin_path: Q) -> io::Result<()> {
+    let json_path = json_path.as_ref();
+    let bin_path = bin_path.as_ref();
+
+    // First pass: lookup table, package and sidecar attributes
+    let mut seen = HashSet::new();
+    let mut package = None;
+    let mut sidecar = Vec::new();
+    {
+        let reader = BufReader::new(File::open(json_path)?);
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        KeyCollector { seen: &mut seen, package: Some(&mut package), path: Vec::new(), sidecar: &mut sidecar }
+            .deserialize(&mut deserializer)?;
+        deserializer.end()?;
+    }
+
+    let package = package_attribute(package.as_ref())
+        .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?
+        .to_string();
+
+    let lookup: Vec<_> = seen.into_iter().collect();
+    let lookup_map: HashMap<_, _> = lookup.iter().enumerate().map(|(i, s)| (s.clone(), i)).collect();
+
+    let mut writer = BufWriter::new(File::create(bin_path)?);
+    write_header(&mut writer, DEFAULT_MAGIC, &package, &lookup)?;
+
+    // Second pass: element data
+    let reader = BufReader::new(File::open(json_path)?);
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    ElementEncoder { writer: &mut writer, lookup: &lookup_map }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    writer.flush()?;
+
+    write_sidecar_attributes(bin_path, sidecar)
+}
+
+/// Collects lookup strings the same way as `DecodedElement::collect_keys`,
+/// and `__` attributes the same way as `write_sidecar`
+struct KeyCollector<'a> {
+    seen: &'a mut HashSet<String>,
+    /// Only set for the root element
+    package: Option<&'a mut Option<Value>>,
+    /// Child indices from the root down to this element
+    path: Vec<usize>,
+    sidecar: &'a mut Vec<SidecarAttribute>,
+}
+
+impl<'de> DeserializeSeed<'de> for KeyCollector<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyCollector<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map element object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut name = None;
+        let mut editor = Vec::new();
+        // The element's own entries go before its children's
+        let start = self.sidecar.len();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "__name" => {
+                    let value: String = map.next_value()?;
+                    self.seen.insert(value.clone());
+                    name = Some(value);
+                }
+                "__children" => {
+                    map.next_value_seed(ChildKeys { seen: &mut *self.seen, path: &self.path, sidecar: &mut *self.sidecar })?;
+                }
+                _ if key.starts_with("__") => {
+                    let value: Value = map.next_value()?;
+                    editor.push((key, value));
+                }
+                _ => {
+                    let value: Value = map.next_value()?;
+
+                    if key == "package" {
+                        if let Some(package) = self.package.as_deref_mut() {
+                            *package = Some(value.clone());
+                        }
+                    }
+
+                    if let Value::String(s) = value {
+                        if key != RUN_LENGTH_ATTRIBUTE {
+                            self.seen.insert(s);
+                        }
+                    }
+
+                    self.seen.insert(key);
+                }
+            }
+        }
+
+        editor.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let element = name.unwrap_or_default();
+        let own = editor.into_iter().map(|(key, value)| SidecarAttribute {
+            path: self.path.clone(),
+            element: element.clone(),
+            key,
+            value,
+        });
+        self.sidecar.splice(start..start, own);
+
+        Ok(())
+    }
+}
+
+struct ChildKeys<'a> {
+    seen: &'a mut HashSet<String>,
+    path: &'a [usize],
+    sidecar: &'a mut Vec<SidecarAttribute>,
+}
+
+impl<'de> DeserializeSeed<'de> for ChildKeys<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ChildKeys<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of child elements or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        for i in 0.. {
+            let mut path = self.path.to_vec();
+            path.push(i);
+
+            let child = KeyCollector { seen: &mut *self.seen, package: None, path, sidecar: &mut *self.sidecar };
+            if seq.next_element_seed(child)?.is_none() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes one element straight from the JSON stream
+struct ElementEncoder<'a, W> {
+    writer: &'a mut W,
+    lookup: &'a HashMap<String, usize>,
+}
+
+impl<W: Write + Seek> ElementEncoder<'_, W> {
+    fn write_header<E: de::Error>(&mut self, name: Option<&str>, attributes: &[(String, Value)]) -> Result<(), E> {
+        let name = name.ok_or_else(|| E::missing_field("__name"))?;
+
+        encode_element_header(self.writer, name, attributes.iter().map(|(k, v)| (k, v)), &EncodeContext::new(self.lookup))
+            .map_err(E::custom)
+    }
+}
+
+impl<'de, W: Write + Seek> DeserializeSeed<'de> for ElementEncoder<'_, W> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, W: Write + Seek> Visitor<'de> for ElementEncoder<'_, W> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map element object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut name: Option<String> = None;
+        let mut attributes = Vec::new();
+        let mut has_children = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "__name" if has_children => {
+                    return Err(de::Error::custom("__name must come before __children when streaming"));
+                }
+                "__name" => name = Some(map.next_value()?),
+                "__children" => {
+                    if has_children {
+                        return Err(de::Error::duplicate_field("__children"));
+                    }
+                    has_children = true;
+
+                    self.write_header(name.as_deref(), &attributes)?;
+
+                    // Reserve the child count and patch it once the array ends
+                    let count_position = self.writer.stream_position().map_err(de::Error::custom)?;
+                    self.writer.write_all(&0u16.to_le_bytes()).map_err(de::Error::custom)?;
+
+                    let count = map.next_value_seed(ChildEncoder { writer: &mut *self.writer, lookup: self.lookup })?;
+
+                    let end_position = self.writer.stream_position().map_err(de::Error::custom)?;
+                    self.writer.seek(SeekFrom::Start(count_position)).map_err(de::Error::custom)?;
+                    self.writer.write_all(&count.to_le_bytes()).map_err(de::Error::custom)?;
+                    self.writer.seek(SeekFrom::Start(end_position)).map_err(de::Error::custom)?;
+                }
+                _ if has_children => {
+                    return Err(de::Error::custom(format!(
+                        "attribute {} must come before __children when streaming",
+                        key
+                    )));
+                }
+                _ => {
+                    let value: Value = map.next_value()?;
+                    attributes.push((key, value));
+                }
+            }
+        }
+
+        if !has_children {
+            self.write_header(name.as_deref(), &attributes)?;
+            self.writer.write_all(&0u16.to_le_bytes()).map_err(de::Error::custom)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes a `__children` array, returning the number of children written
+///
+/// A `null` array, which `DecodedElement` reads as no children, writes none.
+struct ChildEncoder<'a, W> {
+    writer: &'a mut W,
+    lookup: &'a HashMap<String, usize>,
+}
+
+impl<'de, W: Write + Seek> DeserializeSeed<'de> for ChildEncoder<'_, W> {
+    type Value = u16;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<u16, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, W: Write + Seek> Visitor<'de> for ChildEncoder<'_, W> {
+    type Value = u16;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of child elements or null")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<u16, E> {
+        Ok(0)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<u16, E> {
+        Ok(0)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<u16, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<u16, A::Error> {
+        let mut count: u16 = 0;
+
+        while seq.next_element_seed(ElementEncoder { writer: &mut *self.writer, lookup: self.lookup })?.is_some() {
+            count = count.checked_add(1).ok_or_else(|| de::Error::custom("more than 65535 children"))?;
+        }
+
+        Ok(count)
+    }
+}
+
+/// Convert binary map to JSON without building the element tree
+///
+/// The counterpart of [`json_to_bin_streaming`]: each element is decoded
+/// right before it is serialized and dropped once its children are
+/// written, so only the elements on the path from the root to the current
+/// one are in memory, besides the lookup table. Peak memory no longer
+/// grows with the number of rooms or entities.
+///
+/// The JSON is the same as from `bin_to_json`, `.meta.json` sidecar
+/// attributes included. Data after the root element is not checked. A
+/// decode error leaves a partial JSON file behind. Like `bin_to_json`, it
+/// only reads maps with the standard [`DEFAULT_MAGIC`] header and nothing
+/// before it; use a [`Decoder`](crate::Decoder) for other headers.
+pub fn bin_to_json_streaming<P: AsRef<Path>, Q: AsRef<Path>>(bin_path: P, json_path: Q) -> io::Result<()> {
+    let bin_path = bin_path.as_ref();
+
+    let mut sidecar: HashMap<Vec<usize>, Vec<SidecarAttribute>> = HashMap::new();
+    for attribute in read_sidecar_attributes(bin_path)? {
+        sidecar.entry(attribute.path.clone()).or_default().push(attribute);
+    }
+
+    let file = File::open(bin_path)?;
+    let total_len = file.metadata()?.len();
+    let mut reader = PositionReader::new(BufReader::new(file));
+
+    // The smallest root element takes five bytes
+    let (package, lookup) =
+        read_header(&mut reader, &Format::default(), Some(total_len), 5).map_err(|e| wrap_error(e, &reader))?;
+
+    let source = RefCell::new(ElementSource { reader, ctx: DecodeContext::new(&lookup), sidecar, error: None });
+    let Some((mut root, child_count)) = source.borrow_mut().read_head(&[]) else {
+        return Err(source.into_inner().error.unwrap_or_else(|| io::ErrorKind::InvalidData.into()));
+    };
+    root.attributes.insert("package".to_string(), Value::String(package));
+
+    let mut writer = BufWriter::new(File::create(json_path)?);
+    let element = StreamedElement { source: &source, head: root, child_count, path: Vec::new() };
+    let result = serde_json::to_writer_pretty(&mut writer, &element);
+
+    // A decode error surfaces as a generic serializer error; report the original
+    if let Some(error) = source.into_inner().error {
+        return Err(error);
+    }
+    result?;
+
+    writer.flush()
+}
+
+/// Binary element stream shared by the nested [`StreamedElement`]s
+struct ElementSource<'a, R> {
+    reader: PositionReader<R>,
+    ctx: DecodeContext<'a>,
+    /// Sidecar entries by element path
+    sidecar: HashMap<Vec<usize>, Vec<SidecarAttribute>>,
+    /// First decode error, kept since serializers only carry a message
+    error: Option<io::Error>,
+}
+
+impl<R: Read> ElementSource<'_, R> {
+    /// Reads the next element's name, attributes and child count
+    ///
+    /// Returns `None` after recording the error when decoding fails.
+    fn read_head(&mut self, path: &[usize]) -> Option<(DecodedElement, usize)> {
+        let (mut head, child_count) = match decode_element_head(&mut self.reader, &mut self.ctx) {
+            Ok((head, Some(child_count))) => (head, child_count),
+            Ok((_, None)) => unreachable!("recovery mode is off"),
+            Err(e) => {
+                self.error = Some(wrap_error(e, &self.reader));
+                return None;
+            }
+        };
+
+        for attribute in self.sidecar.remove(path).into_iter().flatten() {
+            if attribute.applies_to(&head.name) {
+                head.attributes.insert(attribute.key, attribute.value);
+            }
+        }
+
+        Some((head, child_count))
+    }
+}
+
+/// An element whose children are decoded while it is being serialized
+struct StreamedElement<'s, 'a, R> {
+    source: &'s RefCell<ElementSource<'a, R>>,
+    head: DecodedElement,
+    child_count: usize,
+    path: Vec<usize>,
+}
+
+impl<R: Read> Serialize for StreamedElement<'_, '_, R> {
+    /// Writes the same fields as `DecodedElement`'s own `Serialize`
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("__name", &self.head.name)?;
+        for (key, value) in &self.head.attributes {
+            map.serialize_entry(key, value)?;
+        }
+
+        if self.child_count > 0 {
+            map.serialize_entry("__children", &StreamedChildren { element: self })?;
+        }
+        map.end()
+    }
+}
+
+struct StreamedChildren<'e, 's, 'a, R> {
+    element: &'e StreamedElement<'s, 'a, R>,
+}
+
+impl<R: Read> Serialize for StreamedChildren<'_, '_, '_, R> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let parent = self.element;
+        let mut seq = serializer.serialize_seq(Some(parent.child_count))?;
+
+        for i in 0..parent.child_count {
+            let mut path = parent.path.clone();
+            path.push(i);
+
+            let head = parent.source.borrow_mut().read_head(&path);
+            let Some((head, child_count)) = head else {
+                return Err(ser::Error::custom("binary map decoding failed"));
+            };
+
+            seq.serialize_element(&StreamedElement { source: parent.source, head, child_count, path })?;
+        }
+
+        seq.end()
+    }
+}
\ No newline at end of file
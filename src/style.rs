@@ -0,0 +1,62 @@
+use crate::element::DecodedElement;
+
+/// Stylegrounds of a map, backgrounds first, then foregrounds
+///
+/// Collects the children of `Style > Backgrounds` and `Style > Foregrounds`:
+/// `parallax` images and effects such as `snowFg`. Children of `apply`
+/// groups are returned in place of the group itself; attributes set on the
+/// group are not merged into them. Missing sections yield an empty list.
+pub fn stylegrounds(map: &DecodedElement) -> Vec<&DecodedElement> {
+    let mut result = Vec::new();
+    
+    let Some(style) = map.find_child("Style") else {
+        return result;
+    };
+    
+    for section in ["Backgrounds", "Foregrounds"] {
+        let Some(section) = style.find_child(section) else {
+            continue;
+        };
+        
+        for child in section.children.iter().flatten() {
+            if child.name == "apply" {
+                result.extend(child.children.iter().flatten());
+            } else {
+                result.push(child);
+            }
+        }
+    }
+    
+    result
+}
+
+/// Typed accessors over a styleground element
+#[derive(Debug, Clone, Copy)]
+pub struct Styleground<'a>(pub &'a DecodedElement);
+
+impl<'a> Styleground<'a> {
+    /// True for `parallax` images, false for effects
+    pub fn is_parallax(&self) -> bool {
+        self.0.name == "parallax"
+    }
+    
+    /// Texture path of a parallax image
+    pub fn texture(&self) -> Option<&'a str> {
+        self.0.get_str("texture")
+    }
+    
+    /// Horizontal scroll factor relative to the camera
+    pub fn scroll_x(&self) -> Option<f64> {
+        self.0.get_f64("scrollx")
+    }
+    
+    /// Vertical scroll factor relative to the camera
+    pub fn scroll_y(&self) -> Option<f64> {
+        self.0.get_f64("scrolly")
+    }
+    
+    /// Comma-separated room filter, `*` meaning every room
+    pub fn only(&self) -> Option<&'a str> {
+        self.0.get_str("only")
+    }
+}
\ No newline at end of file
@@ -0,0 +1,292 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+
+use crate::element::DecodedElement;
+
+/// Room children holding character tile data in `innerText`
+const TILE_LAYERS: &[&str] = &["solids", "bg"];
+
+/// Tile characters of the vanilla tilesets
+///
+/// `0` is air; the digits and lowercase letters cover the ids defined in
+/// `ForegroundTiles.xml` and `BackgroundTiles.xml`. Mods with custom tilesets
+/// add their own characters to the set returned by [`default_tile_chars`].
+const VANILLA_TILE_CHARS: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Attribute pointing at the external file that holds a layer's tiles
+///
+/// The `__` prefix keeps it out of the binary, so layers must be re-embedded
+/// with [`embed_tile_layers`] before encoding.
+pub const TILE_FILE_ATTRIBUTE: &str = "__tileFile";
+
+/// Turns a room name into something safe to use as a file name
+fn file_stem(room: &str) -> String {
+    room.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Moves every `solids`/`bg` tile string into its own `.txt` file in `dir`
+///
+/// Returns a copy of the map where each layer's `innerText` is replaced by a
+/// `__tileFile` attribute naming the file, e.g. `a-00.solids.txt`. The input
+/// map is left untouched. Fails if two rooms would write the same file.
+pub fn extract_tile_layers<P: AsRef<Path>>(map: &DecodedElement, dir: P) -> io::Result<DecodedElement> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    
+    let mut extracted = map.clone();
+    let mut written = HashSet::new();
+    
+    for room in extracted.rooms_mut() {
+        let room_name = match room.attributes.get("name") {
+            Some(Value::String(name)) => file_stem(name),
+            _ => continue,
+        };
+        
+        for layer in room.children.iter_mut().flatten() {
+            if !TILE_LAYERS.contains(&layer.name.as_str()) {
+                continue;
+            }
+            
+            let Some(Value::String(tiles)) = layer.attributes.remove("innerText") else {
+                continue;
+            };
+            
+            let file_name = format!("{}.{}.txt", room_name, layer.name);
+            if !written.insert(file_name.clone()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("Tile file {} would be written twice", file_name),
+                ));
+            }
+            
+            fs::write(dir.join(&file_name), tiles)?;
+            layer.set_attribute(TILE_FILE_ATTRIBUTE, file_name);
+        }
+    }
+    
+    Ok(extracted)
+}
+
+/// Reads tile files referenced by `__tileFile` back into `innerText`
+///
+/// The inverse of [`extract_tile_layers`]; call it before `encode_map`.
+/// File names are resolved relative to `dir`, and may not leave it: names
+/// that are absolute or contain `..` are an error.
+pub fn embed_tile_layers<P: AsRef<Path>>(map: &DecodedElement, dir: P) -> io::Result<DecodedElement> {
+    let dir = dir.as_ref();
+    let mut embedded = map.clone();
+    
+    for room in embedded.rooms_mut() {
+        for layer in room.children.iter_mut().flatten() {
+            let Some(Value::String(file_name)) = layer.attributes.remove(TILE_FILE_ATTRIBUTE) else {
+                continue;
+            };
+            
+            let inside = Path::new(&file_name).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if !inside {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Tile file {:?} of {} is outside the tile directory", file_name, layer.name),
+                ));
+            }
+            
+            let tiles = fs::read_to_string(dir.join(&file_name))?;
+            layer.set_attribute("innerText", tiles);
+        }
+    }
+    
+    Ok(embedded)
+}
+
+/// A tile layer's `innerText` split into rows of characters
+///
+/// Rows may have different lengths, as Celeste trims trailing air.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileGrid {
+    rows: Vec<Vec<char>>,
+}
+
+impl TileGrid {
+    /// Parses tile text with `\n` or `\r\n` row separators
+    pub fn parse(text: &str) -> Self {
+        let rows = text.lines().map(|line| line.chars().collect()).collect();
+        Self { rows }
+    }
+    
+    /// Reads the grid of a tile layer element such as `solids`
+    pub fn from_element(layer: &DecodedElement) -> Option<Self> {
+        layer.get_str("innerText").map(Self::parse)
+    }
+    
+    /// Number of rows
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+    
+    /// Length of the longest row
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+    
+    /// Tile at column `x` of row `y`; `None` outside the stored text
+    pub fn get(&self, x: usize, y: usize) -> Option<char> {
+        self.rows.get(y)?.get(x).copied()
+    }
+    
+    /// Iterates `(x, y, tile)` for every stored cell, row by row
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, char)> + '_ {
+        self.rows.iter().enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &tile)| (x, y, tile)))
+    }
+}
+
+/// A tile character outside the allowed set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TileError {
+    /// Name of the room containing the layer
+    pub room: String,
+    /// `solids` or `bg`
+    pub layer: String,
+    /// Column, in tiles from the left edge of the room
+    pub x: usize,
+    /// Row, in tiles from the top edge of the room
+    pub y: usize,
+    /// The offending character
+    pub tile: char,
+}
+
+/// The vanilla tile alphabet, to be extended with modded tilesets
+pub fn default_tile_chars() -> HashSet<char> {
+    VANILLA_TILE_CHARS.chars().collect()
+}
+
+/// Reports every `solids`/`bg` cell whose character is not in `allowed`
+///
+/// Errors are listed room by room, in row order within each layer.
+pub fn validate_tiles(map: &DecodedElement, allowed: &HashSet<char>) -> Vec<TileError> {
+    let mut errors = Vec::new();
+    
+    for room in map.rooms() {
+        let room_name = room.get_str("name").unwrap_or_default();
+        
+        for layer in room.children.iter().flatten() {
+            if !TILE_LAYERS.contains(&layer.name.as_str()) {
+                continue;
+            }
+            
+            let Some(grid) = TileGrid::from_element(layer) else {
+                continue;
+            };
+            
+            for (x, y, tile) in grid.cells() {
+                if !allowed.contains(&tile) {
+                    errors.push(TileError {
+                        room: room_name.to_string(),
+                        layer: layer.name.clone(),
+                        x,
+                        y,
+                        tile,
+                    });
+                }
+            }
+        }
+    }
+    
+    errors
+}
+
+/// Value of an empty cell in object tile layers
+pub const EMPTY_OBJECT_TILE: i32 = -1;
+
+/// An `objtiles`/`bgtiles` layer: a grid of tile indices
+///
+/// These layers store one integer per cell, comma-separated within a row
+/// and one row per line, with `-1` for empty cells. Short rows and cells
+/// outside the stored text are empty too.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ObjectTileGrid {
+    rows: Vec<Vec<i32>>,
+}
+
+impl ObjectTileGrid {
+    /// Parses the comma/newline integer format; blank cells count as empty
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut rows = Vec::new();
+        
+        for (y, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                rows.push(Vec::new());
+                continue;
+            }
+            
+            let row = line.split(',').enumerate().map(|(x, cell)| {
+                let cell = cell.trim();
+                if cell.is_empty() {
+                    return Ok(EMPTY_OBJECT_TILE);
+                }
+                
+                cell.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid object tile {:?} at ({}, {})", cell, x, y))
+                })
+            });
+            rows.push(row.collect::<io::Result<_>>()?);
+        }
+        
+        Ok(Self { rows })
+    }
+    
+    /// Reads the grid of a layer element; a layer without `innerText` is empty
+    pub fn from_element(layer: &DecodedElement) -> io::Result<Self> {
+        layer.get_str("innerText").map_or_else(|| Ok(Self::default()), Self::parse)
+    }
+    
+    /// Number of rows
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+    
+    /// Length of the longest row
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+    
+    /// Tile index at column `x` of row `y`, or `None` for an empty cell
+    pub fn get(&self, x: usize, y: usize) -> Option<i32> {
+        self.rows.get(y)?.get(x).copied().filter(|&tile| tile != EMPTY_OBJECT_TILE)
+    }
+    
+    /// Sets or clears a cell, growing the grid with empty cells as needed
+    pub fn set(&mut self, x: usize, y: usize, tile: Option<i32>) {
+        if self.rows.len() <= y {
+            self.rows.resize_with(y + 1, Vec::new);
+        }
+        
+        let row = &mut self.rows[y];
+        if row.len() <= x {
+            row.resize(x + 1, EMPTY_OBJECT_TILE);
+        }
+        
+        row[x] = tile.unwrap_or(EMPTY_OBJECT_TILE);
+    }
+    
+    /// Formats the grid back into the layer text format
+    pub fn to_text(&self) -> String {
+        self.rows.iter()
+            .map(|row| row.iter().map(i32::to_string).collect::<Vec<_>>().join(","))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    
+    /// Builds a layer element named `name` (`objtiles` or `bgtiles`)
+    pub fn to_element(&self, name: &str) -> DecodedElement {
+        let mut layer = DecodedElement::new(name);
+        layer.set_attribute("innerText", self.to_text());
+        layer
+    }
+}
\ No newline at end of file
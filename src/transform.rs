@@ -0,0 +1,274 @@
+use serde_json::{Number, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+
+use crate::element::{DecodedElement, RUN_LENGTH_ATTRIBUTE};
+use crate::map::encoded_size;
+use crate::order::{entity_order, SORTABLE_CONTAINERS};
+
+/// Room children whose elements carry entity ids
+const ID_CONTAINERS: &[&str] = &["entities", "triggers"];
+
+/// Attributes that are always numeric in Celeste maps, for
+/// [`coerce_numeric_strings`]
+pub const NUMERIC_ATTRIBUTES: &[&str] = &[
+    "x", "y", "width", "height", "originX", "originY", "id", "depth",
+    "scaleX", "scaleY", "rotation", "scrollx", "scrolly", "speedx", "speedy", "alpha",
+];
+
+/// Room attributes kept by [`geometry_only`]
+const GEOMETRY_ATTRIBUTES: &[&str] = &["name", "x", "y", "width", "height"];
+
+/// Room children kept by [`geometry_only`]
+const GEOMETRY_LAYERS: &[&str] = &["solids", "bg"];
+
+/// Renumbers entity and trigger ids in each room to a dense `1..=N` sequence
+///
+/// Ids are assigned in document order, entities before triggers, and share
+/// one sequence per room so they stay unique within it. Only elements
+/// directly inside a room's `entities`/`triggers` that already have an `id`
+/// attribute are touched. Attributes elsewhere that refer to old ids are not
+/// rewritten.
+pub fn compact_ids(map: &mut DecodedElement) {
+    for room in map.rooms_mut() {
+        let mut next_id: i64 = 1;
+        
+        for container in ID_CONTAINERS {
+            let Some(children) = room.children.iter_mut().flatten()
+                .find(|child| child.name == *container)
+                .and_then(|container| container.children.as_mut())
+            else {
+                continue;
+            };
+            
+            for element in children {
+                if element.attributes.contains_key("id") {
+                    element.set_attribute("id", Value::from(next_id));
+                    next_id += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Copies the map keeping only what is needed to draw room geometry
+///
+/// The result has the root with its attributes (so it still encodes) and a
+/// `levels` element whose rooms keep their name, position and size plus
+/// their `solids` and `bg` tile layers. Entities, triggers, decals, style
+/// and filler are left out. The input map is not modified.
+pub fn geometry_only(map: &DecodedElement) -> DecodedElement {
+    let mut levels = DecodedElement::new("levels");
+    
+    for room in map.rooms() {
+        let mut geometry = DecodedElement::new(room.name.clone());
+        
+        for key in GEOMETRY_ATTRIBUTES {
+            if let Some(value) = room.attributes.get(*key) {
+                geometry.attributes.insert(key.to_string(), value.clone());
+            }
+        }
+        
+        let layers: Vec<_> = room.children.iter().flatten()
+            .filter(|child| GEOMETRY_LAYERS.contains(&child.name.as_str()))
+            .cloned()
+            .collect();
+        geometry.children = (!layers.is_empty()).then_some(layers);
+        
+        levels.children.get_or_insert_with(Vec::new).push(geometry);
+    }
+    
+    let mut stripped = DecodedElement::new(map.name.clone());
+    stripped.attributes = map.attributes.clone();
+    stripped.children = Some(vec![levels]);
+    stripped
+}
+
+/// Turns numeric strings such as `"32"` into numbers, for the given attributes
+///
+/// Meant for maps imported from tools that write every value as text. Only
+/// string values of the listed attributes that parse as a number (ignoring
+/// surrounding whitespace) are replaced: `"32"` becomes the integer `32` and
+/// `"1.5"` or `"16.0"` a float, so they encode with the usual number types.
+/// Anything else, including strings of other attributes, is left alone.
+/// Pass [`NUMERIC_ATTRIBUTES`] for the common cases. Returns the number of
+/// values converted.
+pub fn coerce_numeric_strings(map: &mut DecodedElement, attributes: &[&str]) -> usize {
+    let mut converted = 0;
+    
+    map.visit_mut(&mut |element| {
+        for key in attributes {
+            let Some(number) = element.get_str(key).and_then(parse_number) else {
+                continue;
+            };
+            
+            element.set_attribute(*key, number);
+            converted += 1;
+        }
+    });
+    
+    converted
+}
+
+/// Parses a string as an integer if it is written as one, as a float otherwise
+fn parse_number(s: &str) -> Option<Number> {
+    let s = s.trim();
+    
+    if let Ok(integer) = s.parse::<i64>() {
+        return Some(Number::from(integer));
+    }
+    
+    s.parse::<f64>().ok().and_then(Number::from_f64)
+}
+
+/// A string attribute shortened by [`truncate_long_strings`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Truncated {
+    /// Ancestors of the element, as passed by
+    /// [`DecodedElement::visit_mut_with_path`], e.g.
+    /// `["Map", "levels", "lvl_3", "entities"]`
+    pub path: Vec<String>,
+    /// Name of the element holding the attribute
+    pub element: String,
+    pub key: String,
+    /// Length of the value before truncation, in characters
+    pub original_len: usize,
+    /// The characters that were cut off, so the value can be restored
+    pub removed: String,
+}
+
+/// Cuts string attributes down to `max_len` characters
+///
+/// A cleanup pass for maps bloated by a tool writing runaway strings. Every
+/// string value longer than `max_len` characters is shortened to its first
+/// `max_len`, and each change is reported with the part that was removed,
+/// in document order. `innerText` is left alone, as it holds tile grids
+/// that are legitimately long and would be corrupted by a cut. Changed
+/// elements are marked dirty.
+pub fn truncate_long_strings(map: &mut DecodedElement, max_len: usize) -> Vec<Truncated> {
+    let mut truncated = Vec::new();
+    
+    map.visit_mut_with_path(&mut |path, element| {
+        let mut keys: Vec<_> = element.attributes.keys().cloned().collect();
+        keys.sort();
+        
+        for key in keys {
+            if key == RUN_LENGTH_ATTRIBUTE {
+                continue;
+            }
+            let Some(Value::String(value)) = element.attributes.get_mut(&key) else {
+                continue;
+            };
+            let Some((cut, _)) = value.char_indices().nth(max_len) else {
+                continue;
+            };
+            
+            let removed = value.split_off(cut);
+            truncated.push(Truncated {
+                path: path.iter().map(|segment| segment.to_string()).collect(),
+                element: element.name.clone(),
+                original_len: max_len + removed.chars().count(),
+                key,
+                removed,
+            });
+            element.mark_dirty();
+        }
+    });
+    
+    truncated
+}
+
+/// What [`drop_default_attributes`] removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DroppedDefaults {
+    pub attributes: usize,
+    /// Difference in encoded size, lookup strings no longer needed included
+    pub bytes: u64,
+}
+
+/// Removes attributes that are left at their default value
+///
+/// `defaults` maps element names such as `"spinner"` to the attributes
+/// Celeste assumes when they are missing, with their default values; the
+/// table comes from the caller's knowledge of entity schemas. Every element
+/// in the tree with an entry has the attributes equal to their default
+/// removed. Values must have the same type to match, except that numbers
+/// compare by value, as Celeste reads `1` and `1.0` alike: a default of
+/// `false` does not drop `0`, nor does `"1"` match `1`. Changed elements
+/// are marked dirty.
+///
+/// The map is measured with a dry-run encode before and after, the latter
+/// on a pruned copy, so an error is returned, and nothing removed, if
+/// either does not encode.
+pub fn drop_default_attributes(
+    map: &mut DecodedElement,
+    defaults: &HashMap<String, HashMap<String, Value>>,
+) -> io::Result<DroppedDefaults> {
+    let before = encoded_size(map)?;
+    
+    let mut pruned = map.clone();
+    remove_defaults(&mut pruned, defaults);
+    let after = encoded_size(&pruned)?;
+    
+    // Pruned again in place, as the copy lost the dirty flags
+    let attributes = remove_defaults(map, defaults);
+    Ok(DroppedDefaults { attributes, bytes: before.saturating_sub(after) })
+}
+
+/// Removes the default attributes, returning how many there were
+fn remove_defaults(map: &mut DecodedElement, defaults: &HashMap<String, HashMap<String, Value>>) -> usize {
+    let mut attributes = 0;
+    
+    map.visit_mut(&mut |element| {
+        let Some(element_defaults) = defaults.get(&element.name) else {
+            return;
+        };
+        
+        let count = element.attributes.len();
+        element.attributes.retain(|key, value| {
+            !element_defaults.get(key).is_some_and(|default| is_default(value, default))
+        });
+        
+        if element.attributes.len() != count {
+            attributes += count - element.attributes.len();
+            element.mark_dirty();
+        }
+    });
+    
+    attributes
+}
+
+fn is_default(value: &Value, default: &Value) -> bool {
+    match (value, default) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => value == default,
+    }
+}
+
+/// Sorts the entities and triggers of every room into a stable order
+///
+/// Sorts by `id`, then element name, then `x` and `y`, so two saves of the
+/// same room list their entities identically and diffs only show real
+/// changes. Only [`SORTABLE_CONTAINERS`] are touched: decals and
+/// stylegrounds are drawn in file order. Containers that were not already
+/// sorted are marked dirty.
+pub fn sort_entities(map: &mut DecodedElement) {
+    for room in map.rooms_mut() {
+        for container in room.children.iter_mut().flatten() {
+            if !SORTABLE_CONTAINERS.contains(&container.name.as_str()) {
+                continue;
+            }
+            let Some(children) = &mut container.children else {
+                continue;
+            };
+            
+            if children.windows(2).all(|pair| entity_order(&pair[0], &pair[1]) != Ordering::Greater) {
+                continue;
+            }
+            
+            children.sort_by(entity_order);
+            container.mark_dirty();
+        }
+    }
+}
\ No newline at end of file
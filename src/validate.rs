@@ -0,0 +1,362 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::binary::number_type_code;
+use crate::element::DecodedElement;
+use crate::rooms::room_bounds;
+
+/// How serious a validation issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The map is likely to load, but something looks wrong
+    Warning,
+    /// The map will not encode or will not load in Celeste
+    Error,
+}
+
+/// A single problem found while validating a map
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+    /// Byte offset in the binary file, when the issue comes from decoding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u64>,
+}
+
+impl Issue {
+    /// Creates a warning with the given message
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, message: message.into(), offset: None }
+    }
+
+    /// Creates an error with the given message
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, message: message.into(), offset: None }
+    }
+    
+    /// Attaches a byte offset to the issue
+    pub fn at_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+}
+
+/// Checks a decoded map for structural problems
+///
+/// Only looks at the tree itself; decode failures are reported by the caller.
+pub fn validate_map(map: &DecodedElement) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    
+    match package_attribute(map.attributes.get("package")) {
+        Ok("") => issues.push(Issue::error("Empty package attribute")),
+        Ok(package) => issues.extend(package_problems(package).into_iter().map(Issue::warning)),
+        Err(message) => issues.push(Issue::error(message)),
+    }
+    
+    if map.name != "Map" {
+        issues.push(Issue::warning(format!("Root element is named {:?}, expected \"Map\"", map.name)));
+    }
+    
+    let Some(levels) = map.find_child("levels") else {
+        issues.push(Issue::error("Map has no levels element"));
+        return issues;
+    };
+    
+    let mut names = HashSet::new();
+    
+    for level in levels.children.iter().flatten() {
+        match level.attributes.get("name") {
+            Some(Value::String(name)) => {
+                if !names.insert(name.as_str()) {
+                    issues.push(Issue::error(format!("Duplicate room name {:?}", name)));
+                }
+            }
+            _ => issues.push(Issue::warning("Room without a name attribute")),
+        }
+    }
+    
+    if names.is_empty() {
+        issues.push(Issue::warning("Map has no rooms"));
+    }
+    
+    issues
+}
+
+/// Characters that are not allowed in file names on Windows
+const INVALID_PACKAGE_CHARS: &[char] = &[':', '*', '?', '"', '<', '>', '|'];
+
+/// Describes everything wrong with a package name
+///
+/// Everest resolves the package as a path under `Maps/`, so it has to be a
+/// clean forward-slash path without spaces or the `.bin` extension.
+fn package_problems(package: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    
+    if package.chars().any(char::is_whitespace) {
+        problems.push(format!("Package {:?} contains whitespace", package));
+    }
+    if package.contains('\\') {
+        problems.push(format!("Package {:?} uses backslashes instead of forward slashes", package));
+    }
+    if package.contains(INVALID_PACKAGE_CHARS) || package.chars().any(char::is_control) {
+        problems.push(format!("Package {:?} contains characters that are invalid in file names", package));
+    }
+    if package.ends_with(".bin") {
+        problems.push(format!("Package {:?} should not include the .bin extension", package));
+    }
+    if package.starts_with('/') || package.ends_with('/') || package.contains("//") {
+        problems.push(format!("Package {:?} has an empty path segment", package));
+    }
+    
+    problems
+}
+
+/// Reads the root's `package` value
+///
+/// Tells a missing attribute apart from one holding something other than a
+/// string, which usually means a hand-edited JSON forgot the quotes.
+pub(crate) fn package_attribute(value: Option<&Value>) -> Result<&str, String> {
+    match value {
+        Some(Value::String(package)) => Ok(package),
+        None | Some(Value::Null) => Err("Missing package attribute".to_string()),
+        Some(other) => Err(format!(
+            "Package attribute must be a string, found {} {}; quote the value",
+            json_type_name(other),
+            other
+        )),
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Rewrites a package name into the form Everest expects
+///
+/// Backslashes become forward slashes, whitespace becomes underscores,
+/// characters invalid in file names and a trailing `.bin` are dropped, and
+/// empty path segments are removed. A normalized package has no
+/// [`validate_map`] warnings.
+pub(crate) fn normalize_package(package: &str) -> String {
+    let package = package.trim();
+    let package = package.strip_suffix(".bin").unwrap_or(package);
+    
+    let cleaned: String = package
+        .chars()
+        .filter(|c| !INVALID_PACKAGE_CHARS.contains(c) && !c.is_control())
+        .map(|c| match c {
+            '\\' => '/',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect();
+    
+    cleaned.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>().join("/")
+}
+
+/// A decal whose texture is not in the known texture set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MissingTexture {
+    /// Name of the room containing the decal
+    pub room: String,
+    /// `fgdecals` or `bgdecals`
+    pub layer: String,
+    /// Texture path exactly as written in the map
+    pub texture: String,
+}
+
+/// Normalizes a decal texture path for comparison
+///
+/// Uses forward slashes and drops a leading `decals/` and a file extension,
+/// so `decals\1-forsakencity\flag.png` and `1-forsakencity/flag` match.
+fn normalize_texture(texture: &str) -> String {
+    let texture = texture.replace('\\', "/");
+    let texture = texture.strip_prefix("decals/").unwrap_or(&texture);
+    
+    match Path::new(texture).extension() {
+        Some(ext) => texture[..texture.len() - ext.len() - 1].to_string(),
+        None => texture.to_string(),
+    }
+}
+
+/// Reports decals whose `texture` is not in `known_textures`
+///
+/// Walks the `fgdecals` and `bgdecals` of every room. Both the decal
+/// textures and the known set are normalized first, so the set can come
+/// straight from an atlas dump with or without `decals/` prefixes and
+/// `.png` extensions.
+pub fn check_decal_textures(map: &DecodedElement, known_textures: &HashSet<String>) -> Vec<MissingTexture> {
+    let known: HashSet<_> = known_textures.iter().map(|t| normalize_texture(t)).collect();
+    let mut missing = Vec::new();
+    
+    for room in map.rooms() {
+        let room_name = room.attributes.get("name").and_then(Value::as_str).unwrap_or_default();
+        
+        for layer in room.children.iter().flatten() {
+            if layer.name != "fgdecals" && layer.name != "bgdecals" {
+                continue;
+            }
+            
+            for decal in layer.children.iter().flatten() {
+                let Some(Value::String(texture)) = decal.attributes.get("texture") else {
+                    continue;
+                };
+                
+                if !known.contains(&normalize_texture(texture)) {
+                    missing.push(MissingTexture {
+                        room: room_name.to_string(),
+                        layer: layer.name.clone(),
+                        texture: texture.clone(),
+                    });
+                }
+            }
+        }
+    }
+    
+    missing
+}
+
+/// Distance outside its room, in pixels, beyond which
+/// [`check_entity_positions`] reports an entity
+///
+/// Entities often hang a little past the edge on purpose, such as spikes
+/// or a camera target for the next room, so only larger offsets count.
+pub const OUT_OF_ROOM_THRESHOLD: f64 = 64.0;
+
+/// An entity or trigger placed far outside its room
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutOfRoomEntity {
+    /// Name of the room containing the entity
+    pub room: String,
+    /// Element name of the entity, e.g. `spinner`
+    pub entity: String,
+    /// The entity's `id`, if it has one
+    pub id: Option<i64>,
+    pub x: f64,
+    pub y: f64,
+    /// Distance from the position to the nearest point of the room, in pixels
+    pub distance: f64,
+}
+
+/// Reports entities placed far outside their room
+///
+/// Uses [`OUT_OF_ROOM_THRESHOLD`]; see [`check_entity_positions_with_threshold`].
+pub fn check_entity_positions(map: &DecodedElement) -> Vec<OutOfRoomEntity> {
+    check_entity_positions_with_threshold(map, OUT_OF_ROOM_THRESHOLD)
+}
+
+/// Reports entities more than `threshold` pixels outside their room
+///
+/// Walks the `entities` and `triggers` of every room. Their `x` and `y`
+/// are relative to the room's top-left corner, so a position counts as
+/// inside when it lies within the room's `width` and `height` from
+/// [`room_bounds`]. Rooms without bounds and entities without a numeric
+/// position are skipped.
+pub fn check_entity_positions_with_threshold(map: &DecodedElement, threshold: f64) -> Vec<OutOfRoomEntity> {
+    let mut found = Vec::new();
+    
+    for room in map.rooms() {
+        let Some(bounds) = room_bounds(room) else {
+            continue;
+        };
+        let room_name = room.get_str("name").unwrap_or_default();
+        
+        for container in room.children.iter().flatten() {
+            if container.name != "entities" && container.name != "triggers" {
+                continue;
+            }
+            
+            for entity in container.children.iter().flatten() {
+                let (Some(x), Some(y)) = (entity.get_f64("x"), entity.get_f64("y")) else {
+                    continue;
+                };
+                
+                let dx = (-x).max(x - bounds.width).max(0.0);
+                let dy = (-y).max(y - bounds.height).max(0.0);
+                let distance = dx.hypot(dy);
+                
+                if distance > threshold {
+                    found.push(OutOfRoomEntity {
+                        room: room_name.to_string(),
+                        entity: entity.name.clone(),
+                        id: entity.get_i64("id"),
+                        x,
+                        y,
+                        distance,
+                    });
+                }
+            }
+        }
+    }
+    
+    found
+}
+
+/// A numeric attribute that changes value when stored as a 32-bit float
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PrecisionWarning {
+    /// JSON pointer to the attribute, as in `apply_patch`, e.g.
+    /// `/__children/0/__children/3/x`
+    pub path: String,
+    /// Value in the map
+    pub value: f64,
+    /// Value after the round trip through f32
+    pub stored: f64,
+    /// `stored - value`
+    pub error: f64,
+}
+
+/// Reports numeric attributes whose exact value is not representable as f32
+///
+/// Covers every number the encoder writes as an f32: floats, and integers
+/// outside the i32 range. Integers within it are stored exactly. Decimal
+/// fractions such as `0.1` have no exact binary form and are reported too;
+/// use [`PrecisionWarning::error`] to tell a harmless last-digit rounding
+/// from precision a programmatic edit really needs.
+pub fn check_float_precision(map: &DecodedElement) -> Vec<PrecisionWarning> {
+    let mut warnings = Vec::new();
+    collect_precision_warnings(map, &mut String::new(), &mut warnings);
+    warnings
+}
+
+fn collect_precision_warnings(element: &DecodedElement, path: &mut String, out: &mut Vec<PrecisionWarning>) {
+    let mut keys: Vec<_> = element.attributes.keys().collect();
+    keys.sort();
+    
+    for key in keys {
+        let Value::Number(n) = &element.attributes[key] else {
+            continue;
+        };
+        let Some(value) = n.as_f64().filter(|_| number_type_code(n) == Some(4)) else {
+            continue;
+        };
+        
+        let stored = value as f32 as f64;
+        if stored != value {
+            out.push(PrecisionWarning {
+                path: format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1")),
+                value,
+                stored,
+                error: stored - value,
+            });
+        }
+    }
+    
+    for (i, child) in element.children.iter().flatten().enumerate() {
+        let len = path.len();
+        path.push_str(&format!("/__children/{}", i));
+        collect_precision_warnings(child, path, out);
+        path.truncate(len);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,82 @@
+mod common;
+
+use std::ops::Range;
+
+use cairn::{decode_map, decode_map_borrowed, decode_map_sections, DecodeError, ValueRef};
+use common::fixture;
+
+#[test]
+fn borrowed_decode_matches_owned_decode() {
+    for name in ["synthetic/intro.bin", "synthetic/garden.bin"] {
+        let bytes = std::fs::read(fixture(name)).unwrap();
+        let root = decode_map_borrowed(&bytes).unwrap();
+
+        assert_eq!(root.to_element(), decode_map(fixture(name)).unwrap());
+    }
+}
+
+#[test]
+fn borrowed_strings_point_into_the_input() {
+    let bytes = std::fs::read(fixture("synthetic/garden.bin")).unwrap();
+    let root = decode_map_borrowed(&bytes).unwrap();
+    let input: Range<*const u8> = bytes.as_ptr_range();
+
+    let room = &root.find_child("levels").unwrap().children[0];
+    let name = room.attribute("name").and_then(|value| value.as_str()).unwrap();
+
+    assert_eq!(name, "lvl_garden-01");
+    assert!(input.contains(&name.as_ptr()));
+    assert!(input.contains(&room.name.as_ptr()));
+    assert_eq!(root.attribute("package"), Some(ValueRef::Str("ExampleMod/1-Garden")));
+    assert_eq!(room.attribute("width"), Some(ValueRef::Int(40000)));
+
+    let error = decode_map_borrowed(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    // Offsets point at the start of the field that was cut short, the last child count
+    assert_eq!(DecodeError::from_io(&error).unwrap().offset(), bytes.len() as u64 - 2);
+}
+
+#[test]
+fn sections_hold_the_exact_bytes_of_each_room() {
+    let bytes = std::fs::read(fixture("synthetic/intro.bin")).unwrap();
+    let sections = decode_map_sections(&bytes).unwrap();
+    let input: Range<*const u8> = bytes.as_ptr_range();
+
+    assert_eq!(*sections.root(), decode_map_borrowed(&bytes).unwrap());
+
+    let levels: Vec<_> = sections.levels().collect();
+    assert_eq!(levels.len(), sections.root().find_child("levels").unwrap().children.len());
+    assert!(levels.len() >= 2);
+
+    // Rooms are stored back to back, each slice ending where the next starts
+    for pair in levels.windows(2) {
+        assert!(input.contains(&pair[0].1.as_ptr()));
+        assert_eq!(pair[0].1.as_ptr_range().end, pair[1].1.as_ptr());
+    }
+
+    let (first, first_raw) = levels[0];
+    let name = first.attribute("name").and_then(|value| value.as_str()).unwrap();
+    assert_eq!(sections.raw_level(name), Some(first_raw));
+    assert_eq!(sections.raw_level("no such room"), None);
+}
+
+#[test]
+fn swapping_raw_rooms_swaps_the_decoded_rooms() {
+    let bytes = std::fs::read(fixture("synthetic/intro.bin")).unwrap();
+    let sections = decode_map_sections(&bytes).unwrap();
+    let levels: Vec<_> = sections.levels().collect();
+    let (first, first_raw) = levels[0];
+    let (second, second_raw) = levels[1];
+
+    let start = first_raw.as_ptr() as usize - bytes.as_ptr() as usize;
+    let end = start + first_raw.len() + second_raw.len();
+    let mut spliced = bytes[..start].to_vec();
+    spliced.extend_from_slice(second_raw);
+    spliced.extend_from_slice(first_raw);
+    spliced.extend_from_slice(&bytes[end..]);
+
+    let swapped = decode_map_borrowed(&spliced).unwrap();
+    let rooms = &swapped.find_child("levels").unwrap().children;
+    assert_eq!(&rooms[0], second);
+    assert_eq!(&rooms[1], first);
+}
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use cairn::{canonicalize_json, decode_map, encode_map_to_vec, to_canonical_json, Decoder, JsonLoader};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cairn-{}-{}.json", name, std::process::id()))
+}
+
+const HAND_EDITED: &str = r#"{"__children": [{"y": 8, "x": 0.10000000149011612, "__name": "levels", "scale": 2.0}],
+    "package": "canon", "__name": "Map", "__comment": "keep me"}"#;
+
+#[test]
+fn canonical_form_orders_keys_and_floats() {
+    let map = JsonLoader::new().load_from_reader(HAND_EDITED.as_bytes()).unwrap().map;
+
+    let expected = r#"{
+  "__name": "Map",
+  "__comment": "keep me",
+  "package": "canon",
+  "__children": [
+    {
+      "__name": "levels",
+      "scale": 2.0,
+      "x": 0.1,
+      "y": 8
+    }
+  ]
+}"#;
+    assert_eq!(to_canonical_json(&map), expected);
+}
+
+#[test]
+fn canonicalize_is_idempotent() {
+    let path = temp_path("canonicalize");
+    std::fs::write(&path, HAND_EDITED).unwrap();
+
+    assert!(canonicalize_json(&path).unwrap());
+    let first = std::fs::read(&path).unwrap();
+
+    assert!(!canonicalize_json(&path).unwrap());
+    let second = std::fs::read(&path).unwrap();
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(first, second);
+}
+
+#[test]
+fn canonical_json_loads_back_to_the_same_map() {
+    let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/synthetic/garden.bin");
+    let map = decode_map(fixture).unwrap();
+
+    let canonical = to_canonical_json(&map);
+    let loaded = JsonLoader::new().load_from_reader(canonical.as_bytes()).unwrap().map;
+
+    // Floats only lose digits the binary format does not store
+    let bytes = encode_map_to_vec(&loaded).unwrap();
+    assert_eq!(Decoder::new().decode_from_reader(&bytes[..]).unwrap(), map);
+    assert_eq!(to_canonical_json(&loaded), canonical);
+}
\ No newline at end of file
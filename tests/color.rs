@@ -0,0 +1,39 @@
+use cairn::{Color, DecodedElement};
+
+#[test]
+fn parses_rgb_and_rgba_hex() {
+    assert_eq!(Color::from_attr("ff8000"), Some(Color::rgb(255, 128, 0)));
+    assert_eq!(Color::from_attr("ff800040"), Some(Color::rgba(255, 128, 0, 64)));
+    assert_eq!(Color::from_attr("FF80aB"), Some(Color::rgb(255, 128, 171)));
+    assert_eq!(Color::from_attr("#00FF00CC"), Some(Color::rgba(0, 255, 0, 204)));
+
+    for invalid in ["", "fff", "ff80000", "gg0000", "ff00ff0", "ff 000", "+f0000", "ff0000ff00", "é0000"] {
+        assert_eq!(Color::from_attr(invalid), None, "{:?}", invalid);
+    }
+}
+
+#[test]
+fn formats_as_lowercase_hex() {
+    assert_eq!(Color::rgb(255, 128, 171).to_attr(), "ff80ab");
+    assert_eq!(Color::rgba(0, 255, 0, 204).to_attr(), "00ff00cc");
+    assert_eq!(Color::rgba(1, 2, 3, 255).to_attr(), "010203");
+    assert_eq!(Color::from_attr("ABCDEF").unwrap().to_attr(), "abcdef");
+}
+
+#[test]
+fn element_color_accessors() {
+    let mut spinner = DecodedElement::new("spinner");
+    spinner.set_attribute("tint", "7FFFD4");
+    spinner.set_attribute("broken", "not a color");
+    spinner.set_attribute("number", 16);
+    spinner.mark_clean();
+
+    assert_eq!(spinner.get_color("tint"), Some(Color::rgb(127, 255, 212)));
+    assert_eq!(spinner.get_color("broken"), None);
+    assert_eq!(spinner.get_color("number"), None);
+    assert_eq!(spinner.get_color("missing"), None);
+
+    spinner.set_color("tint", Color::rgba(16, 32, 48, 128));
+    assert_eq!(spinner.get_str("tint"), Some("10203080"));
+    assert!(spinner.is_dirty());
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+//! Helpers shared by the integration tests
+//!
+//! Each test file compiles this module on its own and uses only part of it.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use cairn::DecodedElement;
+
+/// Path of a file under `tests/fixtures`
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+/// An empty `level` element named `name`
+pub fn room(name: &str) -> DecodedElement {
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", name);
+    room
+}
+
+/// A `Map` root whose `levels` holds `rooms`, without a package
+pub fn map_with_rooms(rooms: impl IntoIterator<Item = DecodedElement>) -> DecodedElement {
+    let mut levels = DecodedElement::new("levels");
+    for room in rooms {
+        levels.add_child(room);
+    }
+
+    let mut map = DecodedElement::new("Map");
+    map.add_child(levels);
+    map
+}
\ No newline at end of file
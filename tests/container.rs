@@ -0,0 +1,123 @@
+mod common;
+
+use std::io::Read;
+
+use cairn::{decode_map, decode_map_from_reader, decode_map_from_zip, unwrap_gzip, MAX_DECOMPRESSED_SIZE};
+use common::fixture;
+
+#[test]
+fn gzip_layers_are_detected_by_content() {
+    // Gzip inside gzip, with an extension that says neither
+    let bytes = unwrap_gzip(std::fs::read(fixture("containers/garden.dat")).unwrap()).unwrap();
+
+    assert_eq!(bytes, std::fs::read(fixture("synthetic/garden.bin")).unwrap());
+
+    let plain = std::fs::read(fixture("synthetic/intro.bin")).unwrap();
+    assert_eq!(unwrap_gzip(plain.clone()).unwrap(), plain);
+}
+
+#[test]
+fn zip_entries_are_unwrapped_transparently() {
+    let zip = fixture("containers/layered.zip");
+
+    // Skips the readme and finds the deflated .bin.gz
+    let first = decode_map_from_zip(&zip, None).unwrap();
+    assert_eq!(first, decode_map(fixture("synthetic/garden.bin")).unwrap());
+
+    // A stored gzip entry named .txt
+    let named = decode_map_from_zip(&zip, Some("Maps/intro.txt")).unwrap();
+    assert_eq!(named, decode_map(fixture("synthetic/intro.bin")).unwrap());
+
+    let missing = decode_map_from_zip(&zip, Some("Maps/other.bin")).unwrap_err();
+    assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+
+    let not_zip = decode_map_from_zip(fixture("synthetic/intro.bin"), None).unwrap_err();
+    assert!(not_zip.to_string().contains("Not a zip archive"));
+}
+
+#[test]
+fn corrupt_gzip_is_an_error() {
+    let mut bytes = std::fs::read(fixture("containers/garden.dat")).unwrap();
+    let len = bytes.len();
+    bytes[len - 6] ^= 0xff;
+
+    assert!(unwrap_gzip(bytes).is_err());
+}
+
+/// Gzip whose single fixed Huffman block repeats one byte `copies` times
+/// 258 bytes at a time, with a made-up trailer
+fn gzip_bomb(copies: usize) -> Vec<u8> {
+    // Huffman codes are stored most significant bit first
+    fn push_code(bits: &mut Vec<bool>, code: u32, length: u32) {
+        bits.extend((0..length).rev().map(|i| code >> i & 1 == 1));
+    }
+
+    let mut bits = Vec::new();
+    bits.extend([true, true, false]); // last block, fixed codes
+    push_code(&mut bits, 0x30, 8); // literal 0
+    for _ in 0..copies {
+        push_code(&mut bits, 0xc5, 8); // length 258
+        push_code(&mut bits, 0, 5); // distance 1
+    }
+    push_code(&mut bits, 0, 7); // end of block
+
+    let mut bytes = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0];
+    bytes.extend(bits.chunks(8).map(|byte| byte.iter().rev().fold(0u8, |acc, &bit| acc << 1 | bit as u8)));
+    bytes.extend_from_slice(&[0; 8]);
+    bytes
+}
+
+#[test]
+fn decompressed_size_is_capped() {
+    // A small stream under the limit gets as far as the made-up trailer
+    let small = unwrap_gzip(gzip_bomb(4)).unwrap_err();
+    assert!(small.to_string().contains("checksum"), "{}", small);
+
+    let bomb = gzip_bomb(MAX_DECOMPRESSED_SIZE / 258 + 1);
+    assert!(bomb.len() < 2 * 1024 * 1024);
+
+    let error = unwrap_gzip(bomb).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(error.to_string(), format!("Decompressed data exceeds the limit of {} bytes", MAX_DECOMPRESSED_SIZE));
+}
+
+/// Hands out one byte per read, like a slow network stream
+struct Trickle(Vec<u8>, usize);
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(&byte) = self.0.get(self.1) else {
+            return Ok(0);
+        };
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        buf[0] = byte;
+        self.1 += 1;
+        Ok(1)
+    }
+}
+
+#[test]
+fn reader_decoding_handles_short_reads_and_gzip() {
+    let plain = std::fs::read(fixture("synthetic/intro.bin")).unwrap();
+    let decoded = decode_map_from_reader(Trickle(plain, 0)).unwrap();
+    assert_eq!(decoded, decode_map(fixture("synthetic/intro.bin")).unwrap());
+
+    let gzipped = std::fs::read(fixture("containers/garden.dat")).unwrap();
+    let decoded = decode_map_from_reader(Trickle(gzipped, 0)).unwrap();
+    assert_eq!(decoded, decode_map(fixture("synthetic/garden.bin")).unwrap());
+
+    assert!(decode_map_from_reader(Trickle(vec![0x1f], 0)).is_err());
+}
+
+#[test]
+fn reader_decoding_caps_gzip_input_and_output() {
+    // An endless stream after the gzip magic is not read to the end
+    let endless = [0x1f, 0x8b].chain(std::io::repeat(0));
+    let error = decode_map_from_reader(endless).unwrap_err();
+    assert_eq!(error.to_string(), format!("Compressed data exceeds the limit of {} bytes", MAX_DECOMPRESSED_SIZE));
+
+    let error = decode_map_from_reader(&gzip_bomb(MAX_DECOMPRESSED_SIZE / 258 + 1)[..]).unwrap_err();
+    assert_eq!(error.to_string(), format!("Decompressed data exceeds the limit of {} bytes", MAX_DECOMPRESSED_SIZE));
+}
\ No newline at end of file
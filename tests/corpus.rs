@@ -0,0 +1,82 @@
+//! Round-trip checks over a corpus of binary maps
+//!
+//! Every `.bin` file under `tests/fixtures` is decoded, re-encoded and decoded
+//! again. The checked-in maps are small synthetic ones, see
+//! `tests/fixtures/README.md`. Set `CAIRN_FIXTURES` to run the same checks
+//! over another directory, such as a local copy of the game's `Maps` folder
+//! in `vanilla/` and mod maps in `modded/`. Missing directories are skipped
+//! so the suite passes without any fixtures.
+
+use std::path::{Path, PathBuf};
+
+use cairn::{decode_map, encode_map_to_vec, Decoder};
+
+fn fixture_root() -> PathBuf {
+    match std::env::var_os("CAIRN_FIXTURES") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures"),
+    }
+}
+
+fn collect_maps(dir: &Path, maps: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_maps(&path, maps);
+        } else if path.extension().is_some_and(|ext| ext == "bin") {
+            maps.push(path);
+        }
+    }
+}
+
+fn maps_in(subdir: &str) -> Vec<PathBuf> {
+    let mut maps = Vec::new();
+    collect_maps(&fixture_root().join(subdir), &mut maps);
+    maps.sort();
+
+    if maps.is_empty() {
+        eprintln!("no fixtures in {}, skipping", fixture_root().join(subdir).display());
+    }
+    maps
+}
+
+#[test]
+fn corpus_round_trips() {
+    for path in maps_in("") {
+        let map = decode_map(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let bytes = encode_map_to_vec(&map).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let decoded = Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+
+        assert!(decoded.semantically_eq(&map), "{} changed after a round trip", path.display());
+    }
+}
+
+#[test]
+fn synthetic_maps_decode_with_rooms() {
+    for path in maps_in("synthetic") {
+        let map = decode_map(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let rooms = map.find_child("levels").and_then(|levels| levels.children.as_ref());
+        assert!(rooms.is_some_and(|rooms| !rooms.is_empty()), "{} has no rooms", path.display());
+    }
+}
+
+#[test]
+fn vanilla_maps_decode_with_rooms() {
+    for path in maps_in("vanilla") {
+        let map = decode_map(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let rooms = map.find_child("levels").and_then(|levels| levels.children.as_ref());
+        assert!(rooms.is_some_and(|rooms| !rooms.is_empty()), "{} has no rooms", path.display());
+    }
+}
+
+#[test]
+fn modded_maps_decode() {
+    for path in maps_in("modded") {
+        let map = decode_map(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert!(map.find_child("levels").is_some(), "{} has no levels", path.display());
+    }
+}
\ No newline at end of file
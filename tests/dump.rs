@@ -0,0 +1,34 @@
+use cairn::{dump_structure, encode_map_to_vec, DecodedElement};
+
+#[test]
+fn dump_annotates_each_field() {
+    let mut level = DecodedElement::new("level");
+    level.set_attribute("x", 300);
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "dump");
+    map.add_child(level);
+
+    let bytes = encode_map_to_vec(&map).unwrap();
+    let mut out = Vec::new();
+    dump_structure(&bytes, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+
+    assert!(text.starts_with("00000000  0b 43 45 4c"));
+    assert!(text.contains("header \"CELESTE MAP\""));
+    assert!(text.contains("  element level (#"));
+    assert!(text.contains(") = 300 (i16)"));
+
+    let mut out = Vec::new();
+    assert!(dump_structure(&bytes[..bytes.len() - 1], &mut out).is_err());
+    assert!(String::from_utf8(out).unwrap().contains("element level"));
+}
+
+#[test]
+fn corrupt_header_lengths_are_errors() {
+    // A length prefix longer than five bytes, and one far past the end
+    for bytes in [&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01][..], &[0xff, 0xff, 0xff, 0xff, 0x0f]] {
+        let mut out = Vec::new();
+        assert!(dump_structure(bytes, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,252 @@
+mod common;
+
+use cairn::{DecodedElement, SemanticKey, TypedValue};
+use common::map_with_rooms;
+use serde_json::json;
+
+fn room(name: &str, entity_x: i64) -> DecodedElement {
+    let mut entity = DecodedElement::new("spinner");
+    entity.set_attribute("x", entity_x);
+
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(entity);
+
+    let mut room = common::room(name);
+    room.add_child(entities);
+    room
+}
+
+fn sample_map() -> DecodedElement {
+    map_with_rooms([room("lvl_1", 10), room("lvl_3", 20)])
+}
+
+#[test]
+fn visit_mut_with_path_targets_one_room() {
+    let mut map = sample_map();
+
+    map.visit_mut_with_path(&mut |path, element| {
+        if element.name == "spinner" && path.contains(&"lvl_3") {
+            assert_eq!(path, ["Map", "levels", "lvl_3", "entities"]);
+            element.set_attribute("x", 100);
+        }
+    });
+
+    let xs: Vec<_> = map.find_child("levels").unwrap().children.iter().flatten()
+        .map(|room| room.find_child("entities").unwrap().children.as_ref().unwrap()[0].attributes["x"].clone())
+        .collect();
+
+    assert_eq!(xs, [json!(10), json!(100)]);
+}
+
+#[test]
+fn get_bool_lenient_accepts_strings() {
+    let mut element = DecodedElement::new("spinner");
+    element.set_attribute("real", true);
+    element.set_attribute("text", "False");
+    element.set_attribute("other", "yes");
+
+    assert_eq!(element.get_bool_lenient("real"), Some(true));
+    assert_eq!(element.get_bool_lenient("text"), Some(false));
+    assert_eq!(element.get_bool_lenient("other"), None);
+    assert_eq!(element.get_bool_lenient("missing"), None);
+    assert_eq!(element.attributes["text"], json!("False"));
+}
+
+#[test]
+fn room_layer_iterators() {
+    let mut room = room("a-00", 8);
+    let mut fg = DecodedElement::new("fgdecals");
+    fg.add_child(DecodedElement::new("decal"));
+    fg.add_child(DecodedElement::new("decal"));
+    room.add_child(fg);
+
+    assert_eq!(room.entities().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["spinner"]);
+    assert_eq!(room.fg_decals().count(), 2);
+    assert_eq!(room.triggers().count(), 0);
+    assert_eq!(room.bg_decals().count(), 0);
+}
+
+#[test]
+fn semantic_comparison_with_float_tolerance() {
+    let mut a = DecodedElement::new("parallax");
+    a.set_attribute("alpha", 1.0);
+    a.set_attribute("x", 8);
+    let mut b = DecodedElement::new("parallax");
+    b.set_attribute("alpha", 1.0000001);
+    b.set_attribute("x", 8.0);
+
+    assert!(!a.semantically_eq(&b));
+    assert!(a.semantically_eq_with_tolerance(&b, 1e-6));
+    assert!(!a.semantically_eq_with_tolerance(&b, 1e-9));
+}
+
+#[test]
+fn element_kind_from_name() {
+    use cairn::ElementKind;
+
+    assert_eq!(DecodedElement::new("spinner").kind(), ElementKind::Spinner);
+    assert_eq!(DecodedElement::new("levels").kind(), ElementKind::Levels);
+    assert_eq!(
+        DecodedElement::new("MaxHelpingHand/Vine").kind(),
+        ElementKind::Other("MaxHelpingHand/Vine".into())
+    );
+    assert_eq!(ElementKind::FgDecals.name(), "fgdecals");
+}
+
+#[test]
+fn clone_with_new_ids_only_replaces_ids() {
+    let mut entities = DecodedElement::new("entities");
+    for (id, target) in [(4, None), (9, Some(4))] {
+        let mut entity = DecodedElement::new("switchGate");
+        entity.set_attribute("id", id);
+        if let Some(target) = target {
+            entity.set_attribute("targetId", target);
+        }
+        entities.add_child(entity);
+    }
+    entities.add_child(DecodedElement::new("decal"));
+    entities.mark_clean();
+
+    let mut next = 100;
+    let copy = entities.clone_with_new_ids(&mut || {
+        next += 1;
+        next
+    });
+
+    let ids: Vec<_> = copy.descendants().map(|e| e.get_i64("id")).collect();
+    assert_eq!(ids, [Some(101), Some(102), None]);
+    assert_eq!(copy.descendants().nth(1).unwrap().get_i64("targetId"), Some(4));
+
+    let originals: Vec<_> = entities.descendants().map(|e| e.get_i64("id")).collect();
+    assert_eq!(originals, [Some(4), Some(9), None]);
+    assert!(!entities.is_dirty());
+}
+
+#[test]
+fn typed_setters_pick_the_encoded_type() {
+    let mut entity = DecodedElement::new("spinner");
+    entity.set_int("id", 70000);
+    entity.set_float("x", 16.0);
+    entity.set_bool("attachToSolid", true);
+    entity.set_string("color", "32");
+
+    let mut map = DecodedElement::new("Map");
+    map.set_string("package", "typed");
+    map.add_child(entity);
+
+    let bytes = cairn::encode_map_to_vec(&map).unwrap();
+    let decoded = cairn::Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+    let entity = decoded.find_child("spinner").unwrap();
+
+    assert_eq!(entity.attributes["id"], json!(70000));
+    assert!(entity.attributes["x"].is_f64());
+    assert_eq!(entity.attributes["x"], json!(16.0));
+    assert_eq!(entity.attributes["attachToSolid"], json!(true));
+    assert_eq!(entity.attributes["color"], json!("32"));
+}
+
+#[test]
+fn json_diff_lists_only_changes() {
+    let base = sample_map();
+    assert_eq!(base.to_json_diff_against(&base), serde_json::Value::Null);
+
+    let mut edited = base.clone();
+    let levels = &mut edited.children.as_mut().unwrap()[0];
+    levels.children.as_mut().unwrap().insert(0, room("lvl_0", 5));
+    let rooms = levels.children.as_mut().unwrap();
+    rooms[2].set_attribute("music", "event:/music/lvl1");
+    rooms[2].children.as_mut().unwrap()[0].children.as_mut().unwrap()[0].set_attribute("x", 15);
+    rooms[1].attributes.remove("name");
+    rooms[1].set_attribute("name", "lvl_1");
+
+    let diff = edited.to_json_diff_against(&base);
+    let changes = &diff["__children"][0]["__children"];
+    assert_eq!(changes.as_array().unwrap().len(), 2);
+
+    assert_eq!(changes[0]["__index"], json!(0));
+    assert_eq!(changes[0]["__added"], json!(true));
+    assert_eq!(changes[0]["name"], json!("lvl_0"));
+
+    assert_eq!(changes[1], json!({
+        "__name": "level",
+        "__index": 2,
+        "music": "event:/music/lvl1",
+        "__children": [{
+            "__name": "entities",
+            "__index": 0,
+            "__children": [{"__name": "spinner", "__index": 0, "x": 15}],
+        }],
+    }));
+
+    let removed = base.to_json_diff_against(&edited);
+    let changes = &removed["__children"][0]["__children"];
+    assert_eq!(changes[0]["music"], serde_json::Value::Null);
+    assert_eq!(changes[1], json!({"__name": "level", "__index": 0, "__removed": true}));
+}
+
+#[test]
+fn elements_deduplicate_in_hash_sets() {
+    use std::collections::HashSet;
+    use std::hash::{BuildHasher, RandomState};
+
+    let decal = |texture: &str, x: i64, scale: f64| {
+        let mut decal = DecodedElement::new("decal");
+        decal.set_attribute("texture", texture);
+        decal.set_attribute("x", x);
+        decal.set_attribute("scaleX", scale);
+        decal
+    };
+
+    let mut reordered = DecodedElement::new("decal");
+    reordered.set_attribute("scaleX", 1.0);
+    reordered.set_attribute("x", 8);
+    reordered.set_attribute("texture", "flag");
+
+    let decals = [decal("flag", 8, 1.0), reordered, decal("flag", 16, 1.0), decal("flag", 8, -1.0)];
+    let unique: HashSet<_> = decals.iter().cloned().collect();
+    assert_eq!(unique.len(), 3);
+
+    // Hashes follow semantically_eq, while == also sees editor attributes
+    let hasher = RandomState::new();
+    let mut commented = decals[0].clone();
+    commented.set_comment("keep");
+    commented.children = Some(Vec::new());
+    assert!(commented.semantically_eq(&decals[0]));
+    assert_eq!(hasher.hash_one(&commented), hasher.hash_one(&decals[0]));
+    assert_ne!(commented, decals[0]);
+
+    // SemanticKey compares the same way it hashes
+    let mut nulled = decals[2].clone();
+    nulled.set_attribute("flip", serde_json::Value::Null);
+    let keys = [commented, decals[0].clone(), nulled, decals[2].clone(), decals[3].clone()];
+    let unique: HashSet<_> = keys.into_iter().map(SemanticKey).collect();
+    assert_eq!(unique.len(), 3);
+    assert!(unique.contains(&SemanticKey(decals[1].clone())));
+    assert!(!unique.contains(&SemanticKey(decal("flag", 8, 2.0))));
+}
+
+#[test]
+fn sorted_and_typed_attributes_of_a_mixed_element() {
+    let mut booster = DecodedElement::new("booster");
+    booster.set_attribute("y", 16.5);
+    booster.set_attribute("red", true);
+    booster.set_attribute("x", -8);
+    booster.set_attribute("sprite", "boosterRed");
+    booster.set_attribute("nodes", json!([1, 2]));
+    booster.set_attribute("ch", serde_json::Value::Null);
+    booster.set_comment("check the speed");
+
+    let keys: Vec<_> = booster.sorted_attributes().map(|(key, _)| key).collect();
+    assert_eq!(keys, ["ch", "nodes", "red", "sprite", "x", "y"]);
+
+    let typed: Vec<_> = booster.typed_attributes().collect();
+    assert_eq!(
+        typed,
+        [
+            ("red", TypedValue::Bool(true)),
+            ("sprite", TypedValue::Str("boosterRed")),
+            ("x", TypedValue::Int(-8)),
+            ("y", TypedValue::Float(16.5)),
+        ]
+    );
+}
\ No newline at end of file
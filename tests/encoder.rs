@@ -0,0 +1,455 @@
+mod common;
+
+use cairn::{
+    decode_map, encode_map_to_vec, encoded_size, room_size_report, type_roundtrip_report, DecodedElement, Decoder,
+    Encoder, FieldType, FieldTypes,
+};
+use common::map_with_rooms;
+use serde_json::{json, Value};
+
+fn read_string(bytes: &[u8], pos: &mut usize) -> String {
+    let len = bytes[*pos] as usize;
+    let s = String::from_utf8(bytes[*pos + 1..*pos + 1 + len].to_vec()).unwrap();
+    *pos += 1 + len;
+    s
+}
+
+/// Returns the lookup table of an encoded map
+fn lookup_table(bytes: &[u8]) -> Vec<String> {
+    let mut pos = 0;
+    read_string(bytes, &mut pos);
+    read_string(bytes, &mut pos);
+
+    let count = u16::from_le_bytes([bytes[pos], bytes[pos + 1]]);
+    pos += 2;
+
+    (0..count).map(|_| read_string(bytes, &mut pos)).collect()
+}
+
+fn sample_map() -> DecodedElement {
+    let mut map = map_with_rooms([]);
+    map.attributes.insert("package".into(), Value::String("seeded".into()));
+    map
+}
+
+fn round_trip(encoder: &Encoder, map: &DecodedElement, name: &str) -> DecodedElement {
+    let path = std::env::temp_dir().join(format!("cairn-{}-{}.bin", name, std::process::id()));
+    encoder.encode(map, &path).unwrap();
+    let decoded = decode_map(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    decoded
+}
+
+#[test]
+fn seed_lookup_keeps_indices_and_appends_sorted() {
+    let seed = vec!["unused".to_string(), "Map".to_string()];
+    let mut bytes = Vec::new();
+    Encoder::new()
+        .with_seed_lookup(seed)
+        .encode_to_writer(&sample_map(), &mut bytes)
+        .unwrap();
+
+    assert_eq!(lookup_table(&bytes), ["unused", "Map", "levels", "package", "seeded"]);
+}
+
+#[test]
+fn field_types_force_numeric_encoding() {
+    let mut map = sample_map();
+    map.set_attribute("width", json!(16.0));
+    map.set_attribute("scrollx", json!(1));
+    map.set_attribute("custom", json!(2));
+
+    let mut types = FieldTypes::builtin();
+    types.insert("custom", FieldType::Float);
+
+    let decoded = round_trip(&Encoder::new().field_types(types), &map, "field-types");
+
+    assert_eq!(decoded.attributes["width"], json!(16));
+    assert_eq!(decoded.attributes["scrollx"], json!(1.0));
+    assert_eq!(decoded.attributes["custom"], json!(2.0));
+}
+
+#[test]
+fn integer_field_rejects_fractions() {
+    let mut map = sample_map();
+    map.set_attribute("id", json!(1.5));
+
+    let result = Encoder::new()
+        .field_types(FieldTypes::builtin())
+        .encode_to_writer(&map, &mut Vec::new());
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn canonical_child_order_sorts_known_level_children() {
+    let mut level = DecodedElement::new("level");
+    for name in ["entities", "customLayer", "solids", "bg"] {
+        level.add_child(DecodedElement::new(name));
+    }
+
+    let mut map = sample_map();
+    map.add_child(level);
+
+    let decoded = round_trip(&Encoder::new().canonical_child_order(true), &map, "child-order");
+    let names: Vec<_> = decoded.find_child("level").unwrap().children.iter().flatten()
+        .map(|child| child.name.as_str())
+        .collect();
+
+    assert_eq!(names, ["solids", "customLayer", "bg", "entities"]);
+}
+
+#[test]
+fn strict_nulls_rejects_null_attributes() {
+    let mut map = sample_map();
+    map.set_attribute("placeholder", Value::Null);
+
+    assert!(Encoder::new().encode_to_writer(&map, &mut Vec::new()).is_ok());
+
+    let error = Encoder::new().strict_nulls(true).encode_to_writer(&map, &mut Vec::new()).unwrap_err();
+    assert!(error.to_string().contains("placeholder"));
+}
+
+#[test]
+fn encode_to_vec_allocates_exact_size() {
+    let mut map = sample_map();
+    map.set_attribute("tiles", "0".repeat(5000));
+
+    let bytes = encode_map_to_vec(&map).unwrap();
+
+    assert_eq!(bytes.len() as u64, encoded_size(&map).unwrap());
+    assert_eq!(bytes.capacity(), bytes.len());
+}
+
+#[test]
+fn atomic_encode_keeps_original_on_failure() {
+    let dir = std::env::temp_dir().join(format!("cairn-atomic-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("map.bin");
+
+    let encoder = Encoder::new().atomic(true);
+    encoder.encode(&sample_map(), &path).unwrap();
+    let original = std::fs::read(&path).unwrap();
+
+    let mut broken = sample_map();
+    broken.attributes.remove("package");
+    assert!(encoder.encode(&broken, &path).is_err());
+
+    let files = std::fs::read_dir(&dir).unwrap().count();
+    let kept = std::fs::read(&path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(kept, original);
+    assert_eq!(files, 1);
+}
+
+/// Ten 40x23 rooms of mostly air with solid floors and walls
+fn tile_heavy_map() -> DecodedElement {
+    let mut levels = DecodedElement::new("levels");
+
+    for i in 0..10 {
+        let mut rows = vec![format!("1{}1", "0".repeat(38)); 20];
+        rows.extend(vec!["1".repeat(40); 3]);
+
+        let mut solids = DecodedElement::new("solids");
+        solids.set_attribute("innerText", rows.join("\n"));
+        let mut room = DecodedElement::new("level");
+        room.set_attribute("name", format!("room-{}", i));
+        room.add_child(solids);
+        levels.add_child(room);
+    }
+
+    let mut map = sample_map();
+    map.children = None;
+    map.add_child(levels);
+    map
+}
+
+#[test]
+fn run_length_encoding_can_be_tuned() {
+    let map = tile_heavy_map();
+
+    let rle = Encoder::new().encoded_size(&map).unwrap();
+    let raw = Encoder::new().run_length_encoding(false).encoded_size(&map).unwrap();
+    let strict = Encoder::new().rle_min_savings(10_000).encoded_size(&map).unwrap();
+
+    // Run-length encoding cuts this map to about a fifth of its raw size
+    assert_eq!((rle, raw), (2060, 9780));
+    assert_eq!(strict, raw);
+
+    // Enabling keeps a custom threshold, and restores the default after disabling
+    let kept = Encoder::new().rle_min_savings(10_000).run_length_encoding(true).encoded_size(&map).unwrap();
+    let restored = Encoder::new().run_length_encoding(false).run_length_encoding(true).encoded_size(&map).unwrap();
+    assert_eq!(kept, raw);
+    assert_eq!(restored, rle);
+
+    let bytes = Encoder::new().encode_to_vec(&map).unwrap();
+    let decoded = cairn::Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+    assert!(decoded.semantically_eq(&map));
+}
+
+#[test]
+fn preserve_underscore_attrs_keeps_editor_metadata() {
+    let mut map = sample_map();
+    map.set_comment("keep me");
+
+    let dropped = Encoder::new().encode_to_vec(&map).unwrap();
+    let dropped = cairn::Decoder::new().decode_from_reader(&dropped[..]).unwrap();
+    assert_eq!(dropped.comment(), None);
+
+    let kept = Encoder::new().preserve_underscore_attrs(true).encode_to_vec(&map).unwrap();
+    let kept = cairn::Decoder::new().decode_from_reader(&kept[..]).unwrap();
+    assert_eq!(kept.comment(), Some("keep me"));
+}
+
+#[test]
+fn too_many_children_is_an_error() {
+    let mut decals = DecodedElement::new("fgdecals");
+    decals.children = Some(vec![DecodedElement::new("decal"); 70_000]);
+    let mut map = sample_map();
+    map.add_child(decals);
+
+    let error = Encoder::new().encode_to_vec(&map).unwrap_err();
+
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("Element fgdecals has 70000 children"));
+}
+
+#[test]
+fn numbers_beyond_f32_name_the_attribute() {
+    let mut map = sample_map();
+    map.set_attribute("scale", json!(3.5e38));
+
+    let error = encode_map_to_vec(&map).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Element Map: Attribute scale value 3.5e38 is outside the 32-bit float range"
+    );
+}
+
+#[test]
+fn package_errors_tell_missing_from_wrong_type() {
+    let mut map = sample_map();
+    map.attributes.remove("package");
+
+    let error = encode_map_to_vec(&map).unwrap_err();
+    assert_eq!(error.to_string(), "Missing package attribute");
+
+    map.set_attribute("package", 5);
+
+    let error = encode_map_to_vec(&map).unwrap_err();
+    assert_eq!(error.to_string(), "Package attribute must be a string, found number 5; quote the value");
+}
+
+#[test]
+fn dropped_strings_reports_what_an_edit_saves() {
+    let mut original = sample_map();
+    let mut spinner = DecodedElement::new("spinner");
+    spinner.set_attribute("attachToSolid", false);
+    original.add_child(spinner);
+    original.add_child(DecodedElement::new("spinner"));
+
+    let mut edited = original.clone();
+    edited.remove_child(1);
+
+    let dropped = Encoder::new().dropped_strings(&original, &edited);
+
+    assert_eq!(dropped.strings, ["attachToSolid"]);
+    assert_eq!(dropped.total_len, 13);
+    assert_eq!(dropped.table_bytes, 14);
+
+    let saved = encoded_size(&original).unwrap() - encoded_size(&edited).unwrap();
+    assert!(saved >= dropped.table_bytes);
+    assert_eq!(Encoder::new().dropped_strings(&original, &original), Default::default());
+}
+
+#[test]
+fn custom_magic_round_trips() {
+    let map = sample_map();
+    let bytes = Encoder::new().magic("PROTO MAP").encode_to_vec(&map).unwrap();
+
+    assert_eq!(&bytes[1..10], b"PROTO MAP");
+
+    let decoded = Decoder::new().magic("PROTO MAP").decode_from_reader(&bytes[..]).unwrap();
+    assert_eq!(decoded, map);
+
+    let error = Decoder::new().decode_from_reader(&bytes[..]).unwrap_err();
+    assert!(error.to_string().contains("Invalid Celeste map file"));
+
+    let vanilla = encode_map_to_vec(&map).unwrap();
+    let error = Decoder::new().magic("PROTO MAP").decode_from_reader(&vanilla[..]).unwrap_err();
+    assert!(error.to_string().contains("Invalid header \"CELESTE MAP\", expected \"PROTO MAP\""));
+}
+
+#[test]
+fn type_roundtrip_report_flags_attributes_whose_type_changes() {
+    let mut parallax = DecodedElement::new("parallax");
+    parallax.set_attribute("scrollx", 1);
+    parallax.set_attribute("scrolly", 0.5);
+    parallax.set_attribute("x", 8);
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.set_attribute("width", 320.0);
+    room.set_attribute("seed", 5_000_000_000i64);
+    room.add_child(parallax);
+    let mut map = DecodedElement::new("Map");
+    map.add_child(room);
+
+    let report = type_roundtrip_report(&map);
+    let found: Vec<_> = report.iter().map(|m| (m.element.as_str(), m.key.as_str(), m.encoded_type, m.expected_type)).collect();
+
+    assert_eq!(found, [("level", "seed", 4, 3), ("level", "width", 4, 2), ("parallax", "scrollx", 1, 4)]);
+    assert!(report.iter().all(|m| m.room.as_deref() == Some("a-00")));
+    assert_eq!(report[2].value, json!(1));
+}
+
+#[test]
+fn skip_elements_drops_matching_subtrees() {
+    let mut marker = DecodedElement::new("debugMarker");
+    marker.set_attribute("note", "remove before release");
+    marker.add_child(DecodedElement::new("debugChild"));
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(DecodedElement::new("player"));
+    entities.add_child(marker);
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "release");
+    map.add_child(entities);
+
+    let encoder = Encoder::new().skip_elements(|element| element.name == "debugMarker");
+    let bytes = encoder.encode_to_vec(&map).unwrap();
+    let decoded = Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+
+    let entities = decoded.find_child("entities").unwrap();
+    let names: Vec<_> = entities.children.iter().flatten().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["player"]);
+    for unused in ["debugMarker", "debugChild", "note", "remove before release"] {
+        assert!(!bytes.windows(unused.len()).any(|w| w == unused.as_bytes()), "{} is still encoded", unused);
+    }
+    assert_eq!(encoder.encoded_size(&map).unwrap(), bytes.len() as u64);
+}
+
+#[test]
+fn run_length_encoding_round_trips_high_characters() {
+    let mut map = sample_map();
+    let mut solids = DecodedElement::new("solids");
+    let text = format!("{}\u{80}{}", "\u{e9}".repeat(300), "\u{ff}".repeat(4));
+    solids.set_attribute("innerText", text.as_str());
+    map.add_child(solids);
+
+    let rle = Encoder::new().encoded_size(&map).unwrap();
+    let raw = Encoder::new().run_length_encoding(false).encoded_size(&map).unwrap();
+    assert!(rle < raw);
+
+    let bytes = Encoder::new().encode_to_vec(&map).unwrap();
+    let decoded = Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+    assert_eq!(decoded.find_child("solids").unwrap().get_str("innerText"), Some(text.as_str()));
+
+    // Characters past U+00FF do not fit in a run byte and stay raw strings
+    let mut wide = sample_map();
+    let mut solids = DecodedElement::new("solids");
+    solids.set_attribute("innerText", "\u{100}".repeat(50));
+    wide.add_child(solids);
+    let bytes = Encoder::new().encode_to_vec(&wide).unwrap();
+    let decoded = Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+    assert!(decoded.semantically_eq(&wide));
+}
+
+#[test]
+fn double_precision_round_trips_f64_values() {
+    let mut map = sample_map();
+    let mut entity = DecodedElement::new("customEntity");
+    entity.set_float("speed", 0.1);
+    entity.set_float("half", 0.5);
+    entity.set_int("id", 3);
+
+    let mut vanilla = sample_map();
+    vanilla.add_child(entity.clone());
+    let bytes = Encoder::new().encode_to_vec(&vanilla).unwrap();
+    let decoded = Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+    assert_ne!(decoded.find_child("customEntity").unwrap().get_f64("speed"), Some(0.1));
+
+    // Beyond f32 range, only the double type can store the value at all
+    entity.set_float("far", 1e300);
+    map.add_child(entity);
+    assert!(Encoder::new().encode_to_vec(&map).is_err());
+
+    let bytes = Encoder::new().double_precision(true).encode_to_vec(&map).unwrap();
+    let decoder = Decoder::new().double_precision(true);
+    let decoded = decoder.decode_from_reader(&bytes[..]).unwrap();
+    assert!(decoded.semantically_eq(&map));
+
+    let borrowed = decoder.decode_borrowed(&bytes).unwrap();
+    assert_eq!(borrowed.to_element(), decoded);
+    assert_eq!(decoder.count_elements(&bytes[..]).unwrap(), decoded.element_count());
+
+    let mut flat = cairn::FlatMap::new();
+    flat.decode_with(&bytes[..], &decoder).unwrap();
+    assert_eq!(flat.to_element().unwrap(), decoded);
+
+    // Celeste cannot load such a map, so the default decoders refuse it
+    let error = Decoder::new().decode_from_reader(&bytes[..]).unwrap_err();
+    assert!(error.to_string().contains("Non-vanilla value type 8"));
+    assert!(cairn::decode_map_borrowed(&bytes).is_err());
+    assert!(cairn::count_elements(&bytes[..]).is_err());
+    assert!(flat.decode_from(&bytes[..]).is_err());
+
+    // Values that fit an f32 exactly keep their vanilla encoding
+    let entity = map.children.as_mut().unwrap().last_mut().unwrap();
+    entity.attributes.retain(|key, _| key == "half" || key == "id");
+    assert_eq!(
+        Encoder::new().double_precision(true).encoded_size(&map).unwrap(),
+        Encoder::new().encoded_size(&map).unwrap(),
+    );
+}
+
+#[test]
+fn room_sizes_are_sorted_largest_first() {
+    let room = |name: &str, spinners: usize| {
+        let mut entities = DecodedElement::new("entities");
+        for i in 0..spinners {
+            let mut spinner = DecodedElement::new("spinner");
+            spinner.set_attribute("x", (i * 8) as i64);
+            entities.add_child(spinner);
+        }
+        let mut room = common::room(name);
+        room.add_child(entities);
+        room
+    };
+
+    let mut levels = DecodedElement::new("levels");
+    levels.add_child(room("small", 1));
+    levels.add_child(room("big", 20));
+    levels.add_child(room("medium", 5));
+    // Name index, attribute count and child count only
+    levels.add_child(DecodedElement::new("level"));
+    let mut map = sample_map();
+    map.children = Some(vec![levels]);
+
+    let sizes = room_size_report(&map).unwrap();
+    let names: Vec<_> = sizes.iter().map(|(name, _)| name.as_str()).collect();
+
+    assert_eq!(names, ["big", "medium", "small", ""]);
+    assert_eq!(sizes[3].1, 5);
+    assert!(sizes.iter().map(|(_, size)| size).sum::<u64>() < encoded_size(&map).unwrap());
+}
+
+#[test]
+fn custom_magic_reaches_every_decoder() {
+    let map = sample_map();
+    let bytes = Encoder::new().magic("PROTO MAP").encode_to_vec(&map).unwrap();
+    let decoder = Decoder::new().magic("PROTO MAP");
+
+    assert_eq!(decoder.count_elements(&bytes[..]).unwrap(), map.element_count());
+    assert_eq!(decoder.decode_sections(&bytes[..]).unwrap().count(), map.children.as_ref().unwrap().len());
+    assert_eq!(decoder.index_map(std::io::Cursor::new(&bytes)).unwrap().package(), map.get_str("package").unwrap());
+    assert_eq!(decoder.decode_borrowed(&bytes).unwrap().to_element(), map);
+    assert_eq!(decoder.decode_borrowed_sections(&bytes).unwrap().root().to_element(), map);
+
+    // The free functions expect the standard header
+    assert!(cairn::count_elements(&bytes[..]).is_err());
+    assert!(cairn::decode_sections(&bytes[..]).is_err());
+    assert!(cairn::index_map(std::io::Cursor::new(&bytes)).is_err());
+    assert!(cairn::decode_map_borrowed(&bytes).is_err());
+    assert!(cairn::decode_map_sections(&bytes).is_err());
+}
\ No newline at end of file
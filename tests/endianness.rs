@@ -0,0 +1,63 @@
+//! Pins the little-endian wire format independently of the host byte order
+
+use cairn::{Decoder, DecodedElement, Encoder};
+use serde_json::{json, Value};
+
+/// Encodes a map whose last element is `e` with the single attribute `v`
+fn encode_single_value(value: Value) -> Vec<u8> {
+    let mut child = DecodedElement::new("e");
+    child.set_attribute("v", value);
+
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "p");
+    map.add_child(child);
+
+    let seed = ["Map", "package", "p", "e", "v"].map(String::from).to_vec();
+    let mut bytes = Vec::new();
+    Encoder::new().with_seed_lookup(seed).encode_to_writer(&map, &mut bytes).unwrap();
+    bytes
+}
+
+/// Type code and value bytes of `v`, stripping the trailing child count
+fn value_bytes(value: Value) -> Vec<u8> {
+    let bytes = encode_single_value(value);
+    let end = bytes.len() - 2;
+    assert_eq!(&bytes[end..], [0, 0]);
+
+    // Name index 3 ("e"), one attribute, key index 4 ("v"), then the value
+    let start = bytes[..end].windows(5).rposition(|w| w == [3, 0, 1, 4, 0]).unwrap();
+    bytes[start + 5..end].to_vec()
+}
+
+#[test]
+fn integers_are_little_endian() {
+    assert_eq!(value_bytes(json!(200)), [1, 0xC8]);
+    assert_eq!(value_bytes(json!(258)), [2, 0x02, 0x01]);
+    assert_eq!(value_bytes(json!(-2)), [2, 0xFE, 0xFF]);
+    assert_eq!(value_bytes(json!(70000)), [3, 0x70, 0x11, 0x01, 0x00]);
+    assert_eq!(value_bytes(json!(-70000)), [3, 0x90, 0xEE, 0xFE, 0xFF]);
+}
+
+#[test]
+fn floats_are_little_endian() {
+    assert_eq!(value_bytes(json!(1.5)), [4, 0x00, 0x00, 0xC0, 0x3F]);
+    assert_eq!(value_bytes(json!(-2.0)), [4, 0x00, 0x00, 0x00, 0xC0]);
+}
+
+#[test]
+fn lookup_indices_and_counts_are_little_endian() {
+    let bytes = encode_single_value(json!("p"));
+
+    // "CELESTE MAP" and "p" with their one-byte lengths, then the table size
+    assert_eq!(&bytes[14..16], [5, 0]);
+    // String reference to index 2
+    assert_eq!(value_bytes(json!("p")), [5, 0x02, 0x00]);
+}
+
+#[test]
+fn decodes_little_endian_values() {
+    let bytes = encode_single_value(json!(-70000));
+    let map = Decoder::new().decode_from_reader(bytes.as_slice()).unwrap();
+
+    assert_eq!(map.find_child("e").unwrap().attributes["v"], json!(-70000));
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+mod common;
+
+use cairn::{decode_map, Decoder, Encoder, FlatMap, FlatValue};
+use common::fixture;
+
+#[test]
+fn flat_decode_matches_owned_decode() {
+    let mut flat = FlatMap::new();
+
+    // Reusing one arena for several maps must not leak data between them
+    for name in ["synthetic/intro.bin", "synthetic/garden.bin"] {
+        let bytes = std::fs::read(fixture(name)).unwrap();
+        flat.decode_from(&bytes[..]).unwrap();
+
+        let owned = decode_map(fixture(name)).unwrap();
+        assert_eq!(flat.to_element().unwrap(), owned);
+        assert_eq!(flat.len(), owned.element_count());
+    }
+}
+
+#[test]
+fn flat_elements_are_borrowed_views() {
+    let bytes = std::fs::read(fixture("synthetic/garden.bin")).unwrap();
+    let mut flat = FlatMap::new();
+    flat.decode_from(&bytes[..]).unwrap();
+
+    let root = flat.root().unwrap();
+    let levels = root.children().find(|child| child.name() == "levels").unwrap();
+    let room = levels.children().next().unwrap();
+
+    assert_eq!(flat.package(), "ExampleMod/1-Garden");
+    assert_eq!(room.attribute("name"), Some(FlatValue::Str("lvl_garden-01")));
+    assert_eq!(room.attribute("width"), Some(FlatValue::Int(40000)));
+
+    assert!(flat.decode_from(&bytes[..bytes.len() - 1]).is_err());
+    assert!(flat.is_empty());
+}
+
+#[test]
+fn decode_with_follows_the_decoder_settings() {
+    let map = decode_map(fixture("synthetic/intro.bin")).unwrap();
+    let mut bytes = vec![0xef, 0xbb, 0xbf];
+    bytes.extend(Encoder::new().magic("CAIRN TEST").encode_to_vec(&map).unwrap());
+
+    let decoder = Decoder::new().magic("CAIRN TEST").skip_leading_bytes(true);
+    let mut flat = FlatMap::new();
+    assert!(flat.decode_from(&bytes[..]).is_err());
+    flat.decode_with(&bytes[..], &decoder).unwrap();
+    assert_eq!(flat.to_element().unwrap(), map);
+    assert!(flat.warnings().is_empty());
+
+    // Recovery keeps what the owned decoder keeps and reports the same problems
+    let cut = &bytes[..bytes.len() * 2 / 3];
+    let path = std::env::temp_dir().join(format!("cairn-flat-recover-{}.bin", std::process::id()));
+    std::fs::write(&path, cut).unwrap();
+    let decoder = decoder.recover(true);
+    let report = decoder.decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    flat.decode_with(cut, &decoder).unwrap();
+    assert_eq!(flat.to_element().unwrap(), report.map);
+    assert_eq!(flat.warnings(), report.warnings);
+    assert!(!flat.warnings().is_empty());
+}
\ No newline at end of file
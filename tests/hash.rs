@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use cairn::{content_hash, decode_map, sha256, DecodedElement, Decoder, Encoder, JsonLoader};
+use serde_json::json;
+
+fn load(json: &str) -> DecodedElement {
+    JsonLoader::new().load_from_reader(json.as_bytes()).unwrap().map
+}
+
+fn hex(digest: [u8; 32]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn sha256_matches_known_answers() {
+    // FIPS 180-4 examples; the last one needs a second block for its padding
+    assert_eq!(hex(sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    assert_eq!(hex(sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(
+        hex(sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+    );
+}
+
+#[test]
+fn hash_ignores_attribute_and_lookup_order() {
+    let a = load(r#"{ "__name": "Map", "package": "p", "x": 1, "y": 2.5, "__children": [] }"#);
+    let b = load(r#"{ "__name": "Map", "y": 2.5, "x": 1, "package": "p", "__comment": "editor note" }"#);
+    assert_eq!(content_hash(&a), content_hash(&b));
+
+    let map = decode_map(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/synthetic/garden.bin")).unwrap();
+    let reversed = {
+        let mut lookup: Vec<_> = {
+            let mut seen = std::collections::HashSet::new();
+            map.collect_keys(&mut seen);
+            seen.into_iter().collect()
+        };
+        lookup.sort();
+        lookup.reverse();
+
+        let bytes = Encoder::new().with_seed_lookup(lookup).encode_to_vec(&map).unwrap();
+        Decoder::new().decode_from_reader(&bytes[..]).unwrap()
+    };
+    assert_eq!(content_hash(&reversed), content_hash(&map));
+}
+
+#[test]
+fn hash_reflects_real_changes() {
+    let map = load(r#"{ "__name": "Map", "package": "p", "x": 1, "__children": [{ "__name": "levels" }] }"#);
+    let original = content_hash(&map);
+
+    let mut changed = map.clone();
+    changed.set_attribute("x", json!(1.0));
+    assert_ne!(content_hash(&changed), original);
+
+    let mut reordered = map.clone();
+    reordered.add_child(DecodedElement::new("Style"));
+    let mut swapped = map.clone();
+    swapped.children.as_mut().unwrap().insert(0, DecodedElement::new("Style"));
+    assert_ne!(content_hash(&reordered), content_hash(&swapped));
+
+    // Values move between keys without the byte stream lining up by accident
+    let ab = load(r#"{ "__name": "e", "a": "bc" }"#);
+    let abc = load(r#"{ "__name": "e", "ab": "c" }"#);
+    assert_ne!(content_hash(&ab), content_hash(&abc));
+}
\ No newline at end of file
@@ -0,0 +1,51 @@
+mod common;
+
+use std::fs::File;
+use std::io::{BufReader, Cursor, Seek, SeekFrom};
+
+use cairn::{decode_map, index_map};
+use common::fixture;
+
+#[test]
+fn seeking_back_decodes_single_children() {
+    let owned = decode_map(fixture("synthetic/garden.bin")).unwrap();
+    let mut index = index_map(BufReader::new(File::open(fixture("synthetic/garden.bin")).unwrap())).unwrap();
+
+    assert_eq!(index.package(), "ExampleMod/1-Garden");
+    assert_eq!(index.root().name, "Map");
+
+    // Decode the rooms last to first to exercise seeking backwards
+    let levels = index.root().find_child("levels").unwrap().clone();
+    let expected = owned.find_child("levels").unwrap().children.as_ref().unwrap();
+
+    for (offsets, room) in levels.children.iter().zip(expected).rev() {
+        assert_eq!(index.decode_at(offsets.offset).unwrap(), *room);
+    }
+
+    let package = index.package().to_string();
+    let mut root = index.decode_at(index.root().offset).unwrap();
+    root.set_attribute("package", package);
+    assert_eq!(root, owned);
+}
+
+#[test]
+fn offsets_are_positions_in_the_reader() {
+    let map = std::fs::read(fixture("synthetic/intro.bin")).unwrap();
+    let mut bytes = b"padding".to_vec();
+    bytes.extend_from_slice(&map);
+
+    let mut reader = Cursor::new(bytes);
+    reader.seek(SeekFrom::Start(7)).unwrap();
+    let mut index = index_map(reader).unwrap();
+
+    let root = index.root().clone();
+    assert!(root.offset > 7);
+
+    let owned = decode_map(fixture("synthetic/intro.bin")).unwrap();
+    for (offsets, child) in root.children.iter().zip(owned.children.as_ref().unwrap()) {
+        assert_eq!(offsets.name, child.name);
+        assert_eq!(index.decode_at(offsets.offset).unwrap(), *child);
+    }
+
+    assert!(index.decode_at(3).is_err());
+}
\ No newline at end of file
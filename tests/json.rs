@@ -0,0 +1,69 @@
+use cairn::{DuplicateKeys, JsonLoader};
+
+const DUPLICATED: &str = r#"{
+    "__name": "Map",
+    "package": "dup",
+    "__children": [
+        { "__name": "levels", "x": 1, "x": 2 }
+    ]
+}"#;
+
+#[test]
+fn duplicate_keys_error_by_default() {
+    let error = JsonLoader::new().load_from_reader(DUPLICATED.as_bytes()).unwrap_err();
+
+    assert!(error.to_string().contains("Duplicate key \"x\" in element levels"));
+}
+
+#[test]
+fn duplicate_keys_can_warn_or_pass() {
+    let report = JsonLoader::new()
+        .duplicate_keys(DuplicateKeys::Warn)
+        .load_from_reader(DUPLICATED.as_bytes())
+        .unwrap();
+
+    assert_eq!(report.warnings.len(), 1);
+    assert_eq!(report.map.find_child("levels").unwrap().get_i64("x"), Some(2));
+
+    let report = JsonLoader::new()
+        .duplicate_keys(DuplicateKeys::LastWins)
+        .load_from_reader(DUPLICATED.as_bytes())
+        .unwrap();
+
+    assert!(report.warnings.is_empty());
+}
+
+#[test]
+fn matches_serde_deserialization() {
+    let json = r#"{ "__name": "Map", "package": "p", "__children": [{ "__name": "levels", "__children": [] }] }"#;
+
+    let loaded = JsonLoader::new().load_from_reader(json.as_bytes()).unwrap().map;
+    let expected: cairn::DecodedElement = serde_json::from_str(json).unwrap();
+
+    assert_eq!(loaded, expected);
+}
+
+#[test]
+fn null_children_match_serde_deserialization() {
+    let json = r#"{ "__name": "Map", "package": "p", "__children": [{ "__name": "levels", "__children": null }] }"#;
+
+    let loaded = JsonLoader::new().load_from_reader(json.as_bytes()).unwrap().map;
+    let expected: cairn::DecodedElement = serde_json::from_str(json).unwrap();
+
+    assert_eq!(loaded, expected);
+    assert_eq!(loaded.children.unwrap()[0].children, None);
+}
+
+#[test]
+fn non_finite_numbers_name_the_attribute() {
+    for literal in ["1e400", "-1e400"] {
+        let json = format!(r#"{{ "__name": "Map", "package": "p", "__children": [{{ "__name": "levels", "x": {} }}] }}"#, literal);
+
+        let error = JsonLoader::new().load_from_reader(json.as_bytes()).unwrap_err();
+        let message = error.to_string();
+
+        assert!(message.contains("Invalid value for attribute \"x\""), "{}", message);
+        assert!(message.contains("number out of range"), "{}", message);
+        assert_eq!(message.matches("at line").count(), 1, "{}", message);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,110 @@
+//! Tests for layouts the format allows but encoders rarely write
+//!
+//! The maps are built by hand: `Filler` before `levels`, room names stored
+//! raw (type 6) as well as looked up, and run-length encoded tiles (type 7).
+//! They are not taken from the game, and the baseline decoder already read
+//! them correctly, so they pin behavior rather than reproduce a known bug.
+
+use cairn::{count_elements, Decoder};
+
+fn push_string(bytes: &mut Vec<u8>, s: &str) {
+    bytes.push(s.len() as u8);
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+fn element(bytes: &mut Vec<u8>, name: u16, attributes: u8, children: Option<u16>) {
+    bytes.extend_from_slice(&name.to_le_bytes());
+    bytes.push(attributes);
+    if let Some(children) = children {
+        bytes.extend_from_slice(&children.to_le_bytes());
+    }
+}
+
+const LOOKUP: &[&str] = &["Map", "Filler", "levels", "level", "name", "solids", "innerText", "a-00", "music"];
+
+/// `Map` with `Filler` before `levels`, holding two rooms with RLE tiles
+fn layout_map_bytes() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    push_string(&mut bytes, "CELESTE MAP");
+    push_string(&mut bytes, "Celeste/0-Intro");
+    bytes.extend_from_slice(&(LOOKUP.len() as u16).to_le_bytes());
+    for s in LOOKUP {
+        push_string(&mut bytes, s);
+    }
+
+    element(&mut bytes, 0, 0, Some(2)); // Map
+    element(&mut bytes, 1, 0, Some(0)); // Filler
+    element(&mut bytes, 2, 0, Some(2)); // levels
+
+    for room in 0..2 {
+        element(&mut bytes, 3, 2, None); // level
+        bytes.extend_from_slice(&4u16.to_le_bytes());
+        if room == 0 {
+            bytes.push(5); // lookup reference
+            bytes.extend_from_slice(&7u16.to_le_bytes());
+        } else {
+            bytes.push(6); // raw string
+            push_string(&mut bytes, "a-01");
+        }
+        bytes.extend_from_slice(&8u16.to_le_bytes());
+        bytes.push(6);
+        push_string(&mut bytes, "event:/music/lvl0/intro");
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+
+        element(&mut bytes, 5, 1, None); // solids
+        bytes.extend_from_slice(&6u16.to_le_bytes());
+        bytes.push(7); // run-length encoded
+        bytes.extend_from_slice(&6u16.to_le_bytes());
+        bytes.extend_from_slice(&[3, b'1', 1, b'\n', 3, b'0']);
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    bytes
+}
+
+#[test]
+fn filler_first_layout_keeps_all_rooms() {
+    let bytes = layout_map_bytes();
+    let map = Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+
+    let levels = map.find_child("levels").unwrap();
+    let rooms = levels.children.as_ref().unwrap();
+    let names: Vec<_> = rooms.iter().map(|room| room.get_str("name").unwrap()).collect();
+
+    assert_eq!(names, ["a-00", "a-01"]);
+    assert_eq!(rooms[1].find_child("solids").unwrap().get_str("innerText"), Some("111\n000"));
+    assert_eq!(count_elements(&bytes[..]).unwrap(), map.element_count());
+}
+
+#[test]
+fn odd_run_length_data_is_an_error() {
+    let mut bytes = layout_map_bytes();
+    // Declare 5 run bytes for the last layer and drop one, before its child count
+    let len = bytes.len();
+    bytes[len - 10] = 5;
+    bytes.remove(len - 3);
+
+    let error = Decoder::new().decode_from_reader(&bytes[..]).unwrap_err();
+    assert!(error.to_string().contains("odd byte count"));
+}
+
+#[test]
+fn zero_count_runs_warn_in_strict_mode() {
+    let mut bytes = layout_map_bytes();
+    // Turn the last layer's middle run into an empty one
+    let len = bytes.len();
+    bytes[len - 6] = 0;
+
+    let path = std::env::temp_dir().join(format!("cairn-zero-runs-{}.bin", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+
+    let report = Decoder::new().decode_with_report(&path).unwrap();
+    assert!(report.warnings.is_empty());
+
+    let report = Decoder::new().strict_run_length(true).decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let rooms = report.map.find_child("levels").unwrap().children.as_ref().unwrap();
+    assert_eq!(rooms[1].find_child("solids").unwrap().get_str("innerText"), Some("111000"));
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("Attribute innerText has 1 zero-count runs"));
+}
\ No newline at end of file
@@ -0,0 +1,135 @@
+mod common;
+
+use cairn::{
+    decode_map_with_stats, decode_sections, encode_map_to_vec, encode_map_with_stats, DecodedElement, Decoder, FlatMap,
+    Map, MapBuilder,
+};
+use common::room;
+
+#[test]
+fn map_room_operations() {
+    let mut map = Map::new("MyMod/1-Intro");
+    map.add_room(room("a-00"));
+    map.add_room(room("a-01"));
+
+    assert_eq!(map.package(), Some("MyMod/1-Intro"));
+    assert_eq!(map.rooms().count(), 2);
+    assert!(map.room("a-01").is_some());
+
+    map.room_mut("a-01").unwrap().set_attribute("music", "event:/music/lvl1/main");
+    let removed = map.remove_room("a-00").unwrap();
+
+    assert_eq!(removed.get_str("name"), Some("a-00"));
+    assert!(map.remove_room("a-00").is_none());
+    assert_eq!(map.rooms().count(), 1);
+    assert!(map.root().is_dirty());
+}
+
+#[test]
+fn add_room_creates_levels() {
+    let mut root = DecodedElement::new("Map");
+    root.set_attribute("package", "bare");
+    let mut map = Map::from(root);
+
+    map.add_room(room("a-00"));
+
+    assert_eq!(map.root().find_child("levels").unwrap().children.as_ref().unwrap().len(), 1);
+}
+
+#[test]
+fn map_save_and_load() {
+    let path = std::env::temp_dir().join(format!("cairn-facade-{}.bin", std::process::id()));
+    let mut map = Map::new("facade");
+    map.add_room(room("a-00"));
+
+    map.save(&path).unwrap();
+    let loaded = Map::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(loaded.root().semantically_eq(map.root()));
+    assert!(loaded.room("a-00").is_some());
+}
+
+#[test]
+fn root_attributes_survive_alongside_package() {
+    let mut map = Map::new("extras");
+    map.add_room(room("a-00"));
+    map.root_mut().set_attribute("author", "someone");
+    map.root_mut().set_attribute("version", 3);
+    map.root_mut().set_attribute("hidden", true);
+    let root = map.into_element();
+
+    let bytes = encode_map_to_vec(&root).unwrap();
+
+    let decoded = Decoder::new().decode_from_reader(&bytes[..]).unwrap();
+    assert_eq!(decoded, root);
+
+    let sections = decode_sections(&bytes[..]).unwrap();
+    assert_eq!(sections.root().attributes, root.attributes);
+
+    let mut flat = FlatMap::new();
+    flat.decode_from(&bytes[..]).unwrap();
+    assert_eq!(flat.to_element().unwrap(), root);
+}
+
+#[test]
+fn stats_describe_the_binary_map() {
+    let mut map = Map::new("stats");
+    map.add_room(room("a"));
+    map.add_room(room("b"));
+    let root = map.into_element();
+
+    let (bytes, encoded) = encode_map_with_stats(&root).unwrap();
+    let (decoded, stats) = decode_map_with_stats(&bytes).unwrap();
+
+    assert_eq!(decoded, root);
+    assert_eq!(encoded.bytes, bytes.len() as u64);
+    assert_eq!(encoded.elements, 4);
+    // Map, levels, level, name, a, b, package, stats
+    assert_eq!(encoded.lookup_size, 8);
+    assert_eq!((stats.bytes, stats.elements, stats.lookup_size), (encoded.bytes, 4, 8));
+}
+
+fn placed_room(name: &str, x: i64) -> DecodedElement {
+    let mut room = room(name);
+    room.set_attribute("x", x);
+    room.set_attribute("y", 0);
+    room.set_attribute("width", 320);
+    room.set_attribute("height", 184);
+    room
+}
+
+#[test]
+fn map_builder_assembles_rooms() {
+    let map = MapBuilder::new("MyMod/1-Intro")
+        .add_room(placed_room("a-00", 0))
+        .add_room(placed_room("a-01", 320))
+        .build()
+        .unwrap();
+
+    let sections: Vec<_> = map.children.iter().flatten().map(|child| child.name.as_str()).collect();
+    assert_eq!(sections, ["Filler", "levels", "Style"]);
+    assert!(cairn::validate_map(&map).is_empty());
+
+    let decoded = Decoder::new().decode_from_reader(&encode_map_to_vec(&map).unwrap()[..]).unwrap();
+    assert!(decoded.semantically_eq(&map));
+    assert_eq!(Map::from(decoded).room("a-01").unwrap().get_i64("x"), Some(320));
+}
+
+#[test]
+fn map_builder_rejects_bad_rooms() {
+    let build = |room: DecodedElement| MapBuilder::new("MyMod/1-Intro").add_room(room).build().unwrap_err().to_string();
+
+    assert!(build(DecodedElement::new("entities")).contains("expected \"level\""));
+    assert!(build(room("a-00")).contains("no numeric x"));
+
+    let duplicate = MapBuilder::new("MyMod/1-Intro")
+        .add_room(placed_room("a-00", 0))
+        .add_room(placed_room("a-00", 320))
+        .build()
+        .unwrap_err();
+    assert!(duplicate.to_string().contains("Duplicate room name"));
+
+    let style = MapBuilder::new("MyMod/1-Intro").with_style(DecodedElement::new("Foregrounds")).build();
+    assert!(style.is_err());
+}
\ No newline at end of file
@@ -0,0 +1,574 @@
+use std::path::PathBuf;
+
+use std::io::Cursor;
+
+use cairn::{
+    decode_map, encode_map, encode_map_to_vec, index_map, repair_map, DecodeError, DecodedElement, Decoder, Encoder,
+    MapBuilder, TRUNCATED_ATTRIBUTE,
+};
+use serde_json::Value;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cairn-{}-{}.bin", name, std::process::id()))
+}
+
+fn push_string(bytes: &mut Vec<u8>, s: &str) {
+    bytes.push(s.len() as u8);
+    bytes.extend_from_slice(s.as_bytes());
+}
+
+/// Header, package, a one-entry lookup table and a childless `Map` root
+fn minimal_map_bytes() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    push_string(&mut bytes, "CELESTE MAP");
+    push_string(&mut bytes, "minimal");
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    push_string(&mut bytes, "Map");
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // name index
+    bytes.push(0); // attribute count
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // child count
+    bytes
+}
+
+#[test]
+fn decodes_minimal_map() {
+    let path = temp_path("minimal");
+    std::fs::write(&path, minimal_map_bytes()).unwrap();
+
+    let map = decode_map(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(map.name, "Map");
+    assert_eq!(map.children, None);
+    assert_eq!(map.attributes.len(), 1);
+    assert_eq!(map.attributes["package"], Value::String("minimal".into()));
+}
+
+#[test]
+fn minimal_map_round_trips() {
+    let path = temp_path("minimal-roundtrip");
+    std::fs::write(&path, minimal_map_bytes()).unwrap();
+
+    let map = decode_map(&path).unwrap();
+    encode_map(&map, &path).unwrap();
+    let decoded = decode_map(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(decoded, map);
+}
+
+#[test]
+fn empty_children_array_decodes_as_none() {
+    let mut map = DecodedElement::new("Map");
+    map.attributes.insert("package".into(), Value::String("empty".into()));
+    map.children = Some(vec![DecodedElement::new("levels")]);
+    map.children.as_mut().unwrap()[0].children = Some(Vec::new());
+
+    let path = temp_path("empty-children");
+    encode_map(&map, &path).unwrap();
+    let decoded = decode_map(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(decoded.find_child("levels").unwrap().children, None);
+    assert!(decoded.semantically_eq(&map));
+}
+
+#[test]
+fn truncated_map_reports_unexpected_eof() {
+    let bytes = minimal_map_bytes();
+    let path = temp_path("truncated");
+    std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+
+    let error = decode_map(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(error.to_string().contains("truncated"));
+}
+
+/// A root declaring two children, followed by one child cut off after the
+/// first of its two attributes
+fn truncated_children_bytes() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    push_string(&mut bytes, "CELESTE MAP");
+    push_string(&mut bytes, "partial");
+    bytes.extend_from_slice(&3u16.to_le_bytes());
+    push_string(&mut bytes, "Map");
+    push_string(&mut bytes, "levels");
+    push_string(&mut bytes, "a");
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // root name index
+    bytes.push(0); // attribute count
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // child count
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // child name index
+    bytes.push(2); // attribute count
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // key index
+    bytes.push(1); // u8 type
+    bytes.push(5);
+    bytes
+}
+
+#[test]
+fn recover_keeps_partially_read_elements() {
+    let path = temp_path("recover");
+    std::fs::write(&path, truncated_children_bytes()).unwrap();
+
+    let strict = decode_map(&path);
+    let report = Decoder::new().recover(true).decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(strict.is_err());
+
+    let levels = report.map.find_child("levels").unwrap();
+    assert_eq!(report.map.children.as_ref().unwrap().len(), 1);
+    assert_eq!(levels.attributes["a"], Value::from(5));
+
+    assert_eq!(report.warnings.len(), 2);
+    assert!(report.warnings[0].contains("levels: expected 2 attributes, got 1"));
+    assert!(report.warnings[1].contains("Map: expected 2 children, got 1"));
+}
+
+/// A root declaring two children, the first with a corrupt value type
+/// followed by bytes that happen to form a whole element
+fn corrupt_child_bytes() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    push_string(&mut bytes, "CELESTE MAP");
+    push_string(&mut bytes, "corrupt");
+    bytes.extend_from_slice(&3u16.to_le_bytes());
+    push_string(&mut bytes, "Map");
+    push_string(&mut bytes, "levels");
+    push_string(&mut bytes, "a");
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // root name index
+    bytes.push(0); // attribute count
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // child count
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // child name index
+    bytes.push(1); // attribute count
+    bytes.extend_from_slice(&2u16.to_le_bytes()); // key index
+    bytes.push(0xee); // invalid type
+    bytes.extend_from_slice(&[1, 0, 0, 0, 0]); // misaligned, but reads as `levels`
+    bytes
+}
+
+#[test]
+fn recover_stops_at_the_first_error() {
+    let path = temp_path("recover-corrupt");
+    std::fs::write(&path, corrupt_child_bytes()).unwrap();
+
+    let report = Decoder::new().recover(true).decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // The bytes after the bad type are not taken for a second child
+    assert_eq!(report.map.children.as_ref().unwrap().len(), 1);
+    assert!(report.map.find_child("levels").unwrap().attributes.is_empty());
+
+    assert_eq!(report.warnings.len(), 2);
+    assert!(report.warnings[0].contains("levels: expected 1 attributes, got 0 (Invalid value type)"));
+    assert!(report.warnings[1].contains("Map: expected 2 children, got 1"));
+}
+
+#[test]
+fn repair_writes_a_valid_map_from_what_was_recovered() {
+    let input = temp_path("repair-in");
+    let output = temp_path("repair-out");
+    std::fs::write(&input, truncated_children_bytes()).unwrap();
+
+    let report = repair_map(&input, &output).unwrap();
+    let repaired = decode_map(&output).unwrap();
+
+    assert!(report.repaired());
+    assert_eq!(report.problems.len(), 2);
+    assert_eq!(report.elements, 2);
+    assert_eq!(repaired.find_child("levels").unwrap().attributes["a"], Value::from(5));
+
+    // An intact map is copied with nothing to report
+    std::fs::write(&input, minimal_map_bytes()).unwrap();
+    let report = repair_map(&input, &output).unwrap();
+    let copy = decode_map(&output).unwrap();
+    let original = decode_map(&input).unwrap();
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    assert!(!report.repaired());
+    assert!(copy.semantically_eq(&original));
+}
+
+#[test]
+fn repair_never_writes_over_its_input() {
+    // Relative to the manifest directory, where tests run
+    let input = format!("target/cairn-repair-self-{}.bin", std::process::id());
+    let bytes = truncated_children_bytes();
+    std::fs::write(&input, &bytes).unwrap();
+
+    let absolute = std::fs::canonicalize(&input).unwrap();
+    for output in [PathBuf::from(format!("./{}", input)), absolute] {
+        let error = repair_map(&input, &output).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("is the input file"), "{}", error);
+    }
+
+    let unchanged = std::fs::read(&input).unwrap();
+    std::fs::remove_file(&input).unwrap();
+    assert_eq!(unchanged, bytes);
+}
+
+#[test]
+fn repair_keeps_what_precedes_a_corrupt_byte() {
+    let mut builder = MapBuilder::new("corrupt");
+    for (index, name) in ["lvl_a", "lvl_b", "lvl_c"].into_iter().enumerate() {
+        let mut room = DecodedElement::new("level");
+        room.set_attribute("name", name);
+        room.set_attribute("x", index as i32 * 320);
+        room.set_attribute("y", 0);
+        room.set_attribute("width", 320);
+        room.set_attribute("height", 184);
+        builder = builder.add_room(room);
+    }
+    let mut bytes = encode_map_to_vec(&builder.build().unwrap()).unwrap();
+
+    // Point the second room's name far outside the lookup table, mid-file
+    let index = index_map(Cursor::new(&bytes)).unwrap();
+    let offset = index.root().find_child("levels").unwrap().children[1].offset as usize;
+    bytes[offset..offset + 2].copy_from_slice(&[0xff, 0xff]);
+
+    let input = temp_path("repair-corrupt-in");
+    let output = temp_path("repair-corrupt-out");
+    std::fs::write(&input, &bytes).unwrap();
+
+    let report = repair_map(&input, &output).unwrap();
+    let repaired = decode_map(&output).unwrap();
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    assert!(report.repaired());
+    assert_eq!(report.input_bytes, bytes.len() as u64);
+    assert!(report.problems[0].contains("levels: expected 3 children, got 1"));
+    assert!(report.problems[0].contains("Invalid element name index 65535"));
+
+    // Nothing after the corrupt room survives, not even the Style section
+    let rooms = repaired.find_child("levels").unwrap().children.as_ref().unwrap();
+    assert_eq!(rooms.len(), 1);
+    assert_eq!(rooms[0].get_str("name"), Some("lvl_a"));
+    assert!(repaired.find_child("Style").is_none());
+    assert_eq!(report.elements, repaired.element_count());
+}
+
+#[test]
+fn count_elements_matches_decoded_tree() {
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.set_attribute("x", 1280);
+    room.set_attribute("alpha", 0.5);
+    room.set_attribute("dark", true);
+    let mut solids = DecodedElement::new("solids");
+    solids.set_attribute("innerText", "000000000011111");
+    room.add_child(solids);
+    room.add_child(DecodedElement::new("entities"));
+
+    let mut levels = DecodedElement::new("levels");
+    levels.add_child(room);
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "count");
+    map.add_child(levels);
+
+    let bytes = cairn::encode_map_to_vec(&map).unwrap();
+
+    assert_eq!(cairn::count_elements(&bytes[..]).unwrap(), map.element_count());
+    assert!(cairn::count_elements(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn decode_sections_yields_top_level_children() {
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "sections");
+    map.add_child(DecodedElement::new("levels"));
+    map.add_child(DecodedElement::new("Style"));
+    let bytes = cairn::encode_map_to_vec(&map).unwrap();
+
+    let sections = cairn::decode_sections(&bytes[..]).unwrap();
+    assert_eq!(sections.root().get_str("package"), Some("sections"));
+    assert_eq!(sections.root().children, None);
+
+    let names: Vec<_> = sections.map(|section| section.unwrap().name).collect();
+    assert_eq!(names, ["levels", "Style"]);
+
+    let mut truncated = cairn::decode_sections(&bytes[..bytes.len() - 2]).unwrap();
+    assert!(truncated.next().unwrap().is_ok());
+    assert!(truncated.next().unwrap().is_err());
+    assert!(truncated.next().is_none());
+}
+
+#[test]
+fn lookup_usage_counts_every_reference() {
+    let mut bytes = Vec::new();
+    push_string(&mut bytes, "CELESTE MAP");
+    push_string(&mut bytes, "usage");
+    bytes.extend_from_slice(&5u16.to_le_bytes());
+    for s in ["Map", "entity", "name", "unused", "spikes"] {
+        push_string(&mut bytes, s);
+    }
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // Map
+    bytes.push(0);
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    for _ in 0..2 {
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // entity
+        bytes.push(1);
+        bytes.extend_from_slice(&2u16.to_le_bytes()); // name
+        bytes.push(5); // lookup type
+        bytes.extend_from_slice(&4u16.to_le_bytes()); // spikes
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    let path = temp_path("lookup-usage");
+    std::fs::write(&path, bytes).unwrap();
+
+    let counted = Decoder::new().lookup_usage(true).decode_with_report(&path).unwrap();
+    let plain = Decoder::new().decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let usage = counted.lookup_usage;
+    assert_eq!(usage.len(), 5);
+    assert_eq!(usage["Map"], 1);
+    assert_eq!(usage["entity"], 2);
+    assert_eq!(usage["name"], 2);
+    assert_eq!(usage["spikes"], 2);
+    assert_eq!(usage["unused"], 0);
+
+    assert!(plain.lookup_usage.is_empty());
+    assert_eq!(plain.map, counted.map);
+}
+
+#[test]
+fn kept_lookup_reproduces_the_original_table() {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/synthetic/garden.bin");
+    let original = std::fs::read(&path).unwrap();
+
+    let report = Decoder::new().keep_lookup(true).decode_with_report(&path).unwrap();
+    assert!(Decoder::new().decode_with_report(&path).unwrap().lookup.is_empty());
+
+    let reencoded = Encoder::new().with_seed_lookup(report.lookup.clone()).encode_to_vec(&report.map).unwrap();
+    let copy = temp_path("kept-lookup");
+    std::fs::write(&copy, &reencoded).unwrap();
+    let again = Decoder::new().keep_lookup(true).decode_with_report(&copy).unwrap();
+    std::fs::remove_file(&copy).unwrap();
+
+    assert_eq!(again.lookup, report.lookup);
+
+    // Header and lookup section are identical; every string here is short
+    // enough for a one-byte length prefix
+    let package = report.map.get_str("package").unwrap();
+    let header_len = 12 + 1 + package.len() + 2 + report.lookup.iter().map(|s| 1 + s.len()).sum::<usize>();
+    assert_eq!(reencoded[..header_len], original[..header_len]);
+}
+
+/// A map whose root declares `declared` children and has `actual` levels
+fn miscounted_map(declared: u16, actual: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    push_string(&mut bytes, "CELESTE MAP");
+    push_string(&mut bytes, "miscounted");
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    push_string(&mut bytes, "Map");
+    push_string(&mut bytes, "level");
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // Map
+    bytes.push(0);
+    bytes.extend_from_slice(&declared.to_le_bytes());
+    for _ in 0..actual {
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // level
+        bytes.push(0);
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn strict_child_counts_point_at_the_mismatch() {
+    let path = temp_path("miscounted");
+    let decode = |bytes: Vec<u8>, strict: bool| {
+        std::fs::write(&path, bytes).unwrap();
+        Decoder::new().strict_child_counts(strict).decode_with_report(&path)
+    };
+
+    // Too many: the missing third child runs into the end of the file
+    let plain = decode(miscounted_map(3, 2), false).unwrap_err();
+    let strict = decode(miscounted_map(3, 2), true).unwrap_err();
+    assert!(!plain.to_string().contains("mismatch"));
+    assert_eq!(strict.kind(), std::io::ErrorKind::UnexpectedEof);
+    assert!(strict.to_string().starts_with("Child count mismatch suspected: Map declares 3 children, but child 3 does not decode"));
+    assert_eq!(DecodeError::from_io(&strict).unwrap().offset(), 50);
+
+    // Too few: the extra children are left after the root
+    let plain = decode(miscounted_map(1, 3), false).unwrap();
+    let strict = decode(miscounted_map(1, 3), true).unwrap_err();
+    assert_eq!(plain.warnings, ["10 trailing bytes after the root element at byte offset 45"]);
+    assert!(strict.to_string().contains("10 trailing bytes after the root element decode as 2 element(s)"));
+
+    // Intact maps are unaffected
+    let intact = decode(miscounted_map(2, 2), true).unwrap();
+    assert_eq!(intact.map.children.unwrap().len(), 2);
+
+    // A child landing on a byte that is no element start is blamed too
+    let mut misaligned = miscounted_map(3, 2);
+    misaligned.extend_from_slice(&[9, 0, 0, 0, 0]);
+    let strict = decode(misaligned, true).unwrap_err();
+    assert!(strict.to_string().starts_with("Child count mismatch suspected: Map declares 3 children, but child 3"));
+    assert!(strict.to_string().contains("Invalid element name index 9"));
+
+    // Other failures inside a child keep their own error
+    let strict = decode(corrupt_child_bytes(), true).unwrap_err();
+    assert_eq!(strict.to_string(), "Invalid value type at byte offset 46");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn unused_lookup_strings_are_reported() {
+    let mut bytes = Vec::new();
+    push_string(&mut bytes, "CELESTE MAP");
+    push_string(&mut bytes, "unused");
+    bytes.extend_from_slice(&4u16.to_le_bytes());
+    for s in ["Map", "stale", "Map", "level"] {
+        push_string(&mut bytes, s);
+    }
+    bytes.extend_from_slice(&0u16.to_le_bytes()); // Map
+    bytes.push(0);
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&3u16.to_le_bytes()); // level
+    bytes.push(0);
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+
+    let path = temp_path("unused-lookup");
+    std::fs::write(&path, bytes).unwrap();
+    let report = Decoder::new().unused_lookup(true).decode_with_report(&path).unwrap();
+
+    // A map encoded by cairn references every string it stores
+    encode_map(&report.map, &path).unwrap();
+    let reencoded = Decoder::new().unused_lookup(true).decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(report.unused_lookup, ["stale", "Map"]);
+    assert_eq!(
+        report.warnings,
+        [
+            "Lookup string \"stale\" at index 1 is never referenced",
+            "Lookup string \"Map\" at index 2 is never referenced",
+        ],
+    );
+    assert!(report.lookup_usage.is_empty());
+
+    assert!(reencoded.unused_lookup.is_empty());
+    assert!(reencoded.warnings.is_empty());
+}
+
+#[test]
+fn concatenated_maps_are_detected() {
+    let first = minimal_map_bytes();
+    let mut bytes = first.clone();
+    bytes.extend_from_slice(&minimal_map_bytes());
+
+    let path = temp_path("concatenated");
+    std::fs::write(&path, &bytes).unwrap();
+
+    let report = Decoder::new().decode_with_report(&path).unwrap();
+    let strict = Decoder::new().strict_trailing(true).decode(&path).unwrap_err();
+    let recovered = Decoder::new().strict_trailing(true).recover(true).decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let expected = format!("Trailing data looks like a second CELESTE MAP header at byte offset {}", first.len());
+    assert_eq!(report.map.name, "Map");
+    assert_eq!(report.warnings, std::slice::from_ref(&expected));
+    assert_eq!(strict.to_string(), expected);
+    assert_eq!(DecodeError::from_io(&strict).unwrap().offset(), first.len() as u64);
+    assert_eq!(recovered.warnings, [expected]);
+}
+
+#[test]
+fn other_trailing_bytes_are_a_warning() {
+    let mut bytes = minimal_map_bytes();
+    let offset = bytes.len();
+    bytes.extend_from_slice(&[0, 0, 0]);
+
+    let path = temp_path("trailing");
+    std::fs::write(&path, &bytes).unwrap();
+
+    let report = Decoder::new().decode_with_report(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(report.map.name, "Map");
+    assert_eq!(report.warnings, [format!("3 trailing bytes after the root element at byte offset {}", offset)]);
+}
+
+#[test]
+fn max_rooms_stops_after_the_first_rooms() {
+    let mut levels = DecodedElement::new("levels");
+    for name in ["a", "b", "c"] {
+        let mut room = DecodedElement::new("level");
+        room.set_attribute("name", name);
+        levels.add_child(room);
+    }
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "preview");
+    map.add_child(levels);
+    map.add_child(DecodedElement::new("Style"));
+
+    let path = temp_path("max-rooms");
+    encode_map(&map, &path).unwrap();
+
+    let preview = Decoder::new().max_rooms(2).decode_with_report(&path).unwrap();
+    let whole = Decoder::new().max_rooms(3).decode(&path).unwrap();
+    let plain = decode_map(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let sections = preview.map.children.as_ref().unwrap();
+    let rooms: Vec<_> = sections[0].children.iter().flatten().map(|room| room.get_str("name").unwrap()).collect();
+    assert_eq!(rooms, ["a", "b"]);
+    assert_eq!(sections.len(), 1);
+    assert_eq!(preview.map.get_str("package"), Some("preview"));
+    assert_eq!(preview.map.attributes[TRUNCATED_ATTRIBUTE], Value::Bool(true));
+    assert_eq!(preview.warnings.len(), 1);
+    assert_eq!(whole, plain);
+}
+
+#[test]
+fn on_element_hooks_see_matching_elements() {
+    use std::sync::{Arc, Mutex};
+
+    let mut entities = DecodedElement::new("entities");
+    for name in ["FrostHelper/spring", "player", "FrostHelper/lamp"] {
+        entities.add_child(DecodedElement::new(name));
+    }
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "hooks");
+    map.add_child(entities);
+    let bytes = cairn::encode_map_to_vec(&map).unwrap();
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&seen);
+    let decoded = Decoder::new()
+        .on_element("FrostHelper/*", move |element| collected.lock().unwrap().push(element.name.clone()))
+        .on_element("player", |element| element.set_attribute("seen", true))
+        .decode_from_reader(&bytes[..])
+        .unwrap();
+
+    assert_eq!(*seen.lock().unwrap(), ["FrostHelper/spring", "FrostHelper/lamp"]);
+    let player = &decoded.find_child("entities").unwrap().children.as_ref().unwrap()[1];
+    assert_eq!(player.attributes["seen"], Value::Bool(true));
+
+    let all = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&all);
+    Decoder::new().on_element("*", move |_| *counter.lock().unwrap() += 1).decode_from_reader(&bytes[..]).unwrap();
+    assert_eq!(*all.lock().unwrap(), map.element_count());
+}
+
+#[test]
+fn leading_bom_and_whitespace_are_detected_or_skipped() {
+    let mut bytes = vec![0xEF, 0xBB, 0xBF, b'\n', b' '];
+    bytes.extend_from_slice(&minimal_map_bytes());
+
+    let strict = Decoder::new().decode_from_reader(&bytes[..]).unwrap_err();
+    assert_eq!(strict.to_string(), "Unexpected 5 leading bytes before the header at byte offset 6");
+
+    let path = temp_path("leading-bytes");
+    std::fs::write(&path, &bytes).unwrap();
+    let skipped = Decoder::new().skip_leading_bytes(true).decode(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let plain = Decoder::new().decode_from_reader(&minimal_map_bytes()[..]).unwrap();
+    assert_eq!(skipped.unwrap(), plain);
+}
\ No newline at end of file
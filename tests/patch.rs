@@ -0,0 +1,79 @@
+use cairn::{apply_patch, DecodedElement, JsonLoader};
+use serde_json::json;
+
+fn sample_map() -> DecodedElement {
+    let json = r#"{
+        "__name": "Map",
+        "package": "patched",
+        "__children": [
+            { "__name": "levels", "__children": [
+                { "__name": "level", "name": "a-00", "__children": [
+                    { "__name": "entities", "__children": [
+                        { "__name": "spinner", "id": 1, "color": "Red" },
+                        { "__name": "refill", "id": 2 }
+                    ] }
+                ] }
+            ] }
+        ]
+    }"#;
+    JsonLoader::new().load_from_reader(json.as_bytes()).unwrap().map
+}
+
+const SPINNER: &str = "/__children/0/__children/0/__children/0/__children/0";
+
+fn spinner(map: &DecodedElement) -> &DecodedElement {
+    map.descendants().find(|e| e.name == "spinner").unwrap()
+}
+
+#[test]
+fn merge_patch_sets_and_removes_root_attributes() {
+    let mut map = sample_map();
+    apply_patch(&mut map, &json!({ "package": "renamed", "author": "me" })).unwrap();
+
+    assert_eq!(map.get_str("package"), Some("renamed"));
+    assert_eq!(map.get_str("author"), Some("me"));
+    assert!(map.is_dirty());
+
+    apply_patch(&mut map, &json!({ "author": null })).unwrap();
+    assert!(!map.attributes.contains_key("author"));
+    assert_eq!(spinner(&map).get_str("color"), Some("Red"));
+}
+
+#[test]
+fn json_patch_edits_single_children() {
+    let mut map = sample_map();
+    let patch = json!([
+        { "op": "test", "path": format!("{}/__name", SPINNER), "value": "spinner" },
+        { "op": "replace", "path": format!("{}/color", SPINNER), "value": "Blue" },
+        { "op": "remove", "path": "/__children/0/__children/0/__children/0/__children/1" },
+        { "op": "add", "path": "/__children/-", "value": { "__name": "Style" } }
+    ]);
+
+    apply_patch(&mut map, &patch).unwrap();
+
+    assert_eq!(spinner(&map).get_str("color"), Some("Blue"));
+    assert!(map.descendants().all(|e| e.name != "refill"));
+    assert_eq!(map.children.as_ref().unwrap()[1].name, "Style");
+}
+
+#[test]
+fn failed_patches_name_the_path_and_change_nothing() {
+    let mut map = sample_map();
+    let original = map.clone();
+
+    let patch = json!([
+        { "op": "replace", "path": format!("{}/color", SPINNER), "value": "Blue" },
+        { "op": "replace", "path": "/__children/3/name", "value": "b-00" }
+    ]);
+    let error = apply_patch(&mut map, &patch).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Patch operation 1 (replace \"/__children/3/name\") failed: no value at \"/__children/3/name\""
+    );
+
+    let error = apply_patch(&mut map, &json!({ "__children": [{ "__name": 5 }] })).unwrap_err();
+    assert_eq!(error.to_string(), "Patched map is invalid: \"/__children/0/__name\" must be a string");
+
+    assert_eq!(map, original);
+    assert!(!map.is_dirty());
+}
\ No newline at end of file
@@ -0,0 +1,47 @@
+mod common;
+
+use std::path::PathBuf;
+
+use cairn::{decode_map, load_project, save_project, PROJECT_MAGIC};
+use serde_json::json;
+use common::fixture;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cairn-project-{}-{}", name, std::process::id()))
+}
+
+#[test]
+fn project_round_trips_map_and_metadata() {
+    let map = decode_map(fixture("synthetic/intro.bin")).unwrap();
+    let metadata = json!({ "camera": { "x": 128, "y": -64 }, "selectedRoom": "a-00", "undoMarker": 17 });
+
+    let path = temp_path("round-trip");
+    save_project(&path, &map, &metadata).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let project = load_project(&path).unwrap();
+    let as_map = decode_map(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(bytes.starts_with(PROJECT_MAGIC));
+    assert_eq!(project.map, map);
+    assert_eq!(project.metadata, metadata);
+
+    // Never mistaken for a map, in either direction
+    assert!(as_map.is_err());
+    let error = load_project(fixture("synthetic/intro.bin")).unwrap_err();
+    assert_eq!(error.to_string(), "Not a project file");
+}
+
+#[test]
+fn truncated_project_is_rejected() {
+    let map = decode_map(fixture("synthetic/intro.bin")).unwrap();
+
+    let path = temp_path("truncated");
+    save_project(&path, &map, &json!({})).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    let error = load_project(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(error.to_string(), "Project metadata section truncated");
+}
\ No newline at end of file
@@ -0,0 +1,108 @@
+mod common;
+
+use cairn::{filler_rects, map_bounds, rename_room, room_adjacency, set_filler_rects, spawn_points, DecodedElement, Rect};
+use common::map_with_rooms;
+use std::io;
+
+fn room(name: &str, x: i64, y: i64, width: i64, height: i64) -> DecodedElement {
+    let mut room = common::room(name);
+    room.set_attribute("x", x);
+    room.set_attribute("y", y);
+    room.set_attribute("width", width);
+    room.set_attribute("height", height);
+    room
+}
+
+#[test]
+fn adjacency_from_shared_edges() {
+    let map = map_with_rooms(vec![
+        room("a", 0, 0, 320, 184),
+        room("b", 320, 0, 320, 184),   // right of a
+        room("c", 0, 184, 320, 184),   // below a
+        room("d", 320, 184, 320, 184), // corner of a, next to b and c
+        room("far", 2000, 2000, 320, 184),
+    ]);
+
+    assert_eq!(
+        room_adjacency(&map),
+        [("a", "b"), ("a", "c"), ("b", "d"), ("c", "d")].map(|(a, b)| (a.to_string(), b.to_string())),
+    );
+    assert_eq!(map_bounds(&map), Some(Rect::new(0.0, 0.0, 2320.0, 2184.0)));
+}
+
+#[test]
+fn renaming_a_room_updates_triggers_that_name_it() {
+    let mut teleport = DecodedElement::new("teleportTrigger");
+    teleport.set_attribute("targetRoom", "a-00");
+    teleport.set_attribute("label", "a-00");
+    let mut triggers = DecodedElement::new("triggers");
+    triggers.add_child(teleport);
+
+    let mut source = room("b-00", 320, 0, 320, 184);
+    source.add_child(triggers);
+    let mut map = map_with_rooms(vec![room("a-00", 0, 0, 320, 184), source]);
+
+    assert_eq!(rename_room(&mut map, "a-00", "start").unwrap(), 1);
+
+    let levels = &map.children.as_ref().unwrap()[0];
+    let rooms = levels.children.as_ref().unwrap();
+    let teleport = &rooms[1].children.as_ref().unwrap()[0].children.as_ref().unwrap()[0];
+    assert_eq!(rooms[0].get_str("name"), Some("start"));
+    assert_eq!(teleport.get_str("targetRoom"), Some("start"));
+    assert_eq!(teleport.get_str("label"), Some("a-00"));
+
+    assert_eq!(rename_room(&mut map, "missing", "x").unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(rename_room(&mut map, "start", "b-00").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn filler_rects_round_trip_in_pixels() {
+    let mut map = map_with_rooms(vec![room("a", 0, 0, 320, 184)]);
+    assert!(filler_rects(&map).is_empty());
+
+    set_filler_rects(&mut map, &[]);
+    assert!(map.find_child("Filler").is_none());
+
+    let rects = [Rect::new(-80.0, 0.0, 40.0, 184.0), Rect::new(320.0, 16.0, 8.0, 8.0)];
+    set_filler_rects(&mut map, &rects);
+    assert_eq!(map.children.as_ref().unwrap()[0].name, "Filler");
+    assert_eq!(filler_rects(&map), rects);
+
+    // Stored in tiles, as Celeste reads them
+    let first = &map.find_child("Filler").unwrap().children.as_ref().unwrap()[0];
+    assert_eq!((first.get_i64("x"), first.get_i64("w"), first.get_i64("h")), (Some(-10), Some(5), Some(23)));
+
+    set_filler_rects(&mut map, &rects[1..]);
+    assert_eq!(filler_rects(&map), &rects[1..]);
+    assert_eq!(map.children.as_ref().unwrap().len(), 2);
+}
+
+#[test]
+fn spawn_points_lists_every_player() {
+    let entity = |name: &str, x: f64, y: f64| {
+        let mut entity = DecodedElement::new(name);
+        entity.set_attribute("x", x);
+        entity.set_attribute("y", y);
+        entity
+    };
+    let entities = |children: Vec<DecodedElement>| {
+        let mut entities = DecodedElement::new("entities");
+        for child in children {
+            entities.add_child(child);
+        }
+        entities
+    };
+
+    let mut start = room("start", 0, 0, 320, 184);
+    start.add_child(entities(vec![entity("player", 16.0, 160.0), entity("spinner", 40.0, 40.0)]));
+    let empty = room("empty", 320, 0, 320, 184);
+    let mut checkpoint = room("checkpoint", 640, 0, 320, 184);
+    checkpoint.add_child(entities(vec![entity("player", 8.0, 152.0), entity("player", 300.5, 152.0)]));
+    let map = map_with_rooms(vec![start, empty, checkpoint]);
+
+    assert_eq!(
+        spawn_points(&map),
+        [("start", 16.0, 160.0), ("checkpoint", 8.0, 152.0), ("checkpoint", 300.5, 152.0)]
+            .map(|(room, x, y)| (room.to_string(), x, y)),
+    );
+}
\ No newline at end of file
@@ -0,0 +1,33 @@
+use std::collections::BTreeSet;
+
+use cairn::{attribute_keys, attribute_keys_with_internal, DecodedElement};
+
+fn keys(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn attribute_keys_are_grouped_by_element_name() {
+    let mut spinner = DecodedElement::new("spinner");
+    spinner.set_attribute("x", 8);
+    spinner.set_attribute("attachToSolid", false);
+    let mut other_spinner = DecodedElement::new("spinner");
+    other_spinner.set_attribute("x", 16);
+    other_spinner.set_attribute("color", "Red");
+    other_spinner.set_comment("check the color");
+
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(spinner);
+    entities.add_child(other_spinner);
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "schema");
+    map.add_child(entities);
+
+    let found = attribute_keys(&map);
+    assert_eq!(found.keys().collect::<Vec<_>>(), ["Map", "entities", "spinner"]);
+    assert_eq!(found["Map"], keys(&["package"]));
+    assert!(found["entities"].is_empty());
+    assert_eq!(found["spinner"], keys(&["attachToSolid", "color", "x"]));
+
+    assert_eq!(attribute_keys_with_internal(&map)["spinner"], keys(&["__comment", "attachToSolid", "color", "x"]));
+}
\ No newline at end of file
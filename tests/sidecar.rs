@@ -0,0 +1,66 @@
+use cairn::{bin_to_json, json_to_bin, json_to_bin_streaming, sidecar_path, DecodedElement};
+
+#[test]
+fn comments_survive_a_binary_round_trip() {
+    let dir = std::env::temp_dir().join(format!("cairn-sidecar-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let json_in = dir.join("in.json");
+    let bin = dir.join("map.bin");
+    let json_out = dir.join("out.json");
+
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.set_comment("intro room, keep it short");
+    let mut levels = DecodedElement::new("levels");
+    levels.add_child(room);
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "commented");
+    map.add_child(levels);
+
+    std::fs::write(&json_in, serde_json::to_string(&map).unwrap()).unwrap();
+    json_to_bin(&json_in, &bin).unwrap();
+    assert!(sidecar_path(&bin).exists());
+
+    bin_to_json(&bin, &json_out).unwrap();
+    let restored: DecodedElement = serde_json::from_str(&std::fs::read_to_string(&json_out).unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let room = &restored.find_child("levels").unwrap().children.as_ref().unwrap()[0];
+    assert_eq!(room.comment(), Some("intro room, keep it short"));
+}
+
+#[test]
+fn streaming_writes_the_same_sidecar() {
+    let dir = std::env::temp_dir().join(format!("cairn-sidecar-stream-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let json = dir.join("in.json");
+    let tree = dir.join("tree.bin");
+    let streamed = dir.join("streamed.bin");
+
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.set_comment("intro room");
+    room.set_attribute("__editor_color", "#ff0000");
+    let mut levels = DecodedElement::new("levels");
+    levels.add_child(DecodedElement::new("level"));
+    levels.add_child(room);
+    let mut map = DecodedElement::new("Map");
+    map.set_attribute("package", "commented");
+    map.set_comment("whole map");
+    map.add_child(levels);
+
+    std::fs::write(&json, serde_json::to_string(&map).unwrap()).unwrap();
+    json_to_bin(&json, &tree).unwrap();
+    json_to_bin_streaming(&json, &streamed).unwrap();
+    let expected = std::fs::read(sidecar_path(&tree)).unwrap();
+    let actual = std::fs::read(sidecar_path(&streamed)).unwrap();
+
+    // Without `__` attributes the stale sidecar goes away
+    std::fs::write(&json, r#"{"__name": "Map", "package": "plain"}"#).unwrap();
+    json_to_bin_streaming(&json, &streamed).unwrap();
+    let stale = sidecar_path(&streamed).exists();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(String::from_utf8(actual).unwrap(), String::from_utf8(expected).unwrap());
+    assert!(!stale);
+}
\ No newline at end of file
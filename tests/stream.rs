@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use cairn::{bin_to_json, bin_to_json_streaming, decode_map, json_to_bin, json_to_bin_streaming};
+use serde_json::Value;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cairn-{}-{}", name, std::process::id()))
+}
+
+const MAP_JSON: &str = r#"{
+    "__name": "Map",
+    "package": "streamed",
+    "__children": [
+        {
+            "__name": "levels",
+            "__children": [
+                {
+                    "__name": "level",
+                    "name": "a-00",
+                    "x": 0,
+                    "y": -8,
+                    "musicProgress": "",
+                    "__children": [
+                        { "__name": "solids", "innerText": "0000000000\n0011111100" },
+                        { "__name": "entities", "__children": [
+                            { "__name": "player", "id": 1, "x": 16.5, "y": 160 }
+                        ] }
+                    ]
+                }
+            ]
+        },
+        { "__name": "Style", "__children": [] },
+        { "__name": "Filler", "__children": null }
+    ]
+}"#;
+
+#[test]
+fn streaming_matches_tree_conversion() {
+    let json = temp_path("stream.json");
+    let streamed = temp_path("stream-streamed.bin");
+    let regular = temp_path("stream-regular.bin");
+    std::fs::write(&json, MAP_JSON).unwrap();
+
+    json_to_bin_streaming(&json, &streamed).unwrap();
+    json_to_bin(&json, &regular).unwrap();
+
+    let streamed_map = decode_map(&streamed).unwrap();
+    let regular_map = decode_map(&regular).unwrap();
+
+    for path in [&json, &streamed, &regular] {
+        std::fs::remove_file(path).unwrap();
+    }
+
+    assert_eq!(streamed_map, regular_map);
+}
+
+#[test]
+fn streaming_rejects_attributes_after_children() {
+    let json = temp_path("stream-order.json");
+    let bin = temp_path("stream-order.bin");
+    std::fs::write(&json, r#"{"__name": "Map", "__children": [], "package": "late"}"#).unwrap();
+
+    let result = json_to_bin_streaming(&json, &bin);
+
+    std::fs::remove_file(&json).unwrap();
+    let _ = std::fs::remove_file(&bin);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn bin_to_json_streaming_matches_tree_conversion() {
+    let json = temp_path("unstream.json");
+    let bin = temp_path("unstream.bin");
+    let streamed = temp_path("unstream-streamed.json");
+    let regular = temp_path("unstream-regular.json");
+    std::fs::write(&json, MAP_JSON).unwrap();
+    json_to_bin(&json, &bin).unwrap();
+
+    bin_to_json_streaming(&bin, &streamed).unwrap();
+    bin_to_json(&bin, &regular).unwrap();
+
+    let streamed_json: Value = serde_json::from_str(&std::fs::read_to_string(&streamed).unwrap()).unwrap();
+    let regular_json: Value = serde_json::from_str(&std::fs::read_to_string(&regular).unwrap()).unwrap();
+
+    for path in [&json, &bin, &streamed, &regular] {
+        std::fs::remove_file(path).unwrap();
+    }
+
+    assert_eq!(streamed_json, regular_json);
+    assert_eq!(streamed_json["package"], "streamed");
+}
+
+#[test]
+fn bin_to_json_streaming_reports_truncation() {
+    let json = temp_path("unstream-cut.json");
+    let bin = temp_path("unstream-cut.bin");
+    let out = temp_path("unstream-cut-out.json");
+    std::fs::write(&json, MAP_JSON).unwrap();
+    json_to_bin(&json, &bin).unwrap();
+
+    let bytes = std::fs::read(&bin).unwrap();
+    std::fs::write(&bin, &bytes[..bytes.len() - 10]).unwrap();
+
+    let result = bin_to_json_streaming(&bin, &out);
+
+    std::fs::remove_file(&json).unwrap();
+    std::fs::remove_file(&bin).unwrap();
+    let _ = std::fs::remove_file(&out);
+
+    let error = result.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+}
\ No newline at end of file
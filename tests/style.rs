@@ -0,0 +1,37 @@
+use cairn::{stylegrounds, DecodedElement, Styleground};
+
+fn parallax(texture: &str) -> DecodedElement {
+    let mut parallax = DecodedElement::new("parallax");
+    parallax.set_attribute("texture", texture);
+    parallax.set_attribute("scrollx", 0.5);
+    parallax
+}
+
+#[test]
+fn lists_stylegrounds_in_order() {
+    let mut apply = DecodedElement::new("apply");
+    apply.add_child(parallax("bgs/04/bg1"));
+    apply.add_child(parallax("bgs/04/bg2"));
+
+    let mut backgrounds = DecodedElement::new("Backgrounds");
+    backgrounds.add_child(parallax("bgs/04/sky"));
+    backgrounds.add_child(apply);
+
+    let mut foregrounds = DecodedElement::new("Foregrounds");
+    foregrounds.add_child(DecodedElement::new("snowFg"));
+
+    let mut style = DecodedElement::new("Style");
+    style.add_child(foregrounds);
+    style.add_child(backgrounds);
+
+    let mut map = DecodedElement::new("Map");
+    map.add_child(style);
+
+    let found: Vec<_> = stylegrounds(&map).into_iter().map(Styleground).collect();
+    let textures: Vec<_> = found.iter().map(|s| s.texture()).collect();
+
+    assert_eq!(textures, [Some("bgs/04/sky"), Some("bgs/04/bg1"), Some("bgs/04/bg2"), None]);
+    assert_eq!(found[0].scroll_x(), Some(0.5));
+    assert!(!found[3].is_parallax());
+    assert!(stylegrounds(&DecodedElement::new("Map")).is_empty());
+}
\ No newline at end of file
@@ -0,0 +1,103 @@
+mod common;
+
+use cairn::{
+    default_tile_chars, embed_tile_layers, extract_tile_layers, validate_tiles, DecodedElement, ObjectTileGrid, TileGrid,
+    TILE_FILE_ATTRIBUTE,
+};
+use common::{map_with_rooms, room};
+use serde_json::json;
+
+fn map_with_tiles() -> DecodedElement {
+    let mut solids = DecodedElement::new("solids");
+    solids.set_attribute("innerText", "0000\n0110");
+    let mut bg = DecodedElement::new("bg");
+    bg.set_attribute("innerText", "1111");
+
+    let mut room = room("a-00");
+    room.add_child(solids);
+    room.add_child(bg);
+    map_with_rooms([room])
+}
+
+#[test]
+fn extract_then_embed_restores_tiles() {
+    let dir = std::env::temp_dir().join(format!("cairn-tiles-{}", std::process::id()));
+    let map = map_with_tiles();
+
+    let extracted = extract_tile_layers(&map, &dir).unwrap();
+    assert!(extracted.is_dirty());
+    let room = &extracted.find_child("levels").unwrap().children.as_ref().unwrap()[0];
+    let solids = room.find_child("solids").unwrap();
+
+    assert_eq!(solids.attributes.get("innerText"), None);
+    assert_eq!(solids.attributes[TILE_FILE_ATTRIBUTE], json!("a-00.solids.txt"));
+    assert_eq!(std::fs::read_to_string(dir.join("a-00.solids.txt")).unwrap(), "0000\n0110");
+
+    let mut clean = extracted.clone();
+    clean.mark_clean();
+    let embedded = embed_tile_layers(&clean, &dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(embedded.is_dirty());
+    assert_eq!(embedded, map);
+}
+
+#[test]
+fn tile_files_outside_the_directory_are_rejected() {
+    let dir = std::env::temp_dir().join(format!("cairn-tiles-outside-{}", std::process::id()));
+
+    for file_name in ["../a-00.solids.txt", "rooms/../../a-00.solids.txt", "/etc/passwd"] {
+        let mut map = map_with_tiles();
+        let room = &mut map.children.as_mut().unwrap()[0].children.as_mut().unwrap()[0];
+        room.children.as_mut().unwrap()[0].set_attribute(TILE_FILE_ATTRIBUTE, file_name);
+
+        let error = embed_tile_layers(&map, &dir).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("outside the tile directory"), "{}", error);
+    }
+}
+
+#[test]
+fn tile_grid_reads_rows() {
+    let grid = TileGrid::parse("0000\r\n011\n");
+
+    assert_eq!(grid.height(), 2);
+    assert_eq!(grid.width(), 4);
+    assert_eq!(grid.get(1, 1), Some('1'));
+    assert_eq!(grid.get(3, 1), None);
+}
+
+#[test]
+fn validate_tiles_reports_unknown_characters() {
+    let mut map = map_with_tiles();
+    let room = &mut map.children.as_mut().unwrap()[0].children.as_mut().unwrap()[0];
+    room.children.as_mut().unwrap()[0].set_attribute("innerText", "00\n0Z");
+
+    let errors = validate_tiles(&map, &default_tile_chars());
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].room.as_str(), errors[0].layer.as_str()), ("a-00", "solids"));
+    assert_eq!((errors[0].x, errors[0].y, errors[0].tile), (1, 1, 'Z'));
+
+    let mut allowed = default_tile_chars();
+    allowed.insert('Z');
+    assert!(validate_tiles(&map, &allowed).is_empty());
+}
+
+#[test]
+fn object_tile_grid_round_trips() {
+    let mut grid = ObjectTileGrid::parse("-1,-1,12\n3,,4\n").unwrap();
+
+    assert_eq!((grid.width(), grid.height()), (3, 2));
+    assert_eq!(grid.get(2, 0), Some(12));
+    assert_eq!(grid.get(0, 0), None);
+    assert_eq!(grid.get(1, 1), None);
+    assert_eq!(grid.get(5, 5), None);
+
+    grid.set(4, 2, Some(7));
+    grid.set(2, 0, None);
+    let layer = grid.to_element("objtiles");
+
+    assert_eq!(layer.get_str("innerText"), Some("-1,-1,-1\n3,-1,4\n-1,-1,-1,-1,7"));
+    assert_eq!(ObjectTileGrid::from_element(&layer).unwrap(), grid);
+    assert!(ObjectTileGrid::parse("1,x").is_err());
+}
\ No newline at end of file
@@ -0,0 +1,294 @@
+mod common;
+
+use cairn::{
+    coerce_numeric_strings, collect_dialog_keys, compact_ids, drop_default_attributes, encoded_size, find_references_to,
+    find_references_to_in, geometry_only, sort_entities, truncate_long_strings, DecodedElement, Decoder, DroppedDefaults,
+    Encoder, Truncated, DIALOG_ATTRIBUTES, NUMERIC_ATTRIBUTES,
+};
+use common::map_with_rooms;
+use serde_json::json;
+use std::collections::HashMap;
+
+fn element_with_id(name: &str, id: i64) -> DecodedElement {
+    let mut element = DecodedElement::new(name);
+    element.set_attribute("id", id);
+    element
+}
+
+fn ids(container: &DecodedElement) -> Vec<serde_json::Value> {
+    container.children.iter().flatten().map(|e| e.attributes["id"].clone()).collect()
+}
+
+#[test]
+fn compact_ids_removes_gaps() {
+    let mut entities = DecodedElement::new("entities");
+    for id in [3, 100, 7] {
+        entities.add_child(element_with_id("spinner", id));
+    }
+    let mut triggers = DecodedElement::new("triggers");
+    triggers.add_child(element_with_id("cameraOffsetTrigger", 42));
+
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.add_child(entities);
+    room.add_child(triggers);
+    room.add_child(element_with_id("customNonEntity", 9));
+
+    let mut map = map_with_rooms([room]);
+    compact_ids(&mut map);
+
+    let room = &map.find_child("levels").unwrap().children.as_ref().unwrap()[0];
+    assert_eq!(ids(room.find_child("entities").unwrap()), [json!(1), json!(2), json!(3)]);
+    assert_eq!(ids(room.find_child("triggers").unwrap()), [json!(4)]);
+    assert_eq!(room.find_child("customNonEntity").unwrap().attributes["id"], json!(9));
+}
+
+#[test]
+fn finds_references_to_an_id() {
+    let mut gate = element_with_id("switchGate", 1);
+    gate.set_attribute("targetId", 5);
+    let mut other = element_with_id("spinner", 2);
+    other.set_attribute("linkedId", 5.0);
+    let mut custom = element_with_id("customLink", 3);
+    custom.set_attribute("follows", 5);
+
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(gate);
+    entities.add_child(other);
+    entities.add_child(custom);
+    entities.add_child(element_with_id("flag", 5));
+    let mut room = DecodedElement::new("level");
+    room.add_child(entities);
+    let map = map_with_rooms([room]);
+
+    let names: Vec<_> = find_references_to(&map, 5).iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["switchGate", "spinner"]);
+
+    let custom = find_references_to_in(&map, 5, &["follows"]);
+    assert_eq!(custom.len(), 1);
+    assert_eq!(custom[0].name, "customLink");
+}
+
+#[test]
+fn geometry_only_keeps_rooms_and_tiles() {
+    let mut solids = DecodedElement::new("solids");
+    solids.set_attribute("innerText", "11\n11");
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(element_with_id("spinner", 1));
+
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.set_attribute("x", 0);
+    room.set_attribute("width", 320);
+    room.set_attribute("music", "event:/music/lvl1/main");
+    room.add_child(solids);
+    room.add_child(entities);
+
+    let mut map = map_with_rooms([room]);
+    map.set_attribute("package", "geo");
+    map.add_child(DecodedElement::new("Style"));
+
+    let stripped = geometry_only(&map);
+
+    assert_eq!(stripped.get_str("package"), Some("geo"));
+    assert_eq!(stripped.children.as_ref().unwrap().len(), 1);
+    let room = &stripped.find_child("levels").unwrap().children.as_ref().unwrap()[0];
+    assert_eq!(room.get_i64("width"), Some(320));
+    assert_eq!(room.get_str("music"), None);
+    assert_eq!(room.children.as_ref().unwrap().len(), 1);
+    assert_eq!(room.find_child("solids").unwrap().get_str("innerText"), Some("11\n11"));
+}
+
+#[test]
+fn coerces_numeric_strings_of_known_attributes() {
+    let mut entity = DecodedElement::new("spring");
+    entity.set_attribute("x", " 32 ");
+    entity.set_attribute("y", "-8");
+    entity.set_attribute("scaleX", "1.5");
+    entity.set_attribute("width", "wide");
+    entity.set_attribute("label", "12");
+
+    let mut room = DecodedElement::new("level");
+    room.add_child(entity);
+    let mut map = map_with_rooms([room]);
+
+    assert_eq!(coerce_numeric_strings(&mut map, NUMERIC_ATTRIBUTES), 3);
+
+    let entity = &map.children.as_ref().unwrap()[0].children.as_ref().unwrap()[0].children.as_ref().unwrap()[0];
+    assert_eq!(entity.attributes["x"], json!(32));
+    assert_eq!(entity.attributes["y"], json!(-8));
+    assert_eq!(entity.attributes["scaleX"], json!(1.5));
+    assert_eq!(entity.attributes["width"], json!("wide"));
+    assert_eq!(entity.attributes["label"], json!("12"));
+}
+
+#[test]
+fn collects_dialog_keys_across_rooms() {
+    let mut sign = DecodedElement::new("memorial");
+    sign.set_attribute("dialog", "MEMORIAL");
+    let mut trigger = DecodedElement::new("everest/dialogTrigger");
+    trigger.set_attribute("dialogId", "CH1_INTRO");
+    let mut npc = DecodedElement::new("customNPC");
+    npc.set_attribute("dialogId", "CH1_INTRO");
+    npc.set_attribute("text", "");
+    npc.set_attribute("sprite", "granny");
+
+    let mut room = DecodedElement::new("level");
+    room.add_child(sign);
+    room.add_child(trigger);
+    room.add_child(npc);
+    let map = map_with_rooms([room]);
+
+    let keys: Vec<_> = collect_dialog_keys(&map, DIALOG_ATTRIBUTES).into_iter().collect();
+    assert_eq!(keys, ["CH1_INTRO", "MEMORIAL"]);
+    assert_eq!(collect_dialog_keys(&map, &["sprite"]).len(), 1);
+}
+
+#[test]
+fn sorts_entities_but_not_decals() {
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(element_with_id("spring", 7));
+    entities.add_child(DecodedElement::new("player"));
+    entities.add_child(element_with_id("spinner", 2));
+    entities.add_child(element_with_id("refill", 2));
+    let mut decals = DecodedElement::new("fgdecals");
+    for texture in ["b", "a"] {
+        let mut decal = DecodedElement::new("decal");
+        decal.set_attribute("texture", texture);
+        decals.add_child(decal);
+    }
+
+    let mut room = DecodedElement::new("level");
+    room.add_child(entities);
+    room.add_child(decals);
+    let mut map = map_with_rooms([room]);
+    map.set_attribute("package", "sorted");
+
+    let shuffled = Encoder::new().sort_entities(true).encode_to_vec(&map).unwrap();
+    sort_entities(&mut map);
+    let plain = Encoder::new().encode_to_vec(&map).unwrap();
+
+    let room = &map.children.as_ref().unwrap()[0].children.as_ref().unwrap()[0];
+    let entities = room.find_child("entities").unwrap();
+    let decals = room.find_child("fgdecals").unwrap();
+    let names: Vec<_> = entities.children.iter().flatten().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["refill", "spinner", "spring", "player"]);
+    assert_eq!(decals.children.as_ref().unwrap()[0].get_str("texture"), Some("b"));
+
+    // Sorting while encoding writes the same tree as sorting beforehand
+    let from_encoder = Decoder::new().decode_from_reader(&shuffled[..]).unwrap();
+    let from_transform = Decoder::new().decode_from_reader(&plain[..]).unwrap();
+    assert_eq!(from_encoder, from_transform);
+}
+
+#[test]
+fn truncates_long_strings_and_reports_the_cut() {
+    let mut path = DecodedElement::new("customPath");
+    path.set_attribute("nodes", "0,0;8,8;16,16;24,24");
+    path.set_attribute("flag", "ok");
+    path.set_attribute("label", "éééééé");
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(path);
+    let mut solids = DecodedElement::new("solids");
+    solids.set_attribute("innerText", "0000000000\n0011111100");
+
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.add_child(entities);
+    room.add_child(solids);
+    let mut map = map_with_rooms([room]);
+    map.mark_clean();
+
+    let truncated = truncate_long_strings(&mut map, 4);
+
+    let path = vec!["Map".to_string(), "levels".to_string(), "a-00".to_string(), "entities".to_string()];
+    assert_eq!(
+        truncated,
+        [
+            Truncated {
+                path: path.clone(),
+                element: "customPath".to_string(),
+                key: "label".to_string(),
+                original_len: 6,
+                removed: "éé".to_string(),
+            },
+            Truncated {
+                path,
+                element: "customPath".to_string(),
+                key: "nodes".to_string(),
+                original_len: 19,
+                removed: "8,8;16,16;24,24".to_string(),
+            },
+        ],
+    );
+
+    let room = &map.children.as_ref().unwrap()[0].children.as_ref().unwrap()[0];
+    let entity = room.entities().next().unwrap();
+    assert_eq!(entity.get_str("nodes"), Some("0,0;"));
+    assert_eq!(entity.get_str("flag"), Some("ok"));
+    assert_eq!(room.find_child("solids").unwrap().get_str("innerText").unwrap().len(), 21);
+    assert!(map.is_dirty());
+
+    assert!(truncate_long_strings(&mut map, 4).is_empty());
+}
+
+#[test]
+fn drops_attributes_left_at_their_default() {
+    let mut spinner = DecodedElement::new("spinner");
+    spinner.set_attribute("x", 8);
+    spinner.set_attribute("attachToSolid", false);
+    spinner.set_attribute("color", "Blue");
+    spinner.set_attribute("scale", 1);
+    let mut fast_spinner = spinner.clone();
+    fast_spinner.set_attribute("color", "Red");
+    fast_spinner.set_attribute("scale", 1.5);
+    let mut odd_spinner = spinner.clone();
+    odd_spinner.set_attribute("attachToSolid", 0);
+    odd_spinner.set_attribute("scale", "1");
+    let mut cloud = DecodedElement::new("cloud");
+    cloud.set_attribute("fragile", false);
+
+    let mut entities = DecodedElement::new("entities");
+    for entity in [spinner, fast_spinner, odd_spinner, cloud] {
+        entities.add_child(entity);
+    }
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.add_child(entities);
+    let mut map = map_with_rooms([room]);
+    map.set_attribute("package", "minify");
+    map.mark_clean();
+
+    let defaults = HashMap::from([(
+        "spinner".to_string(),
+        HashMap::from([
+            ("attachToSolid".to_string(), json!(false)),
+            ("color".to_string(), json!("Blue")),
+            ("scale".to_string(), json!(1.0)),
+        ]),
+    )]);
+    let before = encoded_size(&map).unwrap();
+    let dropped = drop_default_attributes(&mut map, &defaults).unwrap();
+
+    assert_eq!(dropped.attributes, 5);
+    assert_eq!(dropped.bytes, before - encoded_size(&map).unwrap());
+    assert!(dropped.bytes > 0);
+    assert!(map.is_dirty());
+
+    let entities = map.find_child("levels").and_then(|levels| levels.find_child("level")).unwrap();
+    let entities = entities.find_child("entities").unwrap();
+    let keys: Vec<Vec<&str>> = entities
+        .children
+        .iter()
+        .flatten()
+        .map(|entity| {
+            let mut keys: Vec<_> = entity.attributes.keys().map(String::as_str).collect();
+            keys.sort();
+            keys
+        })
+        .collect();
+    assert_eq!(keys, [vec!["x"], vec!["color", "scale", "x"], vec!["attachToSolid", "scale", "x"], vec!["fragile"]]);
+
+    // A second pass has nothing left to remove
+    assert_eq!(drop_default_attributes(&mut map, &defaults).unwrap(), DroppedDefaults::default());
+}
\ No newline at end of file
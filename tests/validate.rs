@@ -0,0 +1,116 @@
+mod common;
+
+use std::collections::HashSet;
+
+use cairn::{
+    check_decal_textures, check_entity_positions, check_entity_positions_with_threshold, check_float_precision,
+    validate_map, DecodedElement, Severity,
+};
+use common::{map_with_rooms, room};
+
+fn decal(texture: &str) -> DecodedElement {
+    let mut decal = DecodedElement::new("decal");
+    decal.set_attribute("texture", texture);
+    decal
+}
+
+#[test]
+fn reports_unknown_decal_textures() {
+    let mut fg = DecodedElement::new("fgdecals");
+    fg.add_child(decal("decals\\1-forsakencity\\flag.png"));
+    fg.add_child(decal("1-forsakencity/missing"));
+
+    let mut room = room("a-00");
+    room.add_child(fg);
+    let map = map_with_rooms([room]);
+
+    let known: HashSet<String> = ["decals/1-forsakencity/flag".to_string()].into();
+    let missing = check_decal_textures(&map, &known);
+
+    assert_eq!(missing.len(), 1);
+    assert_eq!(missing[0].room, "a-00");
+    assert_eq!(missing[0].layer, "fgdecals");
+    assert_eq!(missing[0].texture, "1-forsakencity/missing");
+}
+
+fn map_with_package(package: &str) -> DecodedElement {
+    let mut map = map_with_rooms([room("a-00")]);
+    map.set_attribute("package", package);
+    map
+}
+
+#[test]
+fn warns_about_malformed_package() {
+    let issues = validate_map(&map_with_package("My Mod\\1-Intro.bin"));
+
+    assert_eq!(issues.len(), 3);
+    assert!(issues.iter().all(|issue| issue.severity == Severity::Warning));
+    assert!(validate_map(&map_with_package("MyMod/1-Intro")).is_empty());
+}
+
+#[test]
+fn set_package_normalizes() {
+    let mut map = map_with_package("");
+    map.set_package(" My Mod\\\\1-Intro?.bin ");
+
+    assert_eq!(map.get_str("package"), Some("My_Mod/1-Intro"));
+    assert!(validate_map(&map).is_empty());
+}
+
+#[test]
+fn float_precision_flags_values_f32_cannot_hold() {
+    let mut spinner = DecodedElement::new("spinner");
+    spinner.set_attribute("x", 0.1 + 0.2);
+    spinner.set_attribute("y", 0.5);
+    spinner.set_attribute("id", 16_777_217);
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(DecodedElement::new("player"));
+    entities.add_child(spinner);
+    let mut map = DecodedElement::new("Map");
+    map.add_child(entities);
+
+    let warnings = check_float_precision(&map);
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].path, "/__children/0/__children/1/x");
+    assert_eq!(warnings[0].value, 0.30000000000000004);
+    assert_eq!(warnings[0].stored, 0.3f32 as f64);
+    assert!(warnings[0].error.abs() < 1e-7);
+}
+
+#[test]
+fn reports_entities_far_outside_their_room() {
+    let spinner = |id: i64, x: i64, y: i64| {
+        let mut spinner = DecodedElement::new("spinner");
+        spinner.set_attribute("id", id);
+        spinner.set_attribute("x", x);
+        spinner.set_attribute("y", y);
+        spinner
+    };
+
+    let mut entities = DecodedElement::new("entities");
+    entities.add_child(spinner(1, 160, 90));
+    entities.add_child(spinner(2, 360, 90));
+    entities.add_child(spinner(3, 320 + 300, 184 + 400));
+
+    let mut room = DecodedElement::new("level");
+    room.set_attribute("name", "a-00");
+    room.set_attribute("x", 1000);
+    room.set_attribute("y", 2000);
+    room.set_attribute("width", 320);
+    room.set_attribute("height", 184);
+    room.add_child(entities);
+
+    let mut levels = DecodedElement::new("levels");
+    levels.add_child(room);
+    let mut map = DecodedElement::new("Map");
+    map.add_child(levels);
+
+    let found = check_entity_positions(&map);
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].room.as_str(), found[0].entity.as_str(), found[0].id), ("a-00", "spinner", Some(3)));
+    assert_eq!(found[0].distance, 500.0);
+
+    let strict = check_entity_positions_with_threshold(&map, 10.0);
+    assert_eq!(strict.iter().map(|e| e.id).collect::<Vec<_>>(), [Some(2), Some(3)]);
+}
\ No newline at end of file
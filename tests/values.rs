@@ -0,0 +1,82 @@
+use cairn::{decode_value, number_type_code};
+use serde_json::{json, Number, Value};
+
+fn type_code(value: serde_json::Value) -> Option<u8> {
+    number_type_code(value.as_number().unwrap())
+}
+
+#[test]
+fn integer_boundaries() {
+    assert_eq!(type_code(json!(-32769)), Some(3));
+    assert_eq!(type_code(json!(-32768)), Some(2));
+    assert_eq!(type_code(json!(-1)), Some(2));
+    assert_eq!(type_code(json!(0)), Some(1));
+    assert_eq!(type_code(json!(127)), Some(1));
+    assert_eq!(type_code(json!(128)), Some(1));
+    assert_eq!(type_code(json!(255)), Some(1));
+    assert_eq!(type_code(json!(256)), Some(2));
+    assert_eq!(type_code(json!(32767)), Some(2));
+    assert_eq!(type_code(json!(32768)), Some(3));
+    assert_eq!(type_code(json!(i32::MAX)), Some(3));
+    assert_eq!(type_code(json!(i32::MIN)), Some(3));
+    assert_eq!(type_code(json!(i64::from(i32::MAX) + 1)), Some(4));
+}
+
+#[test]
+fn whole_floats_stay_floats() {
+    assert_eq!(type_code(json!(0.0)), Some(4));
+    assert_eq!(type_code(json!(200.0)), Some(4));
+    assert_eq!(type_code(json!(-1.0)), Some(4));
+    assert_eq!(type_code(json!(1.5)), Some(4));
+}
+
+#[test]
+fn out_of_f32_range() {
+    let too_big = Number::from_f64(f64::from(f32::MAX) * 2.0).unwrap();
+    assert_eq!(number_type_code(&too_big), None);
+}
+
+fn decode(type_byte: u8, bytes: &[u8]) -> std::io::Result<Value> {
+    let lookup = ["spikes".to_string()];
+    let mut reader = bytes;
+    let value = decode_value(type_byte, &lookup, &mut reader)?;
+    assert!(reader.is_empty(), "type {} left {} bytes unread", type_byte, reader.len());
+    Ok(value)
+}
+
+#[test]
+fn decodes_each_value_type() {
+    assert_eq!(decode(0, &[1]).unwrap(), json!(true));
+    assert_eq!(decode(1, &[200]).unwrap(), json!(200));
+    assert_eq!(decode(2, &(-300i16).to_le_bytes()).unwrap(), json!(-300));
+    assert_eq!(decode(3, &70000i32.to_le_bytes()).unwrap(), json!(70000));
+    assert_eq!(decode(4, &1.5f32.to_le_bytes()).unwrap(), json!(1.5));
+    assert_eq!(decode(4, &f32::NAN.to_le_bytes()).unwrap(), Value::Null);
+    assert_eq!(decode(5, &[0, 0]).unwrap(), json!("spikes"));
+    assert_eq!(decode(6, b"\x03abc").unwrap(), json!("abc"));
+    assert_eq!(decode(7, &[4, 0, 3, b'0', 1, b'1']).unwrap(), json!("0001"));
+}
+
+#[test]
+fn rejects_bad_value_bytes() {
+    assert!(decode(5, &[1, 0]).is_err());
+    assert!(decode(3, &[0, 0]).is_err());
+    assert!(decode(9, &[]).is_err());
+}
+
+#[test]
+fn run_length_values_at_the_edges() {
+    let rle = |runs: &[u8]| {
+        let mut bytes = (runs.len() as u16).to_le_bytes().to_vec();
+        bytes.extend_from_slice(runs);
+        decode_value(7, &[], &mut &bytes[..]).unwrap()
+    };
+
+    assert_eq!(rle(&[]), json!(""));
+    assert_eq!(rle(&[0, b'x']), json!(""));
+    assert_eq!(rle(&[0, b'x', 2, b'0', 0, b'y']), json!("00"));
+    assert_eq!(rle(&[255, b'1']), json!("1".repeat(255)));
+
+    // High bytes are code points, not UTF-8
+    assert_eq!(rle(&[1, 0x80, 2, 0xe9, 1, 0xff]), json!("\u{80}\u{e9}\u{e9}\u{ff}"));
+}
\ No newline at end of file